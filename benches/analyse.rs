@@ -0,0 +1,62 @@
+//! Times `Calldata::analyse_data` on a 64 KB zero-dominated payload against the byte by byte
+//! zero scans it used to run at every byte. Run with `cargo bench --bench analyse`.
+
+use std::{hint::black_box, time::Instant};
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{Bytes32, Calldata};
+
+const PAYLOAD_SIZE: usize = 64 * 1024;
+const ROUNDS: u32 = 20;
+
+// mostly abi words of small integers: a non-zero byte ends every 32 zero bytes or so
+fn payload() -> Bytes {
+    let mut data = vec![0x00; PAYLOAD_SIZE];
+    for i in (31..PAYLOAD_SIZE).step_by(97) {
+        data[i] = 0x01;
+    }
+    Bytes::from(data)
+}
+
+// what the zero run and copy analysis of every byte scanned before the precomputed runs
+fn scan_zeros(data: &[u8]) -> usize {
+    let mut total = 0;
+    for n in 0..data.len() {
+        let mut end = n;
+        while data.get(end) == Some(&0x00) && end - n < 64 {
+            end += 1;
+        }
+        total += end - n;
+        let mut end = n;
+        while data.get(end) == Some(&0x00) && end - n <= 32 {
+            end += 1;
+        }
+        total += end - n;
+    }
+    total
+}
+
+fn time(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    println!(
+        "{name}: {elapsed:?} per round, {:.2} ns/byte",
+        elapsed.as_nanos() as f64 / PAYLOAD_SIZE as f64
+    );
+}
+
+fn main() {
+    let data = payload();
+    let mut calldata = Calldata::new(data.clone(), Bytes32::default(), Bytes32::default()).unwrap();
+    time("analyse_data", || {
+        calldata.analyse_data();
+        black_box(&calldata.bytes_info);
+    });
+    time("byte by byte zero scans", || {
+        black_box(scan_zeros(black_box(&data)));
+    });
+}
@@ -0,0 +1,38 @@
+//! Times `Calldata::compress` of the `test-data/calldata.json` fixture, whose 72 KB make the DP
+//! of `OptimalDpPlanner` the bulk of the work. Run with `cargo bench --bench compress`.
+
+use std::{hint::black_box, time::Instant};
+
+use alloy::primitives::Bytes;
+use calldata_compressor::Calldata;
+
+const ROUNDS: u32 = 10;
+
+fn main() {
+    let fixture: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("test-data/calldata.json").unwrap()).unwrap();
+    let data: Bytes = fixture["uncompress"].as_str().unwrap().parse().unwrap();
+    let mut calldata = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+    calldata.init_dict(&[]);
+    calldata.analyse();
+
+    let plan = || {
+        black_box(
+            calldata
+                .compress_analysed(&Default::default())
+                .unwrap()
+                .compressed_data,
+        )
+    };
+    plan();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        plan();
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    println!(
+        "compress_analysed of {} bytes: {elapsed:?} per round, {:.2} ns/byte",
+        data.len(),
+        elapsed.as_nanos() as f64 / data.len() as f64
+    );
+}
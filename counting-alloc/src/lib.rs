@@ -0,0 +1,77 @@
+//! Counting global allocator of tests/alloc_budget.rs. `GlobalAlloc` can only be implemented
+//! unsafely, and `unsafe_code` is forbidden in calldata-compressor, so it lives in this crate.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The `System` allocator, counting allocations and live heap bytes for `track`
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn record_alloc(size: usize) {
+        if TRACKING.load(Ordering::Relaxed) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(size: usize) {
+        LIVE_BYTES.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+// SAFETY: every call is forwarded to `System` as is, the bookkeeping doesn't allocate
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::record_dealloc(layout.size());
+            Self::record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// What `track` measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub allocations: usize, // reallocations included
+    pub peak_bytes: usize,  // of live heap bytes, over those live when tracking started
+}
+
+/// Runs `f`, counting its allocations when `CountingAlloc` is the global allocator. Allocations
+/// of other threads meanwhile are counted too.
+pub fn track<T>(f: impl FnOnce() -> T) -> (T, Usage) {
+    let baseline = LIVE_BYTES.load(Ordering::Relaxed);
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    PEAK_BYTES.store(baseline, Ordering::Relaxed);
+    TRACKING.store(true, Ordering::Relaxed);
+    let value = f();
+    TRACKING.store(false, Ordering::Relaxed);
+    let usage = Usage {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed) - baseline,
+    };
+    (value, usage)
+}
@@ -0,0 +1,55 @@
+//! Vendors the calldata of the mainnet transactions listed in `test-data/corpus/manifest.txt`
+//! into `test-data/corpus/<hash>.json` as test vectors compressed without a dictionary, where
+//! the corpus test picks them up.
+//!
+//! ```sh
+//! CORPUS_RPC_URL=<rpc url> cargo run --example fetch_corpus
+//! ```
+//!
+//! It refuses to run without `CORPUS_RPC_URL` so nothing accidentally reaches the network,
+//! `cargo test` only ever reads what is already vendored.
+use std::{fs, path::Path};
+
+use alloy::{
+    consensus::Transaction,
+    primitives::TxHash,
+    providers::{Provider, ProviderBuilder},
+};
+use calldata_compressor::{Bytes32, Calldata, CompressOptions, Dict, TestVector};
+
+const CORPUS_DIR: &str = "test-data/corpus";
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(url) = std::env::var("CORPUS_RPC_URL") else {
+        eprintln!("CORPUS_RPC_URL is not set, refusing to fetch the corpus");
+        std::process::exit(1);
+    };
+    let provider = ProviderBuilder::new().on_http(url.parse()?);
+
+    let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[]);
+    let opts = CompressOptions::default();
+    let manifest = fs::read_to_string(Path::new(CORPUS_DIR).join("manifest.txt"))?;
+    let hashes = manifest
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty());
+    for hash in hashes {
+        let hash: TxHash = hash.parse()?;
+        let path = Path::new(CORPUS_DIR).join(format!("{hash}.json"));
+        if path.exists() {
+            continue;
+        }
+        let tx = provider
+            .get_transaction_by_hash(hash)
+            .await?
+            .ok_or_else(|| format!("transaction {hash} not found"))?;
+        let mut calldata =
+            Calldata::new(tx.input().clone(), dict.wallet_addr(), dict.contract_addr())?;
+        calldata.set_dict(&dict);
+        let result = calldata.compress_with(&opts)?;
+        TestVector::from_result(&hash.to_string(), &dict, &opts, &result).save(&path)?;
+        println!("vendored {hash} ({} bytes)", tx.input().len());
+    }
+    Ok(())
+}
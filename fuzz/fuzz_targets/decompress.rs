@@ -0,0 +1,33 @@
+//! Arbitrary bytes fed to the decompressor, as compressed calldata from untrusted sources: it
+//! returns the decoded bytes or a `CompressorError`, and never panics.
+
+#![no_main]
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{decompress, decompress_with, CompressOptions, Dict};
+use libfuzzer_sys::fuzz_target;
+
+// no instruction expands to more than the 64 bytes of a `0x3f` zero run
+const MAX_EXPANSION: usize = 64;
+
+fuzz_target!(|data: &[u8]| {
+    let (wallet, contract) = ([0xaa; 32], [0xbb; 32]);
+    let dict = [[0x11; 32], [0x22; 32], [0x33; 32]];
+
+    if let Ok(out) = decompress(&Bytes::copy_from_slice(data), &wallet, &contract, &dict) {
+        assert!(out.len() <= MAX_EXPANSION * data.len());
+    }
+
+    // the first byte picks the checksum and reversed order of the rest
+    if let Some((&flags, stream)) = data.split_first() {
+        let opts = CompressOptions {
+            checksum: flags & 1 != 0,
+            reverse: flags & 2 != 0,
+            ..CompressOptions::default()
+        };
+        let dict = Dict::new(wallet, contract, &dict);
+        if let Ok(out) = decompress_with(stream, &dict, &opts) {
+            assert!(out.len() <= MAX_EXPANSION * stream.len());
+        }
+    }
+});
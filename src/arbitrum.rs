@@ -0,0 +1,104 @@
+use crate::{cost::CostModel, op_stack::flz_compress_len};
+
+/// L1 units of ArbOS per byte of compressed calldata, the non-zero calldata gas of L1
+pub const UNITS_PER_BYTE: u64 = 16;
+
+/// L1 component of the fee of a transaction on Arbitrum, in wei. ArbOS charges the size of the
+/// transaction after a fast brotli pass; the FastLZ size of `flz_compress_len` stands in for
+/// it, both only find the repetitions of the input. `price_per_byte` is the L1 price of a
+/// compressed byte, 16 times the `L1PricePerUnit` of ArbOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArbitrumCostModel {
+    pub price_per_byte: u64,
+}
+
+impl ArbitrumCostModel {
+    pub fn new(price_per_byte: u64) -> Self {
+        Self { price_per_byte }
+    }
+
+    /// Model with the price of an ArbOS unit, as read from `ArbGasInfo.getL1BaseFeeEstimate`
+    pub fn from_price_per_unit(price_per_unit: u64) -> Self {
+        Self::new(price_per_unit.saturating_mul(UNITS_PER_BYTE))
+    }
+
+    /// estimated size of `calldata` after the compression of the sequencer
+    pub fn compressed_len(calldata: &[u8]) -> usize {
+        flz_compress_len(calldata)
+    }
+
+    /// L1 fee of sending `calldata`
+    pub fn l1_fee(&self, calldata: &[u8]) -> u64 {
+        (Self::compressed_len(calldata) as u64).saturating_mul(self.price_per_byte)
+    }
+}
+
+impl CostModel for ArbitrumCostModel {
+    fn data_cost(&self, bytes: &[u8]) -> u64 {
+        self.l1_fee(bytes)
+    }
+
+    // the decompressor runs on L2, its execution is priced apart from the L1 fee
+    fn transaction_cost(&self, calldata: &[u8], _decompression: Option<u64>) -> u64 {
+        self.l1_fee(calldata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::compressor::{compress, Bytes32};
+
+    const SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+    // pseudo-random words, nothing for the sequencer to compress
+    fn random_words(count: usize) -> Vec<Bytes32> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..count)
+            .map(|_| {
+                let mut word = Bytes32::default();
+                for b in word.iter_mut() {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    *b = (state >> 56) as u8;
+                }
+                word
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arbitrum_cost_model() {
+        let model = ArbitrumCostModel::new(1_000);
+        assert_eq!(
+            ArbitrumCostModel::from_price_per_unit(62),
+            ArbitrumCostModel::new(992)
+        );
+        assert_eq!(model.l1_fee(&[]), 0);
+        assert_eq!(model.l1_fee(&SELECTOR), 5 * 1_000);
+
+        // the same word over and over: the sequencer compresses it better than we do, our copies
+        // of it are only noise to its compression
+        let word = random_words(1)[0];
+        let repetitive = Bytes::from([SELECTOR.as_slice(), &word.repeat(40)].concat());
+        let result = compress(repetitive.clone(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let report = result.evaluate(&model);
+        assert!(
+            report.before < repetitive.len() as u64 * 1_000 / 10,
+            "{report:?}"
+        );
+        assert!(report.saved <= 0, "{report:?}");
+
+        // words only the dictionary knows, which no compression of the calldata finds
+        let words = random_words(40);
+        let entropic = Bytes::from([SELECTOR.as_slice(), &words.concat()].concat());
+        let result = compress(entropic.clone(), [0xaa; 32], [0xbb; 32], &words).unwrap();
+        let report = result.evaluate(&model);
+        assert!(report.before >= entropic.len() as u64 * 1_000, "{report:?}");
+        assert!(report.saved > report.before as i64 / 2, "{report:?}");
+        assert!(result.is_worth_it(&model, u64::MAX / 2));
+    }
+}
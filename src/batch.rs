@@ -0,0 +1,207 @@
+#![allow(clippy::cast_possible_wrap)]
+use alloy::primitives::Bytes;
+
+use crate::{
+    compressor::{Bytes32, CompressResult},
+    corpus::WORD_SIZE,
+    dict::{word_frequencies, Dict},
+    errors::CompressorError,
+    options::CompressOptions,
+    session::Compressor,
+};
+
+/// A batch compressed against a dictionary extended with words repeated inside the batch
+#[derive(Debug, Clone)]
+pub struct AdaptiveBatch {
+    pub delta: Vec<Bytes32>, // entries appended to the base dictionary, in index order
+    pub results: Vec<CompressResult>, // in input order
+}
+
+impl AdaptiveBatch {
+    // the extended dictionary a decompressor rebuilds from the base dictionary and the delta
+    pub fn dict(&self, base: &Dict) -> Dict {
+        base.extended(&self.delta)
+    }
+}
+
+/// Compresses every payload against the same dictionary, built once for the whole batch, with
+/// the default options. Results are in input order, a payload that fails to compress doesn't
+/// abort the others.
+///
+/// `wallet_addr` fills the reserved slot 0 of the shared dictionary, so the batch only suits
+/// payloads of a single signer: the decompressor puts the sender of each transaction in that
+/// slot and a payload of another signer must not reference it. Payloads of several signers
+/// share everything but slot 0, compress them with `Calldata::retarget_wallet` on one shared
+/// dictionary, which replaces the wallet without copying or rebuilding the lookup.
+pub fn compress_batch(
+    calldatas: &[Bytes],
+    wallet_addr: &Bytes32,
+    contract_addr: &Bytes32,
+    dict: &[Bytes32],
+) -> Vec<Result<CompressResult, CompressorError>> {
+    let compressor = Compressor::new(
+        Dict::new(*wallet_addr, *contract_addr, dict),
+        CompressOptions::default(),
+    );
+    calldatas
+        .iter()
+        .map(|data| compressor.compress(data))
+        .collect()
+}
+
+/// Compresses a batch against `dict` extended with up to `max_delta` words that repeat across
+/// the batch, so that intra-batch redundancy missed by the static dictionary is captured too.
+///
+/// A word is added when referencing it saves more bytes over its occurrences than the 32 bytes
+/// it costs to post it in the delta. The most valuable words come first so they keep the
+/// smallest indices.
+pub fn compress_batch_adaptive(
+    calldatas: &[Bytes],
+    dict: &Dict,
+    max_delta: usize,
+    opts: &CompressOptions,
+) -> Result<AdaptiveBatch, CompressorError> {
+    let mut candidates: Vec<(i64, Bytes32)> = word_frequencies(calldatas.iter().map(|c| &c[..]))
+        .into_iter()
+        .filter(|(word, _)| dict.index_of(word).is_none())
+        .filter_map(|(word, count)| {
+            // a copy emits the word without its leading zeros, a storage reference 2 bytes
+            let literal_len = WORD_SIZE - word.iter().take_while(|&&b| b == 0x00).count();
+            let saved = count as i64 * (literal_len as i64 + 1 - 2) - WORD_SIZE as i64;
+            (saved > 0).then_some((saved, word))
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let delta: Vec<Bytes32> = candidates
+        .into_iter()
+        .take(max_delta)
+        .map(|(_, word)| word)
+        .collect();
+
+    let compressor = Compressor::new(dict.extended(&delta), opts.clone());
+    let results = calldatas
+        .iter()
+        .map(|data| compressor.compress(data))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(AdaptiveBatch { delta, results })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::Address;
+
+    use super::*;
+    use crate::compressor::{decompress_with, Calldata};
+
+    #[test]
+    fn test_compress_batch() {
+        let wallet = [0xaa; 32];
+        let contract = [0xbb; 32];
+        let word = [0x42; 32];
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let calldatas = [
+            Bytes::from([selector.as_slice(), &word].concat()),
+            Bytes::new(),
+            // no word of the dictionary
+            Bytes::from([selector.as_slice(), &[0x07; 32]].concat()),
+            Bytes::from([selector.as_slice(), &wallet, &word].concat()),
+        ];
+
+        let results = compress_batch(&calldatas, &wallet, &contract, &[word]);
+        assert_eq!(results.len(), calldatas.len());
+        for (result, data) in results.iter().zip(&calldatas) {
+            let result = result.as_ref().unwrap();
+            assert_eq!(&result.uncompressed_data, data);
+            let single = crate::compressor::compress(data.clone(), wallet, contract, &[word]);
+            assert_eq!(result.compressed_data, single.unwrap().compressed_data);
+        }
+        assert!(results[1].as_ref().unwrap().compressed_data.is_empty());
+
+        assert!(compress_batch(&[], &wallet, &contract, &[word]).is_empty());
+    }
+
+    #[test]
+    fn test_compress_batch_signers() {
+        let signers = [0x11, 0x22, 0x33].map(Address::repeat_byte);
+        let contract = [0xbb; 32];
+        let token = [0x42; 32];
+        // transfers back to their signer
+        let calldatas = signers.map(|signer| {
+            Bytes::from(
+                [
+                    [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                    &signer.into_word()[..],
+                    &token,
+                ]
+                .concat(),
+            )
+        });
+
+        // the batch references the wallet of the first signer only
+        let wallet = signers[0].into_word().0;
+        let results = compress_batch(&calldatas, &wallet, &contract, &[token]);
+        let references: Vec<usize> = results
+            .iter()
+            .map(|result| result.as_ref().unwrap().stats().storage_references)
+            .collect();
+        assert_eq!(references, [2, 1, 1]);
+
+        // one dictionary, retargeted to the signer of each payload
+        let shared = Arc::new(Dict::new(wallet, contract, &[token]));
+        for (signer, data) in signers.into_iter().zip(&calldatas) {
+            let view = shared.retarget_wallet(signer);
+            let mut cb =
+                Calldata::new(data.clone(), view.wallet_addr(), view.contract_addr()).unwrap();
+            cb.set_shared_dict(Arc::clone(&shared));
+            cb.retarget_wallet(signer);
+            let result = cb.compress().unwrap();
+            assert_eq!(result.stats().storage_references, 2, "{signer}");
+            assert_eq!(
+                decompress_with(
+                    &result.compressed_data,
+                    &view.to_dict(),
+                    &CompressOptions::default()
+                )
+                .as_ref(),
+                Ok(data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compress_batch_adaptive() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let shared: Bytes32 = core::array::from_fn(|i| i as u8 + 1);
+        let calldatas: Vec<Bytes> = (1..=3_u8)
+            .map(|i| Bytes::from([selector.as_slice(), &shared, &[i; 32]].concat()))
+            .collect();
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[]);
+        let opts = CompressOptions::default();
+
+        let batch = compress_batch_adaptive(&calldatas, &dict, 8, &opts).unwrap();
+        // only the shared word repeats often enough to pay for itself
+        assert_eq!(batch.delta, vec![shared]);
+
+        let extended = batch.dict(&dict);
+        assert_eq!(extended.index_of(&shared), Some(2));
+        for (result, data) in batch.results.iter().zip(&calldatas) {
+            let plain = crate::compressor::compress(
+                data.clone(),
+                Bytes32::default(),
+                Bytes32::default(),
+                &[],
+            )
+            .unwrap();
+            assert!(result.compressed_data.len() < plain.compressed_data.len());
+            assert_eq!(
+                decompress_with(&result.compressed_data, &extended, &opts).as_ref(),
+                Ok(data)
+            );
+        }
+
+        let no_delta = compress_batch_adaptive(&calldatas, &dict, 0, &opts).unwrap();
+        assert!(no_delta.delta.is_empty());
+    }
+}
@@ -0,0 +1,356 @@
+use alloy::{
+    network::Network,
+    primitives::{Address, Bytes, U256},
+    providers::Provider,
+    transports::Transport,
+};
+
+use crate::{
+    compressor::{Bytes32, Calldata},
+    dict::{Dict, DriftReport},
+    errors::CompressorError,
+};
+
+impl Dict {
+    /// Fetches a dictionary deployed as contract code through SSTORE2 pointers.
+    /// The code of every pointer, without its leading STOP byte, is concatenated in order and
+    /// sliced into 32-byte entries that follow the reserved wallet and contract slots.
+    pub async fn from_sstore2<P, T, N>(
+        provider: &P,
+        wallet_addr: Bytes32,
+        contract_addr: Bytes32,
+        pointers: &[Address],
+    ) -> Result<Self, CompressorError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let mut data = Vec::new();
+        for pointer in pointers {
+            let code: Bytes = provider
+                .get_code_at(*pointer)
+                .await
+                .map_err(|e| CompressorError::Provider(e.to_string()))?;
+            // SSTORE2 prefixes the data with a STOP opcode so the pointer can't be called
+            match code.split_first() {
+                Some((0x00, rest)) => data.extend_from_slice(rest),
+                _ => return Err(CompressorError::InvalidPointer(*pointer)),
+            }
+        }
+        if data.len() % 32 != 0 {
+            return Err(CompressorError::InvalidDictLength(data.len()));
+        }
+
+        let entries: Vec<Bytes32> = data
+            .chunks_exact(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        Ok(Self::new(wallet_addr, contract_addr, &entries))
+    }
+
+    /// Fetches a dictionary stored in the `count` consecutive storage slots of `contract`
+    /// starting at `start_slot`, one entry per slot
+    pub async fn from_storage<P, T, N>(
+        provider: &P,
+        wallet_addr: Bytes32,
+        contract_addr: Bytes32,
+        contract: Address,
+        start_slot: u64,
+        count: usize,
+    ) -> Result<Self, CompressorError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let mut entries = Vec::with_capacity(count);
+        for slot in (0..count as u64).map(|i| U256::from(start_slot) + U256::from(i)) {
+            let value = provider
+                .get_storage_at(contract, slot)
+                .await
+                .map_err(|e| CompressorError::Provider(e.to_string()))?;
+            entries.push(value.to_be_bytes::<32>());
+        }
+        Ok(Self::new(wallet_addr, contract_addr, &entries))
+    }
+
+    /// Fetches the dictionary deployed through the SSTORE2 `pointers`, see `Dict::from_sstore2`,
+    /// and reports how it drifted from this one, see `Dict::drift_from`
+    pub async fn verify_against_chain<P, T, N>(
+        &self,
+        provider: &P,
+        pointers: &[Address],
+    ) -> Result<DriftReport, CompressorError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let deployed =
+            Self::from_sstore2(provider, self.wallet_addr(), self.contract_addr(), pointers)
+                .await?;
+        Ok(self.drift_from(&deployed))
+    }
+}
+
+impl Calldata {
+    /// `init_dict` with the entries stored on chain by `contract`, see `Dict::from_storage`
+    pub async fn init_dict_from_chain<P, T, N>(
+        &mut self,
+        provider: &P,
+        contract: Address,
+        start_slot: u64,
+        count: usize,
+    ) -> Result<(), CompressorError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let dict = Dict::from_storage(
+            provider,
+            self.wallet_addr,
+            self.contract_addr,
+            contract,
+            start_slot,
+            count,
+        )
+        .await?;
+        self.set_dict(&dict);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::{
+        collections::HashMap,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use alloy::{
+        primitives::{Address, Bytes, U256},
+        providers::RootProvider,
+        rpc::{
+            client::RpcClient,
+            json_rpc::{
+                RequestPacket, Response, ResponsePacket, ResponsePayload, SerializedRequest,
+            },
+        },
+        transports::{TransportError, TransportFut},
+    };
+    use serde_json::value::RawValue;
+
+    /// In-memory JSON-RPC transport answering from canned chain state
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct MockTransport {
+        pub code: Arc<HashMap<Address, Bytes>>,
+        pub storage: Arc<HashMap<(Address, U256), U256>>, // unset slots are zero
+    }
+
+    impl MockTransport {
+        pub(crate) fn provider(self) -> RootProvider<Self> {
+            RootProvider::new(RpcClient::new(self, true))
+        }
+
+        fn respond(&self, request: &SerializedRequest) -> Response {
+            let params: Vec<serde_json::Value> = request
+                .params()
+                .map(|params| serde_json::from_str(params.get()).unwrap())
+                .unwrap_or_default();
+            let address: Address = serde_json::from_value(params[0].clone()).unwrap();
+            let result = match request.method() {
+                "eth_getCode" => {
+                    serde_json::to_string(&self.code.get(&address).cloned().unwrap_or_default())
+                }
+                "eth_getStorageAt" => {
+                    let slot: U256 = serde_json::from_value(params[1].clone()).unwrap();
+                    serde_json::to_string(
+                        &self
+                            .storage
+                            .get(&(address, slot))
+                            .copied()
+                            .unwrap_or_default(),
+                    )
+                }
+                method => panic!("unexpected request: {method}"),
+            }
+            .unwrap();
+            Response {
+                id: request.id().clone(),
+                payload: ResponsePayload::Success(RawValue::from_string(result).unwrap()),
+            }
+        }
+    }
+
+    impl tower::Service<RequestPacket> for MockTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: RequestPacket) -> Self::Future {
+            let response = match request {
+                RequestPacket::Single(request) => ResponsePacket::Single(self.respond(&request)),
+                RequestPacket::Batch(requests) => {
+                    ResponsePacket::Batch(requests.iter().map(|r| self.respond(r)).collect())
+                }
+            };
+            Box::pin(async move { Ok(response) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::{mock::MockTransport, *};
+
+    fn sstore2_code(data: &[u8]) -> Bytes {
+        Bytes::from([[0x00].as_slice(), data].concat())
+    }
+
+    #[tokio::test]
+    async fn test_from_sstore2() {
+        let first = Address::repeat_byte(0x01);
+        let second = Address::repeat_byte(0x02);
+        let odd = Address::repeat_byte(0x03);
+        let not_sstore2 = Address::repeat_byte(0x04);
+
+        // the second entry spans both pointers
+        let entries: Vec<Bytes32> = vec![[0x11; 32], [0x22; 32], [0x33; 32]];
+        let data = entries.concat();
+        let provider = MockTransport {
+            code: Arc::new(HashMap::from([
+                (first, sstore2_code(&data[..48])),
+                (second, sstore2_code(&data[48..])),
+                (odd, sstore2_code(&[0x44; 33])),
+                (not_sstore2, Bytes::from(vec![0x60, 0x80])),
+            ])),
+            ..MockTransport::default()
+        }
+        .provider();
+
+        let wallet_addr = [0xaa; 32];
+        let contract_addr = [0xbb; 32];
+        let dict = Dict::from_sstore2(&provider, wallet_addr, contract_addr, &[first, second])
+            .await
+            .unwrap();
+        assert_eq!(dict, Dict::new(wallet_addr, contract_addr, &entries));
+        assert_eq!(dict.index_of(&[0x22; 32]), Some(3));
+
+        assert_eq!(
+            Dict::from_sstore2(&provider, wallet_addr, contract_addr, &[first, odd]).await,
+            Err(CompressorError::InvalidDictLength(48 + 33))
+        );
+        assert_eq!(
+            Dict::from_sstore2(&provider, wallet_addr, contract_addr, &[not_sstore2]).await,
+            Err(CompressorError::InvalidPointer(not_sstore2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_dict_from_chain() {
+        let contract = Address::repeat_byte(0x01);
+        let entries: Vec<Bytes32> = vec![[0x11; 32], [0x22; 32], [0x33; 32]];
+        let provider = MockTransport {
+            storage: Arc::new(
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| ((contract, U256::from(7 + i)), U256::from_be_bytes(*entry)))
+                    .collect(),
+            ),
+            ..MockTransport::default()
+        }
+        .provider();
+
+        let calldata = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x22; 32]].concat());
+        let mut cb = Calldata::new(calldata.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict_from_chain(&provider, contract, 7, 3)
+            .await
+            .unwrap();
+        let mut expected = Calldata::new(calldata, [0xaa; 32], [0xbb; 32]).unwrap();
+        expected.init_dict(&entries);
+        assert_eq!(cb.dict, expected.dict);
+        assert_eq!(
+            cb.compress().unwrap().compressed_data,
+            expected.compress().unwrap().compressed_data
+        );
+
+        // slots past the dictionary read as zero words
+        let dict = Dict::from_storage(&provider, [0xaa; 32], [0xbb; 32], contract, 9, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            dict,
+            Dict::new([0xaa; 32], [0xbb; 32], &[[0x33; 32], [0x00; 32]])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_against_chain() {
+        let pointer = Address::repeat_byte(0x01);
+        let (token, router, pool, spender): (Bytes32, Bytes32, Bytes32, Bytes32) =
+            ([0x11; 32], [0x22; 32], [0x33; 32], [0x44; 32]);
+        let local = Dict::new([0xaa; 32], [0xbb; 32], &[token, router, pool]);
+        let deploy = |entries: &[Bytes32]| {
+            MockTransport {
+                code: Arc::new(HashMap::from([(pointer, sstore2_code(&entries.concat()))])),
+                ..MockTransport::default()
+            }
+            .provider()
+        };
+        let compress = |data: Vec<u8>| {
+            let mut cb = Calldata::new(
+                Bytes::from(data),
+                local.wallet_addr(),
+                local.contract_addr(),
+            )
+            .unwrap();
+            cb.set_dict(&local);
+            cb.compress().unwrap()
+        };
+        let uses_token = compress([[0x12].as_slice(), &token].concat());
+        let uses_pool = compress([[0x12].as_slice(), &pool].concat());
+
+        let report = local
+            .verify_against_chain(&deploy(&[token, router, pool]), &[pointer])
+            .await
+            .unwrap();
+        assert!(report.is_clean());
+        assert!(report.is_safe_for(&uses_pool));
+
+        // the router was never deployed, the pool went in its place and a spender was appended
+        let report = local
+            .verify_against_chain(&deploy(&[token, pool, spender]), &[pointer])
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            DriftReport {
+                missing: vec![3],
+                extra: vec![4],
+                mismatched: vec![(4, 3)],
+                diverging: vec![3, 4],
+            }
+        );
+        assert!(report.is_safe_for(&uses_token));
+        assert!(!report.is_safe_for(&uses_pool));
+
+        // a shorter deployment lacks the last entries
+        let report = local
+            .verify_against_chain(&deploy(&[token]), &[pointer])
+            .await
+            .unwrap();
+        assert_eq!(report.missing, vec![3, 4]);
+        assert_eq!(report.diverging, vec![3, 4]);
+        assert!(report.is_safe_for(&uses_token));
+    }
+}
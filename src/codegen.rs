@@ -0,0 +1,109 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+use crate::instruction::TABLE_LENGTHS;
+
+// the decompressor, `{dict_slot}`, `{tables}` and `{table_offset}` are filled by
+// `emit_solidity_decompressor`
+const DECOMPRESSOR_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+// Generated by `emit_solidity_decompressor` of calldata-compressor, do not edit
+pragma solidity ^0.8.0;
+
+/// @title CalldataDecompressor
+/// @notice Inverse of `zip` of calldata-compressor: rebuilds the calldata of a compressed call
+/// and calls the contract with it. The payload is framed with the default `CompressOptions`,
+/// instructions in regular order and no checksum trailer.
+abstract contract CalldataDecompressor {
+    /// @dev storage slot of dictionary entry 2, entry `i` is at `DICT_SLOT + i - 2`. Entries 0
+    /// and 1 are the caller and this contract.
+    uint256 internal constant DICT_SLOT = {dict_slot};
+
+    /// @dev Rebuilds the calldata in memory and delegatecalls this contract with it, forwarding
+    /// what it returns or reverts with. The calldata must select a function of the contract,
+    /// calldata matching none would come back to this fallback.
+    fallback() external payable {
+        assembly {
+            let out := mload(0x40)
+            let ptr := out
+            for { let i := 0 } lt(i, calldatasize()) {} {
+                let tag := byte(0, calldataload(i))
+                switch shr(6, tag)
+                case 0 {
+                    // 00XXXXXX: XXXXXX + 1 zero bytes
+                    let len := add(and(tag, 0x3f), 1)
+                    calldatacopy(ptr, calldatasize(), len)
+                    ptr := add(ptr, len)
+                    i := add(i, 1)
+                }
+                case 1 {
+                    // 01PXXXXX: the XXXXX + 1 bytes that follow, left-padded to a word if P
+                    let len := add(and(tag, 0x1f), 1)
+                    if and(tag, 0x20) {
+                        calldatacopy(ptr, calldatasize(), sub(32, len))
+                        ptr := add(ptr, sub(32, len))
+                    }
+                    calldatacopy(ptr, add(i, 1), len)
+                    ptr := add(ptr, len)
+                    i := add(i, add(len, 1))
+                }
+                default {
+                    // 10BBXXXX XXXXXXXX or 11BBXXXX XXXXXXXX XXXXXXXX: the tail of entry X,
+                    // as many bytes as byte BB of {tables}
+                    let size := shr(6, tag)
+                    let bits := sub(mul(8, size), 4)
+                    let value := shr(sub(256, mul(8, size)), calldataload(i))
+                    let index := and(value, sub(shl(bits, 1), 1))
+                    let len := byte(add({table_offset}, and(shr(bits, value), 3)), {tables})
+                    let word
+                    switch index
+                    case 0 { word := caller() }
+                    case 1 { word := address() }
+                    default { word := sload(add(DICT_SLOT, sub(index, 2))) }
+                    mstore(ptr, shl(mul(8, sub(32, len)), word))
+                    ptr := add(ptr, len)
+                    i := add(i, size)
+                }
+            }
+            let ok := delegatecall(gas(), address(), out, sub(ptr, out), 0, 0)
+            returndatacopy(0, 0, returndatasize())
+            if iszero(ok) { revert(0, returndatasize()) }
+            return(0, returndatasize())
+        }
+    }
+}
+"#;
+
+/// Solidity source of the on-chain counterpart of `decompress`: an abstract contract whose
+/// `fallback` decodes the four instruction methods of `zip` and delegatecalls the contract with
+/// the rebuilt calldata. The reserved dictionary entries are the caller and the contract, the
+/// others are read from consecutive storage slots starting at `dict_slot`, the layout
+/// `Dict::from_storage` reads. The tail lengths of storage references come from
+/// `TABLE_LENGTHS`, so the source follows the encoder of this crate.
+pub fn emit_solidity_decompressor(dict_slot: u64) -> String {
+    let tables = TABLE_LENGTHS
+        .iter()
+        .fold(String::from("0x"), |hex, len| format!("{hex}{len:02x}"));
+    DECOMPRESSOR_TEMPLATE
+        .replace("{dict_slot}", &dict_slot.to_string())
+        .replace("{tables}", &tables)
+        .replace("{table_offset}", &(32 - TABLE_LENGTHS.len()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::assert_snapshot;
+
+    #[test]
+    fn test_emit_solidity_decompressor() {
+        let source = emit_solidity_decompressor(0);
+        assert_snapshot("solidity_decompressor", &source);
+        assert!(source.contains("byte(add(28, and(shr(bits, value), 3)), 0x2014041f)"));
+
+        let source = emit_solidity_decompressor(u64::MAX);
+        assert!(source.contains("uint256 internal constant DICT_SLOT = 18446744073709551615;"));
+        assert!(!source.contains("{dict_slot}"));
+    }
+}
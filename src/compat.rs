@@ -0,0 +1,287 @@
+use alloc::vec::Vec;
+
+use crate::{
+    compressor::{CompressResult, Method, CHECKSUM_SIZE},
+    instruction::{Instruction, MAX_LONG_INDEX, TABLE_LENGTHS},
+    plan::{MAX_COPY, MAX_ZERO_RUN},
+};
+
+/// Capabilities and limits of a deployed decompressor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecompressorVersion {
+    pub dict_short: bool,                // supports the 2-byte `0x10` storage method
+    pub dict_long: bool,                 // supports the 3-byte `0x11` storage method
+    pub max_dict_index: usize,           // largest dictionary index deployed
+    pub storage_lengths: Vec<usize>,     // `BB` selected lengths the decoder implements
+    pub max_zero_run: usize,             // longest `0x00` zero run
+    pub max_copy: usize,                 // longest `0x01` copy
+    pub max_payload_size: Option<usize>, // largest compressed payload accepted
+    pub max_decompressed_size: Option<usize>, // memory reserved for the decompressed calldata
+    pub checksum: bool,                  // expects the payload to end with a checksum trailer
+}
+
+impl Default for DecompressorVersion {
+    // the reference 1inch decompressor, which implements every method without size limits
+    fn default() -> Self {
+        Self {
+            dict_short: true,
+            dict_long: true,
+            max_dict_index: MAX_LONG_INDEX as usize,
+            storage_lengths: TABLE_LENGTHS.to_vec(),
+            max_zero_run: MAX_ZERO_RUN,
+            max_copy: MAX_COPY,
+            max_payload_size: None,
+            max_decompressed_size: None,
+            checksum: false,
+        }
+    }
+}
+
+/// A reason a compressed payload can't be decoded by a given decompressor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompatIssue {
+    // the instruction at `offset` of the payload is cut short
+    Truncated {
+        offset: usize,
+    },
+    UnsupportedMethod {
+        offset: usize,
+        method: Method,
+    },
+    UnsupportedLength {
+        offset: usize,
+        method: Method,
+        len: usize,
+    },
+    DictIndexOutOfRange {
+        offset: usize,
+        index: usize,
+    },
+    PayloadTooLarge {
+        size: usize,
+        max: usize,
+    },
+    DecompressedTooLarge {
+        size: usize,
+        max: usize,
+    },
+    // the payload has (`true`) or lacks (`false`) a checksum trailer, contrary to the decompressor
+    ChecksumTrailer(bool),
+}
+
+// walks the instruction stream, returning the offset of the first truncated instruction on error
+fn scan(compressed: &[u8]) -> Result<Vec<(usize, Instruction)>, usize> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < compressed.len() {
+        let (instruction, size) = Instruction::decode(&compressed[offset..]).map_err(|_| offset)?;
+        instructions.push((offset, instruction));
+        offset += size;
+    }
+    Ok(instructions)
+}
+
+impl CompressResult {
+    /// Checks whether `compressed_data` decodes on a decompressor with the capabilities of `version`.
+    /// Only the compressed bytes are inspected, so results loaded from a cache can be checked too.
+    /// Offsets of a reversed stream are counted in regular instruction order.
+    pub fn check_compatibility(
+        &self,
+        version: &DecompressorVersion,
+    ) -> Result<(), Vec<CompatIssue>> {
+        let mut issues = Vec::new();
+
+        let size = self.compressed_data.len();
+        if let Some(max) = version.max_payload_size.filter(|&max| size > max) {
+            issues.push(CompatIssue::PayloadTooLarge { size, max });
+        }
+
+        let mut stream: &[u8] = &self.compressed_data;
+        if self.checksum != version.checksum {
+            issues.push(CompatIssue::ChecksumTrailer(self.checksum));
+        }
+        if self.checksum {
+            stream = &stream[..stream.len().saturating_sub(CHECKSUM_SIZE)];
+        }
+
+        let reversed: Vec<u8>;
+        if self.reverse {
+            reversed = stream.iter().rev().copied().collect();
+            stream = &reversed;
+        }
+
+        let instructions = match scan(stream) {
+            Ok(instructions) => instructions,
+            Err(offset) => {
+                issues.push(CompatIssue::Truncated { offset });
+                return Err(issues);
+            }
+        };
+
+        let mut decompressed_size = 0;
+        for (offset, instruction) in instructions {
+            let method = instruction.method();
+            let len = instruction.decompressed_len();
+            decompressed_size += len;
+
+            let supported = match method {
+                Method::DictShort => version.dict_short,
+                Method::DictLong => version.dict_long,
+                Method::Zeros | Method::Copy => true,
+            };
+            if !supported {
+                issues.push(CompatIssue::UnsupportedMethod { offset, method });
+                continue;
+            }
+
+            let len_supported = match method {
+                Method::Zeros => len <= version.max_zero_run,
+                Method::Copy => len <= version.max_copy,
+                Method::DictShort | Method::DictLong => version.storage_lengths.contains(&len),
+            };
+            if !len_supported {
+                issues.push(CompatIssue::UnsupportedLength {
+                    offset,
+                    method,
+                    len,
+                });
+            }
+
+            if let Some(index) = instruction
+                .dict_index()
+                .filter(|&index| index > version.max_dict_index)
+            {
+                issues.push(CompatIssue::DictIndexOutOfRange { offset, index });
+            }
+        }
+
+        if let Some(max) = version
+            .max_decompressed_size
+            .filter(|&max| decompressed_size > max)
+        {
+            issues.push(CompatIssue::DecompressedTooLarge {
+                size: decompressed_size,
+                max,
+            });
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{compress, Bytes32, CompressedCalldata},
+        instruction::MAX_SHORT_INDEX,
+    };
+
+    #[test]
+    fn test_check_compatibility() {
+        let entry: Bytes32 = [0x42; 32];
+        let mut dict = vec![[0x00; 32]; 5000];
+        for (i, word) in dict.iter_mut().enumerate() {
+            word[28..].copy_from_slice(&(i as u32 | 0xee00_0000).to_be_bytes());
+        }
+        // beyond the 2-byte index range
+        dict.push(entry);
+
+        let calldata = [vec![0x00; 40], entry.to_vec(), vec![0x01, 0x02]].concat();
+        let result = compress(
+            Bytes::from(calldata),
+            Bytes32::default(),
+            Bytes32::default(),
+            &dict,
+        )
+        .unwrap();
+
+        // the reference decompressor handles every instruction
+        assert_eq!(
+            result.check_compatibility(&DecompressorVersion::default()),
+            Ok(())
+        );
+
+        // a deployment without 3-byte references and a small dictionary rejects it
+        let minimal = DecompressorVersion {
+            dict_long: false,
+            max_dict_index: MAX_SHORT_INDEX as usize,
+            max_payload_size: Some(4),
+            ..DecompressorVersion::default()
+        };
+        let issues = result.check_compatibility(&minimal).unwrap_err();
+        assert!(issues.contains(&CompatIssue::PayloadTooLarge {
+            size: result.compressed_data.len(),
+            max: 4,
+        }));
+        assert!(issues.iter().any(|issue| matches!(
+            issue,
+            CompatIssue::UnsupportedMethod {
+                method: Method::DictLong,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_check_compatibility_limits() {
+        let result = compress(
+            Bytes::from(vec![0x00; 64]),
+            Bytes32::default(),
+            Bytes32::default(),
+            &[],
+        )
+        .unwrap();
+        let version = DecompressorVersion {
+            max_zero_run: 32,
+            max_decompressed_size: Some(48),
+            ..DecompressorVersion::default()
+        };
+        let issues = result.check_compatibility(&version).unwrap_err();
+        assert!(issues.contains(&CompatIssue::DecompressedTooLarge { size: 64, max: 48 }));
+        assert!(issues.iter().any(|issue| matches!(
+            issue,
+            CompatIssue::UnsupportedLength {
+                method: Method::Zeros,
+                len: 64,
+                ..
+            }
+        )));
+
+        let checksum_version = DecompressorVersion {
+            checksum: true,
+            ..DecompressorVersion::default()
+        };
+        assert_eq!(
+            result.check_compatibility(&checksum_version),
+            Err(vec![CompatIssue::ChecksumTrailer(false)])
+        );
+        let with_checksum = CompressResult {
+            compressed_data: CompressedCalldata::from_raw(Bytes::from(
+                [result.compressed_data.to_vec(), vec![0xff; 4]].concat(),
+            )),
+            checksum: true,
+            ..result.clone()
+        };
+        assert_eq!(with_checksum.check_compatibility(&checksum_version), Ok(()));
+        assert_eq!(
+            with_checksum.check_compatibility(&DecompressorVersion::default()),
+            Err(vec![CompatIssue::ChecksumTrailer(true)])
+        );
+
+        let truncated = CompressResult {
+            compressed_data: CompressedCalldata::from_raw(Bytes::from(vec![0x3f, 0xc0, 0x00])),
+            ..result
+        };
+        assert_eq!(
+            truncated.check_compatibility(&DecompressorVersion::default()),
+            Err(vec![CompatIssue::Truncated { offset: 1 }])
+        );
+    }
+}
@@ -6,26 +6,157 @@
 #![allow(clippy::missing_panics_doc)]
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_sign_loss)]
-use std::collections::HashMap;
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::fmt;
+
+use alloy::primitives::{keccak256, Address, Bytes};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cost::{
+        calldata_gas, count_zero_bytes, estimate_decompression_gas, CostModel, CostReport,
+        MainnetCalldata,
+    },
+    detector::{
+        add_candidate, check_extra_candidate, copy_case, storage_case, zero_runs, zeros_case,
+        AnalysisCtx, CopyDetector, Detector, StorageDetector, ZeroRunDetector,
+    },
+    dict::{word_frequencies, Dict, DictEntry, DictView, LOOKUP_LENGTHS},
+    errors::CompressorError,
+    hex_str::decode_hex,
+    instruction::{Instruction, MAX_LONG_INDEX, MAX_SHORT_INDEX, TABLE_LENGTHS},
+    options::{CompressObjective, CompressOptions, CompressProfile, CompressionStrategy},
+    plan::{validate_descriptions, MAX_COPY, MAX_ZERO_RUN},
+    planner::{golf_plan, OptimalDpPlanner, Planner},
+};
 
-use alloy::primitives::Bytes;
-use num_bigint::BigUint;
-use rayon::prelude::*;
+pub type Bytes32 = [u8; 32];
 
-use crate::errors::CompressorError;
+/// A wallet or contract address, as the 32-byte word of its dictionary slot or as a 20-byte
+/// address, which is left-padded with zeros the way the abi encodes it
+pub trait AddressLike {
+    fn to_word(&self) -> Bytes32;
+}
 
-pub type Bytes32 = [u8; 32];
+impl AddressLike for Bytes32 {
+    fn to_word(&self) -> Bytes32 {
+        *self
+    }
+}
+
+impl AddressLike for [u8; 20] {
+    fn to_word(&self) -> Bytes32 {
+        Address::from(*self).to_word()
+    }
+}
+
+impl AddressLike for Address {
+    fn to_word(&self) -> Bytes32 {
+        self.into_word().0
+    }
+}
+
+impl<T: AddressLike + ?Sized> AddressLike for &T {
+    fn to_word(&self) -> Bytes32 {
+        (**self).to_word()
+    }
+}
+
+/// Compression method of a description, its discriminant is the byte of the method in the
+/// plans of the reference implementation. Serialized, it's its `name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Method {
+    Zeros = 0x00,     // `00XXXXXX`, a run of zero bytes
+    Copy = 0x01,      // `01PXXXXX`, literal bytes
+    DictShort = 0x10, // `10BBXXXX XXXXXXXX`, a 2-byte dictionary reference
+    DictLong = 0x11,  // `11BBXXXX XXXXXXXX XXXXXXXX`, a 3-byte dictionary reference
+}
+
+impl Method {
+    pub const ALL: [Self; 4] = [Self::Zeros, Self::Copy, Self::DictShort, Self::DictLong];
+
+    /// human readable name of the method
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zeros => "zero-run",
+            Self::Copy => "copy",
+            Self::DictShort => "storage-2",
+            Self::DictLong => "storage-3",
+        }
+    }
+
+    /// the method of `name`, see `Method::name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|method| method.name() == name)
+    }
+
+    // whether the method references a dictionary entry
+    pub fn is_storage(self) -> bool {
+        matches!(self, Self::DictShort | Self::DictLong)
+    }
+
+    // the storage method of a reference encoded on `compressed_size` bytes
+    pub(crate) fn storage(compressed_size: usize) -> Self {
+        if compressed_size == 2 {
+            Self::DictShort
+        } else {
+            Self::DictLong
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Method {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Method {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = alloc::borrow::Cow::<'de, str>::deserialize(deserializer)?;
+        Self::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(alloc::format!("unknown method `{name}`")))
+    }
+}
+
+impl From<Method> for u8 {
+    fn from(method: Method) -> Self {
+        method as u8
+    }
+}
+
+impl TryFrom<u8> for Method {
+    type Error = CompressorError;
+
+    fn try_from(method: u8) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|&m| m as u8 == method)
+            .ok_or(CompressorError::UnsupportedMethod(method))
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
 
 /// How to compress a specific portion of data
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompressDataDescription {
     pub start_byte: usize,   // starting byte index of the data portion to compress
     pub amount_bytes: usize, // number of bytes to compress starting from start_byte
-    pub method: u8,          // compression method(decompress mask) to use
+    pub method: Method,      // compression method(decompress mask) to use
 }
 
 impl CompressDataDescription {
-    pub fn new(start_byte: usize, amount_bytes: usize, method: u8) -> Self {
+    pub fn new(start_byte: usize, amount_bytes: usize, method: Method) -> Self {
         Self {
             start_byte,
             amount_bytes,
@@ -34,8 +165,22 @@ impl CompressDataDescription {
     }
 }
 
+impl fmt::Display for CompressDataDescription {
+    /// `<method> <start>..<end>`, e.g. `copy 4..36`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}..{}",
+            self.method,
+            self.start_byte,
+            self.start_byte + self.amount_bytes
+        )
+    }
+}
+
 /// the power of the compressed data
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompressDataPower {
     pub decompressed_size: usize, // the size of the original(decompressed) data in bytes.
     pub compressed_size: usize,   // the size of the compressed data in bytes.
@@ -63,6 +208,7 @@ impl CompressDataPower {
 
 /// the compressed data itself, along with its description and power
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompressData {
     pub power: CompressDataPower, /* An instance of CompressDataPower representing the power of the compressed data. */
     pub descriptions: Vec<CompressDataDescription>, /* An instance of CompressDataDescription representing the description of how the data was compressed. */
@@ -105,453 +251,481 @@ impl ByteInfo {
 /// The compression is done by
 #[derive(Debug, Clone)]
 pub struct Calldata {
-    pub data: Bytes,
+    pub(crate) data: Bytes, // replaced through `set_data`, which keeps `zero_runs` in sync
     pub wallet_addr: Bytes32,
     pub contract_addr: Bytes32,
-    pub bytes_info: Vec<ByteInfo>,
-    pub dict: Vec<Bytes32>,              // contract dict data
-    pub lookup: HashMap<Vec<u8>, usize>, // value -> index
+    pub bytes_info: Vec<ByteInfo>, // candidates of every byte found by `analyse`, see `explain`
+    pub dict: Arc<Dict>, // entries and lookup, shared with other instances, see `set_shared_dict`
+    zero_runs: Vec<usize>, // zero bytes starting at every byte of the data
 }
 
 impl Calldata {
     pub fn new(
         data: Bytes,
-        wallet_addr: Bytes32,
-        contract_addr: Bytes32,
+        wallet_addr: impl AddressLike,
+        contract_addr: impl AddressLike,
     ) -> Result<Self, CompressorError> {
         let len = data.len();
         Ok(Self {
+            zero_runs: zero_runs(&data),
             data,
-            wallet_addr,
-            contract_addr,
+            wallet_addr: wallet_addr.to_word(),
+            contract_addr: contract_addr.to_word(),
             bytes_info: vec![ByteInfo::default(); len],
-            dict: Vec::new(),
-            lookup: HashMap::new(),
+            dict: Arc::default(),
         })
     }
 
+    /// `new` from hex calldata, with or without the `0x` prefix and in either case
+    pub fn from_hex(
+        hex_str: &str,
+        wallet_addr: impl AddressLike,
+        contract_addr: impl AddressLike,
+    ) -> Result<Self, CompressorError> {
+        Self::new(
+            Bytes::from(decode_hex(hex_str)?),
+            wallet_addr,
+            contract_addr,
+        )
+    }
+
+    /// the calldata to compress, replaced with `set_data`
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Swaps the payload, keeping the dictionary and its lookup. The analysis of the previous
+    /// data is cleared, `compress` analyses the new one.
+    pub fn set_data(&mut self, data: &Bytes) {
+        self.zero_runs = zero_runs(data);
+        self.bytes_info = vec![ByteInfo::default(); data.len()];
+        self.data = data.clone();
+    }
+
     pub fn analyse(&mut self) {
-        for i in 0..self.data.len() {
-            self.bytes_info[i] = ByteInfo {
-                index: i,
-                zero_compress: self.check_zeros_case(i),
-                copy_compress: self.check_copy_case_with_zeros(i),
-                storage_compress: self.check_storage_case(i).unwrap_or_default(),
-            };
+        self.analyse_data();
+        self.analyse_dict();
+    }
+
+    // zero and copy analysis, which only depends on the data and can be shared across dictionaries
+    pub fn analyse_data(&mut self) {
+        let ctx = AnalysisCtx {
+            // the fields, `dict_view` would borrow the `bytes_info` written below
+            dict: DictView {
+                base: &self.dict,
+                wallet_addr: self.wallet_addr,
+            },
+            zero_runs: &self.zero_runs,
+        };
+        for (i, info) in self.bytes_info.iter_mut().enumerate() {
+            info.index = i;
+            info.zero_compress = CompressDataPower::default();
+            for candidate in ZeroRunDetector
+                .candidates(&self.data, i, &ctx)
+                .into_iter()
+                .chain(CopyDetector.candidates(&self.data, i, &ctx))
+            {
+                add_candidate(info, candidate);
+            }
         }
     }
 
+    // storage analysis against the current dictionary
+    pub fn analyse_dict(&mut self) {
+        self.analyse_dict_with(&[])
+            .expect("the built-in detectors only propose encodable candidates");
+    }
+
+    // storage analysis against the current dictionary followed by the `extra` detectors, whose
+    // candidates must be dictionary references, see `Detector`
+    pub fn analyse_dict_with(
+        &mut self,
+        extra: &[Arc<dyn Detector>],
+    ) -> Result<(), CompressorError> {
+        self.analyse_dict_up_to(extra, LOOKUP_LENGTHS.len())
+    }
+
+    // `analyse_dict_with` as `compress_with` runs it: with the extra detectors of `opts`, and
+    // the lookups of `opts.dict_scan_depth`
+    pub fn analyse_dict_for(&mut self, opts: &CompressOptions) -> Result<(), CompressorError> {
+        self.analyse_dict_up_to(&opts.extra_detectors, opts.dict_scan_depth)
+    }
+
+    fn analyse_dict_up_to(
+        &mut self,
+        extra: &[Arc<dyn Detector>],
+        depth: usize,
+    ) -> Result<(), CompressorError> {
+        let ctx = AnalysisCtx {
+            // the fields, `dict_view` would borrow the `bytes_info` written below
+            dict: DictView {
+                base: &self.dict,
+                wallet_addr: self.wallet_addr,
+            },
+            zero_runs: &self.zero_runs,
+        };
+        analyse_storage(&mut self.bytes_info, &self.data, &ctx, extra, depth)
+    }
+
     pub fn create_desc(
         &self,
         from_byte: usize,
         array_desc: &[CompressDataDescription],
         amount_bytes: usize,
-        method: u8,
+        method: Method,
     ) -> CompressDataDescription {
-        let start_byte: usize = if array_desc.is_empty() {
-            from_byte
-        } else {
-            let prev_desc_index = array_desc.len() - 1;
-            array_desc[prev_desc_index].start_byte + array_desc[prev_desc_index].amount_bytes
-        };
-        CompressDataDescription {
-            start_byte,
-            amount_bytes,
-            method,
-        }
+        create_desc(from_byte, array_desc, amount_bytes, method)
     }
 
     pub fn add_just_copy_compress(
         &self,
         from_byte: usize,
-        mut result_compress: CompressData,
+        result_compress: CompressData,
         amount: usize,
     ) -> CompressData {
-        if amount != 0 {
-            result_compress.power.add(&CompressDataPower {
-                decompressed_size: amount,
-                compressed_size: 1 + amount,
-            });
-            result_compress.descriptions.push(self.create_desc(
-                from_byte,
-                &result_compress.descriptions,
-                amount,
-                0x01,
-            ));
-        }
-        result_compress
+        add_just_copy_compress(from_byte, result_compress, amount)
     }
 
     pub fn compress_part(&self, from_byte: usize, to_byte: usize) -> CompressData {
-        let mut part_compress = CompressData {
-            power: CompressDataPower {
-                decompressed_size: 0,
-                compressed_size: 0,
-            },
-            descriptions: vec![],
-        };
-        let mut just_copy_amount: usize = 0;
-
-        let mut i = from_byte;
-        while i <= to_byte {
-            if self.bytes_info[i].zero_compress.decompressed_size > to_byte - i {
-                part_compress =
-                    self.add_just_copy_compress(from_byte, part_compress, just_copy_amount);
-                part_compress.power.add(&CompressDataPower {
-                    decompressed_size: to_byte - from_byte + 1,
-                    compressed_size: 1,
-                });
-                part_compress.descriptions.push(CompressDataDescription {
-                    start_byte: i,
-                    amount_bytes: to_byte - i + 1,
-                    method: 0x00,
-                });
-                return part_compress;
-            }
+        let mut part_compress = CompressData::default();
+        compress_part_into(&self.bytes_info, from_byte, to_byte, &mut part_compress);
+        part_compress
+    }
 
-            let mut zero_bytes_amount = 0;
-            let mut is_padding_with_copy = false;
-            let mut need_just_copy_amount = true;
+    pub fn zip(
+        &self,
+        descriptions: &[CompressDataDescription],
+    ) -> Result<Vec<u8>, CompressorError> {
+        let mut result: Vec<u8> = Vec::new();
+        self.encode_with(descriptions, true, false, |bytes| {
+            result.extend_from_slice(bytes);
+            Ok(())
+        })?;
+        Ok(result)
+    }
 
-            if self.bytes_info[i].zero_compress.decompressed_size != 0 {
-                if self.bytes_info[i].copy_compress.decompressed_size > to_byte - i
-                    || self.bytes_info[i].zero_compress.range()
-                        > self.bytes_info[i].copy_compress.range()
-                {
-                    zero_bytes_amount = self.bytes_info[i].zero_compress.decompressed_size;
-                } else {
-                    is_padding_with_copy = true;
-                }
+    // the instruction encoding `description`, the `i`th of its plan, a word copy only strips
+    // its leading zeros with `pad_words`. A reference goes to an index of `read`, the indices
+    // already read by the payload, holding the bytes as well when there is one.
+    fn instruction(
+        &self,
+        i: usize,
+        description: &CompressDataDescription,
+        pad_words: bool,
+        read: Option<&[usize]>,
+    ) -> Result<Instruction, CompressorError> {
+        let bytes = self.get_bytes(description.start_byte, description.amount_bytes)?;
+        let index = || {
+            let view = self.dict_view();
+            let index = view
+                .index_of(bytes)
+                .ok_or(CompressorError::LookupNotFound)?;
+            // every entry ending with the bytes decodes the same, a warm one saves a cold `SLOAD`
+            // and one on the same side of `MAX_SHORT_INDEX` keeps the size of the instruction
+            let short = index <= MAX_SHORT_INDEX as usize;
+            let warm = read.filter(|read| !read.contains(&index)).and_then(|read| {
+                read.iter().copied().find(|&warm| {
+                    (warm <= MAX_SHORT_INDEX as usize) == short
+                        && view.get(warm).is_some_and(|entry| entry.ends_with(bytes))
+                })
+            });
+            Ok(warm.unwrap_or(index))
+        };
+        let table = || {
+            TABLE_LENGTHS
+                .iter()
+                .position(|&len| len == description.amount_bytes)
+                .map(|table| table as u8)
+                .ok_or(CompressorError::UnsupportedMethod(
+                    description.method.into(),
+                ))
+        };
+        match description.method {
+            Method::Zeros if description.amount_bytes == 0 => Err(CompressorError::InvalidPlan(i)),
+            Method::Zeros if description.amount_bytes > MAX_ZERO_RUN => {
+                Err(CompressorError::RunTooLong(description.amount_bytes))
             }
-            let mut is_storage_compress_used: bool = false;
-            let is_zero_compress: bool = zero_bytes_amount > 0;
-            for j in 0..self.bytes_info[i].storage_compress.len() {
-                if self.bytes_info[i].storage_compress[j].decompressed_size <= to_byte - i + 1 {
-                    let is_storage_range_more_than_copy_compress =
-                        self.bytes_info[i].storage_compress[j].range()
-                            > self.bytes_info[i].copy_compress.range();
-
-                    if !is_zero_compress
-                        && !is_storage_range_more_than_copy_compress
-                        && !is_padding_with_copy
-                    {
-                        continue;
-                    }
-
-                    part_compress =
-                        self.add_just_copy_compress(from_byte, part_compress, just_copy_amount);
-
-                    if is_zero_compress {
-                        if self.bytes_info[i].storage_compress[j].range()
-                            > self.bytes_info[i].zero_compress.range()
-                        {
-                            part_compress
-                                .power
-                                .add(&self.bytes_info[i].storage_compress[j]);
-                            part_compress.descriptions.push(self.create_desc(
-                                from_byte,
-                                &part_compress.descriptions,
-                                self.bytes_info[i].storage_compress[j].decompressed_size,
-                                if self.bytes_info[i].storage_compress[j].compressed_size == 2 {
-                                    0x10
-                                } else {
-                                    0x11
-                                },
-                            ));
-                            i += self.bytes_info[i].storage_compress[j].decompressed_size;
-                        } else {
-                            part_compress.power.add(&self.bytes_info[i].zero_compress);
-                            part_compress.descriptions.push(self.create_desc(
-                                from_byte,
-                                &part_compress.descriptions,
-                                zero_bytes_amount,
-                                0x00,
-                            ));
-                            i += zero_bytes_amount;
-                        }
-                    } else if is_storage_range_more_than_copy_compress {
-                        part_compress
-                            .power
-                            .add(&self.bytes_info[i].storage_compress[j]);
-                        part_compress.descriptions.push(self.create_desc(
-                            from_byte,
-                            &part_compress.descriptions,
-                            self.bytes_info[i].storage_compress[j].decompressed_size,
-                            if self.bytes_info[i].storage_compress[j].compressed_size == 2 {
-                                0x10
-                            } else {
-                                0x11
-                            },
-                        ));
-                        i += self.bytes_info[i].storage_compress[j].decompressed_size;
-                    } else if is_padding_with_copy {
-                        part_compress.power.add(&self.bytes_info[i].copy_compress);
-                        part_compress.descriptions.push(self.create_desc(
-                            from_byte,
-                            &part_compress.descriptions,
-                            self.bytes_info[i].copy_compress.decompressed_size,
-                            0x01,
-                        ));
-                        i += self.bytes_info[i].copy_compress.decompressed_size;
-                    }
-
-                    just_copy_amount = 0;
-                    need_just_copy_amount = false;
-                    is_storage_compress_used = true;
-                    break;
-                }
+            Method::Zeros => Ok(Instruction::ZeroRun {
+                len: description.amount_bytes as u8,
+            }),
+            // the 5 bits of a copy count 1 to `MAX_COPY` bytes
+            Method::Copy
+                if description.amount_bytes == 0 || description.amount_bytes > MAX_COPY =>
+            {
+                Err(CompressorError::InvalidPlan(i))
             }
-
-            if !is_storage_compress_used {
-                if is_zero_compress || is_padding_with_copy {
-                    part_compress =
-                        self.add_just_copy_compress(from_byte, part_compress, just_copy_amount);
-                }
-
-                if is_zero_compress {
-                    part_compress.power.add(&self.bytes_info[i].zero_compress);
-                    part_compress.descriptions.push(self.create_desc(
-                        from_byte,
-                        &part_compress.descriptions,
-                        zero_bytes_amount,
-                        0x00,
-                    ));
-                    i += zero_bytes_amount;
-                } else if is_padding_with_copy {
-                    part_compress.power.add(&self.bytes_info[i].copy_compress);
-                    part_compress.descriptions.push(self.create_desc(
-                        from_byte,
-                        &part_compress.descriptions,
-                        self.bytes_info[i].copy_compress.decompressed_size,
-                        0x01,
-                    ));
-                    i += self.bytes_info[i].copy_compress.decompressed_size;
-                }
-
-                if is_zero_compress || is_padding_with_copy {
-                    just_copy_amount = 0;
-                    need_just_copy_amount = false;
-                }
+            Method::Copy => {
+                // leading zeros of a word are restored by the decompressor, those of shorter
+                // copies are copied
+                let pad = match bytes.len() {
+                    32 if pad_words => bytes.iter().position(|&b| b != 0x00).unwrap_or(0),
+                    _ => 0,
+                };
+                Ok(Instruction::Copy {
+                    pad: pad as u8,
+                    bytes: bytes[pad..].to_vec(),
+                })
             }
-            if need_just_copy_amount {
-                let new_just_copy_amount = std::cmp::min(
-                    self.bytes_info[i].copy_compress.decompressed_size,
-                    to_byte - i + 1,
-                );
-                just_copy_amount += new_just_copy_amount;
-                if just_copy_amount > 32 {
-                    part_compress = self.add_just_copy_compress(from_byte, part_compress, 32);
-                    just_copy_amount -= 32;
-                }
-                i += new_just_copy_amount;
+            Method::DictShort => {
+                let index = index()?;
+                let table = table()?;
+                Ok(Instruction::DictShort {
+                    table,
+                    index: u16::try_from(index)
+                        .ok()
+                        .filter(|&index| index <= MAX_SHORT_INDEX)
+                        .ok_or(CompressorError::DictTooLarge(index))?,
+                })
+            }
+            Method::DictLong => {
+                let index = index()?;
+                let table = table()?;
+                Ok(Instruction::DictLong {
+                    table,
+                    index: u32::try_from(index)
+                        .ok()
+                        .filter(|&index| index <= MAX_LONG_INDEX)
+                        .ok_or(CompressorError::DictTooLarge(index))?,
+                })
             }
         }
+    }
 
-        part_compress = self.add_just_copy_compress(from_byte, part_compress, just_copy_amount);
-
-        part_compress
+    // encodes `descriptions` into `sink`, returning the number of bytes written
+    #[cfg(feature = "std")]
+    pub fn zip_into<W: std::io::Write>(
+        &self,
+        descriptions: &[CompressDataDescription],
+        sink: &mut W,
+    ) -> Result<usize, CompressorError> {
+        self.encode_with(descriptions, true, false, |bytes| {
+            sink.write_all(bytes)
+                .map_err(|e| CompressorError::Io(e.to_string()))
+        })
     }
 
-    pub fn zip(
+    // encodes `descriptions` an instruction at a time into `write`, returning the number of
+    // bytes written
+    fn encode_with(
         &self,
         descriptions: &[CompressDataDescription],
-    ) -> Result<Vec<u8>, CompressorError> {
-        let mut result: Vec<u8> = Vec::new();
-        let bb = [32, 20, 4, 31];
-        for description in descriptions {
-            match description.method {
-                0x00 => {
-                    // 00XXXXXX
-                    result.push((description.amount_bytes - 1) as u8);
-                }
-                0x01 => {
-                    // 01PXXXXX
-                    let copy_bytes =
-                        self.get_bytes(description.start_byte, description.amount_bytes)?;
-                    let mut non_zero_byte_index = 0;
-                    for (j, _) in copy_bytes.iter().enumerate().take(description.amount_bytes) {
-                        if copy_bytes[j] != 0x00 {
-                            non_zero_byte_index = j;
-                            break;
-                        }
-                    }
-                    result.push(
-                        ((description.amount_bytes - non_zero_byte_index - 1)
-                            + 64
-                            + if non_zero_byte_index == 0 { 0 } else { 32 })
-                            as u8,
-                    );
-                    let copy_bytes = self.get_bytes(
-                        description.start_byte + non_zero_byte_index,
-                        description.amount_bytes - non_zero_byte_index,
-                    )?;
-                    result.extend(copy_bytes);
-                }
-                0x10 => {
-                    // 10BBXXXX XXXXXXXX
-                    let index = *self
-                        .lookup
-                        .get(self.get_bytes(description.start_byte, description.amount_bytes)?)
-                        .ok_or(CompressorError::LookupNotFound)?;
-                    result.extend(
-                        BigUint::from(
-                            index
-                                + 2_u64.pow(15) as usize
-                                + (bb
-                                    .par_iter()
-                                    .position_first(|&r| r == description.amount_bytes)
-                                    .unwrap()
-                                    * 2_u64.pow(12) as usize),
-                        )
-                        .to_bytes_be(),
-                    );
-                }
-                0x11 => {
-                    // 11BBXXXX XXXXXXXX XXXXXXXX
-                    let index = *self
-                        .lookup
-                        .get(self.get_bytes(description.start_byte, description.amount_bytes)?)
-                        .ok_or(CompressorError::LookupNotFound)?;
-                    result.extend(
-                        BigUint::from(
-                            index
-                                + 3 * 2_u64.pow(22) as usize
-                                + (bb
-                                    .par_iter()
-                                    .position_first(|&r| r == description.amount_bytes)
-                                    .unwrap()
-                                    * 2_u64.pow(20) as usize),
-                        )
-                        .to_bytes_be(),
-                    );
-                }
-                _ => {
-                    return Err(CompressorError::UnsupportedMethod(description.method));
-                }
+        pad_words: bool,
+        warm: bool,
+        mut write: impl FnMut(&[u8]) -> Result<(), CompressorError>,
+    ) -> Result<usize, CompressorError> {
+        let mut written = 0;
+        let mut encoded = Vec::new();
+        let mut read = Vec::new();
+        for (i, description) in descriptions.iter().enumerate() {
+            encoded.clear();
+            let instruction =
+                self.instruction(i, description, pad_words, warm.then_some(&read[..]))?;
+            let index = match instruction {
+                Instruction::DictShort { index, .. } => Some(index as usize),
+                Instruction::DictLong { index, .. } => Some(index as usize),
+                _ => None,
+            };
+            if let Some(index) = index.filter(|index| warm && !read.contains(index)) {
+                read.push(index);
             }
+            instruction.encode(&mut encoded);
+            written += encoded.len();
+            write(&encoded)?;
         }
-        Ok(result)
+        Ok(written)
+    }
+
+    // `zip_into` appending to a `bytes::BufMut`, which grows as needed
+    #[cfg(feature = "bytes")]
+    pub fn zip_into_buf<B: bytes::BufMut>(
+        &self,
+        descriptions: &[CompressDataDescription],
+        buf: &mut B,
+    ) -> Result<usize, CompressorError> {
+        self.encode_with(descriptions, true, false, |bytes| {
+            buf.put_slice(bytes);
+            Ok(())
+        })
     }
 
+    /// Analyses and compresses the current data. It can be called repeatedly on the same
+    /// instance, along with `set_data`, to compress many payloads against one dictionary.
     pub fn compress(&mut self) -> Result<CompressResult, CompressorError> {
-        self.analyse();
-        let mut best_compress_for_first_n_bytes: Vec<CompressData> =
-            vec![CompressData::default(); self.bytes_info.len()];
-
-        if self.bytes_info[0].zero_compress.decompressed_size != 0 {
-            best_compress_for_first_n_bytes[0] = CompressData {
-                power: CompressDataPower {
-                    decompressed_size: 1,
-                    compressed_size: 1,
-                },
-                descriptions: vec![CompressDataDescription {
-                    start_byte: 0,
-                    amount_bytes: 1,
-                    method: 0x00,
-                }],
-            };
-        } else {
-            best_compress_for_first_n_bytes[0] = CompressData {
-                power: CompressDataPower {
-                    decompressed_size: 1,
-                    compressed_size: 2,
-                },
-                descriptions: vec![CompressDataDescription {
-                    start_byte: 0,
-                    amount_bytes: 1,
-                    method: 0x01,
-                }],
-            };
-        }
+        self.compress_with(&CompressOptions::default())
+    }
 
-        for i in 1..self.bytes_info.len() {
-            best_compress_for_first_n_bytes[i] = CompressData {
-                power: CompressDataPower {
-                    decompressed_size: best_compress_for_first_n_bytes[i - 1]
-                        .power
-                        .decompressed_size
-                        + 1,
-                    compressed_size: best_compress_for_first_n_bytes[i - 1].power.compressed_size
-                        + 2,
-                },
-                descriptions: [
-                    best_compress_for_first_n_bytes[i - 1].descriptions.clone(),
-                    vec![CompressDataDescription {
-                        start_byte: i,
-                        amount_bytes: 1,
-                        method: 0x01,
-                    }],
-                ]
-                .concat(),
-            };
+    pub fn compress_with(
+        &mut self,
+        opts: &CompressOptions,
+    ) -> Result<CompressResult, CompressorError> {
+        self.analyse_data();
+        self.analyse_dict_for(opts)?;
+        self.compress_analysed(opts)
+    }
 
-            for j in (std::cmp::max(0, i as isize - 63) as usize..=i).rev() {
-                let part_compress = self.compress_part(j, i);
+    /// `compress_with` against `dict`, which the instance shares rather than copies as
+    /// `set_dict` does: one dictionary serves the threads compressing with it. The instance
+    /// keeps it, see `set_shared_dict`, so `zip` and `explain` read the same dictionary.
+    pub fn compress_with_dict(
+        &mut self,
+        dict: &Arc<Dict>,
+        opts: &CompressOptions,
+    ) -> Result<CompressResult, CompressorError> {
+        self.set_shared_dict(Arc::clone(dict));
+        self.compress_with(opts)
+    }
 
-                let mut prefix_compress = CompressData {
-                    power: CompressDataPower::default(),
-                    descriptions: Vec::new(),
-                };
+    /// `compress` with the planner of `strategy`, `CompressionStrategy::Fast` trading output
+    /// size for a linear pass over the data
+    pub fn compress_with_strategy(
+        &mut self,
+        strategy: CompressionStrategy,
+    ) -> Result<CompressResult, CompressorError> {
+        self.compress_with(&CompressOptions::strategy(strategy))
+    }
 
-                if part_compress.descriptions[0].start_byte != 0 {
-                    let prev_desc_index = part_compress.descriptions[0].start_byte - 1;
-                    prefix_compress.power = best_compress_for_first_n_bytes[prev_desc_index]
-                        .power
-                        .clone();
-                    prefix_compress.descriptions = best_compress_for_first_n_bytes[prev_desc_index]
-                        .descriptions
-                        .clone();
-                }
+    // compresses using the current `bytes_info`, `analyse` must have been run for the current
+    // data and dictionary
+    pub fn compress_analysed(
+        &self,
+        opts: &CompressOptions,
+    ) -> Result<CompressResult, CompressorError> {
+        let descriptions = self.plan_analysed(opts)?;
+        self.encode_plan(descriptions, opts)
+    }
 
-                if prefix_compress.power.range() + part_compress.power.range()
-                    > best_compress_for_first_n_bytes[i].power.range()
-                {
-                    best_compress_for_first_n_bytes[i] = CompressData {
-                        power: CompressDataPower {
-                            decompressed_size: prefix_compress.power.decompressed_size
-                                + part_compress.power.decompressed_size,
-                            compressed_size: prefix_compress.power.compressed_size
-                                + part_compress.power.compressed_size,
-                        },
-                        descriptions: [prefix_compress.descriptions, part_compress.descriptions]
-                            .concat(),
-                    };
-                }
-            }
+    // the plan of `compress_analysed`, made by the planner of `opts`
+    pub(crate) fn plan_analysed(
+        &self,
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        let planner: &dyn Planner = opts.planner.as_deref().unwrap_or(&OptimalDpPlanner);
+        let descriptions = match opts
+            .chunk_len
+            .filter(|&len| len > 0 && len < self.data.len())
+        {
+            Some(chunk_len) => self.plan_chunks(planner, chunk_len, opts)?,
+            None => self.plan_unchunked(planner, opts)?,
+        };
+        Ok(if opts.gas_golf {
+            golf_plan(&self.data, descriptions, opts)
+        } else {
+            descriptions
+        })
+    }
+
+    fn plan_unchunked(
+        &self,
+        planner: &dyn Planner,
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        if opts.restricts_methods() {
+            let analysis = restrict_methods(&self.bytes_info, &self.data, opts);
+            planner.plan(&analysis, &self.data, opts)
+        } else {
+            self.plan_with(planner, opts)
+        }
+    }
+
+    // plans every `chunk_len` bytes of the data as calldata of their own, see
+    // `CompressOptions::chunk_len`, the descriptions are offsets of the whole data
+    fn plan_chunks(
+        &self,
+        planner: &dyn Planner,
+        chunk_len: usize,
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        let mut descriptions = Vec::new();
+        for start in (0..self.data.len()).step_by(chunk_len) {
+            let end = core::cmp::min(start + chunk_len, self.data.len());
+            let mut chunk = Calldata::new(
+                self.data.slice(start..end),
+                self.wallet_addr,
+                self.contract_addr,
+            )?;
+            chunk.set_shared_dict(Arc::clone(&self.dict));
+            chunk.analyse_data();
+            chunk.analyse_dict_for(opts)?;
+            descriptions.extend(chunk.plan_unchunked(planner, opts)?.into_iter().map(
+                |mut description| {
+                    description.start_byte += start;
+                    description
+                },
+            ));
+        }
+        Ok(descriptions)
+    }
 
-            // best_compress_for_first_n_bytes.push(current_best_compress);
+    // encodes a plan of the whole data, applying the output options of `opts`
+    pub(crate) fn encode_plan(
+        &self,
+        descriptions: Vec<CompressDataDescription>,
+        opts: &CompressOptions,
+    ) -> Result<CompressResult, CompressorError> {
+        // a gap would still encode, into a payload decompressing to fewer bytes
+        validate_descriptions(self.data.len(), &descriptions)?;
+        let mut compressed_data = Vec::new();
+        self.encode_with(
+            &descriptions,
+            opts.enable_copy_padding,
+            opts.gas_golf,
+            |bytes| {
+                compressed_data.extend_from_slice(bytes);
+                Ok(())
+            },
+        )?;
+        let power = CompressDataPower::new(self.data.len(), compressed_data.len());
+        if opts.reverse {
+            compressed_data.reverse();
+        }
+        if opts.checksum {
+            compressed_data.extend(checksum(&self.data));
+        }
+        if opts.forbid_inflation && compressed_data.len() >= self.data.len() {
+            return Err(CompressorError::NoCompression {
+                uncompressed: self.data.len(),
+                compressed: compressed_data.len(),
+            });
         }
 
-        Ok(CompressResult {
+        let result = CompressResult {
             uncompressed_data: self.data.clone(),
-            compressed_data: Bytes::from(
-                self.zip(
-                    &best_compress_for_first_n_bytes
-                        .last()
-                        .unwrap()
-                        .descriptions
-                        .clone(),
-                )?,
-            ),
-            power: best_compress_for_first_n_bytes
-                .last()
-                .unwrap()
-                .power
-                .clone(),
-            description: best_compress_for_first_n_bytes
-                .last()
-                .unwrap()
-                .descriptions
-                .clone(),
-        })
+            compressed_data: CompressedCalldata::from_raw(Bytes::from(compressed_data)),
+            power,
+            decompression_gas_estimate: estimate_decompression_gas(&descriptions),
+            description: descriptions,
+            checksum: opts.checksum,
+            reverse: opts.reverse,
+            objective: opts.objective,
+            profile: opts.profile,
+        };
+        debug_assert_eq!(result.verify_power(), Ok(()));
+        if opts.verify_roundtrip {
+            // decoding reads slot 0 of a `Dict`, a retargeted wallet is only in the view
+            if self.dict.entries.first() == Some(&self.wallet_addr) {
+                result.verify(&self.dict)?;
+            } else {
+                result.verify(&self.dict_view().to_dict())?;
+            }
+        }
+        Ok(result)
     }
 
     pub fn get_byte(&self, n: usize) -> Result<&u8, CompressorError> {
         self.data.get(n).ok_or(CompressorError::InvalidRange)
     }
 
+    // returns up to `n` bytes starting at `start`, truncated at the end of the data.
+    // a zero-length read is valid and yields an empty slice as long as `start` is within
+    // (or exactly at the end of) the data; reads starting past the end are `InvalidRange`.
     pub fn get_bytes(&self, start: usize, n: usize) -> Result<&[u8], CompressorError> {
-        let end = std::cmp::min(start + n, self.data.len());
+        if n == 0 {
+            return self
+                .data
+                .get(start..start)
+                .ok_or(CompressorError::InvalidRange);
+        }
+        let end = core::cmp::min(start + n, self.data.len());
         if start >= end {
             return Err(CompressorError::InvalidRange);
         }
@@ -561,128 +735,1004 @@ impl Calldata {
     }
 
     pub fn init_dict(&mut self, dict: &[Bytes32]) {
-        let mut dict_data = vec![self.wallet_addr, self.contract_addr];
-        dict_data.extend(dict);
-        self.dict = dict_data;
-
-        self.dict.iter().enumerate().for_each(|(i, data)| {
-            let value: Vec<u8> = data.to_vec();
-            self.lookup.insert(value.clone(), i);
-            self.lookup.insert(value[value.len() - 4..].to_vec(), i);
-            self.lookup.insert(value[value.len() - 20..].to_vec(), i);
-            self.lookup.insert(value[value.len() - 31..].to_vec(), i);
-        });
+        self.set_dict(&Dict::new(self.wallet_addr, self.contract_addr, dict));
+    }
+
+    /// Puts `wallet` in the reserved wallet slot of the dictionary, as `Dict::retarget_wallet`
+    /// does: the shared dictionary is read in place, only slot 0 is overridden, so one
+    /// dictionary serves payloads of every signer. `set_dict` and `set_shared_dict` restore the
+    /// wallet of the dictionary.
+    pub fn retarget_wallet(&mut self, wallet: impl AddressLike) {
+        self.wallet_addr = wallet.to_word();
+    }
+
+    /// The dictionary the analysis and `zip` read, `dict` with `wallet_addr` in slot 0
+    pub fn dict_view(&self) -> DictView<'_> {
+        DictView {
+            base: &self.dict,
+            wallet_addr: self.wallet_addr,
+        }
+    }
+
+    // `init_dict` with entries of their actual lengths, see `DictEntry`
+    pub fn init_dict_entries(&mut self, dict: &[DictEntry]) {
+        self.set_dict(&Dict::with_entries(
+            self.wallet_addr,
+            self.contract_addr,
+            dict,
+        ));
+    }
+
+    // uses a copy of an already built dictionary, its reserved slots take precedence over
+    // the wallet and contract addresses passed to `new`
+    pub fn set_dict(&mut self, dict: &Dict) {
+        self.set_shared_dict(Arc::new(dict.clone()));
+    }
+
+    /// `set_dict` without the copy, the instance reads `dict` through the `Arc` for the
+    /// analysis, `zip` and `check_storage_case` alike
+    pub fn set_shared_dict(&mut self, dict: Arc<Dict>) {
+        self.wallet_addr = dict.wallet_addr();
+        self.contract_addr = dict.contract_addr();
+        self.dict = dict;
+    }
+
+    /// The `top_n` most frequent abi words of the data that the current dictionary doesn't
+    /// contain, with their occurrence counts, most frequent first. All-zero words are left out
+    /// since zero runs already compress them.
+    pub fn suggest_dict_additions(&self, top_n: usize) -> Vec<(Bytes32, usize)> {
+        let mut words: Vec<(Bytes32, usize)> = word_frequencies([&self.data[..]])
+            .into_iter()
+            .filter(|(word, _)| {
+                *word != [0x00; 32] && self.dict_view().index_of(&word[..]).is_none()
+            })
+            .collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        words.truncate(top_n);
+        words
     }
 
     // 00XXXXXX
     pub fn check_zeros_case(&self, n: usize) -> CompressDataPower {
-        let mut current_byte_index = n;
-        let byte = self.get_byte(current_byte_index);
-        if !byte.is_ok_and(|x| *x == 0x00) {
-            return CompressDataPower {
-                decompressed_size: 0,
-                compressed_size: 0,
-            };
-        }
-        current_byte_index += 1;
-        // 00XXXXXX case, XXXXXX max value is 2**6-1=63
-        while self.get_byte(current_byte_index).is_ok_and(|x| *x == 0x00)
-            && current_byte_index < self.data.len()
-            && current_byte_index - n <= 63
-        {
-            current_byte_index += 1;
-        }
-        CompressDataPower {
-            decompressed_size: current_byte_index - n,
-            compressed_size: 1,
-        }
+        zeros_case(&self.zero_runs, n)
     }
 
     // 01PXXXXX
     pub fn check_copy_case_with_zeros(&self, n: usize) -> CompressDataPower {
-        let mut current_byte_index = n;
-        let byte = self.get_byte(current_byte_index);
-        if !byte.is_ok_and(|x| *x == 0x00) {
-            // decompressed: 0xXX, 1 Byte
-            // compressed: 01000000 0xXX, 2 Byte
-            return CompressDataPower {
-                decompressed_size: 1,
-                compressed_size: 2,
-            };
-        }
-        current_byte_index += 1;
-        // 01PXXXXX case, XXXXX max value is 2**5-1=31
-        while self.get_byte(current_byte_index).is_ok_and(|x| *x == 0x00)
-            && current_byte_index < self.data.len()
-        {
-            if current_byte_index - n == 32 {
-                return CompressDataPower {
-                    decompressed_size: 31,
-                    compressed_size: 32,
-                };
-            }
-            current_byte_index += 1;
-        }
-        let decompressed_bytes_amount = std::cmp::min(self.data.len() - n, 32);
-        CompressDataPower {
-            decompressed_size: decompressed_bytes_amount,
-            compressed_size: if decompressed_bytes_amount == 32 {
-                1 + 32 - (current_byte_index - n + 1)
-            } else {
-                1 + decompressed_bytes_amount
-            },
-        }
+        copy_case(&self.data, &self.zero_runs, n)
     }
 
     // 10BBXXXX XXXXXXXX case and 11BBXXXX XXXXXXXX XXXXXXXX case
     pub fn check_storage_case(&self, n: usize) -> Result<Vec<CompressDataPower>, CompressorError> {
-        if self.dict.is_empty() || self.lookup.is_empty() {
-            return Err(CompressorError::DictNotInit);
-        }
-
-        let mut best = Vec::<CompressDataPower>::new();
-        for len in &[32, 31, 20, 4] {
-            let tail = self.get_bytes(n, *len).unwrap();
-            let index = self.lookup.get(tail);
-            if let Some(index) = index {
-                if tail.len() >= *len {
-                    best.push(CompressDataPower {
-                        decompressed_size: *len,
-                        compressed_size: if *index > 4096 { 3 } else { 2 }, // 11BBXXXX XXXXXXXX XXXXXXXX or 10BBXXXX XXXXXXXX
-                    });
-                }
+        storage_case(&self.data, n, self.dict_view())
+    }
+}
+
+// storage analysis of `data` against the dictionary of `ctx`, looking up the first `depth`
+// `LOOKUP_LENGTHS`, see `Calldata::analyse_dict_with`
+fn analyse_storage(
+    bytes_info: &mut [ByteInfo],
+    data: &[u8],
+    ctx: &AnalysisCtx<'_>,
+    extra: &[Arc<dyn Detector>],
+    depth: usize,
+) -> Result<(), CompressorError> {
+    for (i, info) in bytes_info.iter_mut().enumerate() {
+        info.storage_compress.clear();
+        for candidate in StorageDetector::candidates_up_to(data, i, ctx, depth) {
+            add_candidate(info, candidate);
+        }
+        for detector in extra {
+            for candidate in detector.candidates(data, i, ctx) {
+                check_extra_candidate(&candidate)?;
+                add_candidate(info, candidate);
             }
         }
-        Ok(best)
     }
+    Ok(())
 }
 
-pub struct CompressResult {
-    pub uncompressed_data: Bytes,
-    pub compressed_data: Bytes,
-    pub power: CompressDataPower,
-    pub description: Vec<CompressDataDescription>,
+fn create_desc(
+    from_byte: usize,
+    array_desc: &[CompressDataDescription],
+    amount_bytes: usize,
+    method: Method,
+) -> CompressDataDescription {
+    let start_byte: usize = if array_desc.is_empty() {
+        from_byte
+    } else {
+        let prev_desc_index = array_desc.len() - 1;
+        array_desc[prev_desc_index].start_byte + array_desc[prev_desc_index].amount_bytes
+    };
+    CompressDataDescription {
+        start_byte,
+        amount_bytes,
+        method,
+    }
 }
 
-pub fn compress(
-    calldata: Bytes,
-    wallet_addr: Bytes32,
-    contract_addr: Bytes32,
-    dict: &[Bytes32],
-) -> Result<CompressResult, CompressorError> {
-    let mut calldata = Calldata::new(calldata, wallet_addr, contract_addr).unwrap();
-    calldata.init_dict(dict);
-    calldata.compress()
+fn add_just_copy_compress(
+    from_byte: usize,
+    mut result_compress: CompressData,
+    amount: usize,
+) -> CompressData {
+    if amount != 0 {
+        result_compress.power.add(&CompressDataPower {
+            decompressed_size: amount,
+            compressed_size: 1 + amount,
+        });
+        result_compress.descriptions.push(create_desc(
+            from_byte,
+            &result_compress.descriptions,
+            amount,
+            Method::Copy,
+        ));
+    }
+    result_compress
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{fs::File, io::Read, str::FromStr};
+// `analysis` without the candidates of the methods `opts` disables. A zero byte that can't
+// start a padded copy is a single literal byte instead.
+pub(crate) fn restrict_methods(
+    analysis: &[ByteInfo],
+    data: &[u8],
+    opts: &CompressOptions,
+) -> Vec<ByteInfo> {
+    let mut restricted = analysis.to_vec();
+    for (info, &byte) in restricted.iter_mut().zip(data.iter()) {
+        if !opts.enable_zero {
+            info.zero_compress = CompressDataPower::default();
+        }
+        if !opts.enable_copy_padding && byte == 0x00 {
+            info.copy_compress = CompressDataPower::new(1, 2);
+        }
+        info.storage_compress.retain_mut(|power| {
+            if power.compressed_size == 2 && !opts.enable_dict_short {
+                power.compressed_size = 3;
+            }
+            match power.compressed_size {
+                2 => opts.enable_dict_short,
+                _ => opts.enable_dict_long,
+            }
+        });
+    }
+    restricted
+}
 
-    use serde::Deserialize;
+// `compress_part` writing into `part_compress`, so that the DP can reuse one buffer for
+// all of its candidates instead of allocating one per candidate
+fn compress_part_into(
+    bytes_info: &[ByteInfo],
+    from_byte: usize,
+    to_byte: usize,
+    part_compress: &mut CompressData,
+) {
+    part_compress.power = CompressDataPower::default();
+    part_compress.descriptions.clear();
+    let mut state = Some((from_byte, 0));
+    while let Some((i, just_copy_amount)) = state {
+        let mut emit = |power: &CompressDataPower, desc| {
+            part_compress.power.add(power);
+            part_compress.descriptions.push(desc);
+        };
+        if i > to_byte {
+            emit_just_copy(&mut emit, i - just_copy_amount, just_copy_amount);
+            return;
+        }
+        state = part_step(bytes_info, to_byte, i, just_copy_amount, &mut emit);
+        if state.is_none() {
+            part_compress.power.decompressed_size += to_byte - from_byte + 1;
+        }
+    }
+}
+
+// one step of `compress_part_into` for a part ending at `to_byte`: plans byte `i`, with the
+// `just_copy_amount` bytes before it still waiting for their copy. Emits what it plans and
+// returns the next byte and copy amount, `None` once a zero run ends the part. That zero run
+// is emitted as decompressing to nothing, `compress_part_into` counts it as the whole part.
+// Nothing depends on where the part starts, which is what lets the DP share the steps.
+fn part_step(
+    bytes_info: &[ByteInfo],
+    to_byte: usize,
+    mut i: usize,
+    mut just_copy_amount: usize,
+    emit: &mut impl FnMut(&CompressDataPower, CompressDataDescription),
+) -> Option<(usize, usize)> {
+    let info = &bytes_info[i];
+    let copy_start = i - just_copy_amount;
+    if info.zero_compress.decompressed_size > to_byte - i {
+        emit_just_copy(emit, copy_start, just_copy_amount);
+        emit(
+            &CompressDataPower::new(0, 1),
+            CompressDataDescription::new(i, to_byte - i + 1, Method::Zeros),
+        );
+        return None;
+    }
+
+    let mut zero_bytes_amount = 0;
+    let mut is_padding_with_copy = false;
+    if info.zero_compress.decompressed_size != 0 {
+        if info.copy_compress.decompressed_size > to_byte - i
+            || info.zero_compress.range() > info.copy_compress.range()
+        {
+            zero_bytes_amount = info.zero_compress.decompressed_size;
+        } else {
+            is_padding_with_copy = true;
+        }
+    }
+    let is_zero_compress: bool = zero_bytes_amount > 0;
+    let zeros = |i| CompressDataDescription::new(i, zero_bytes_amount, Method::Zeros);
+    let padded =
+        |i| CompressDataDescription::new(i, info.copy_compress.decompressed_size, Method::Copy);
+    let storage = |i, power: &CompressDataPower| {
+        CompressDataDescription::new(
+            i,
+            power.decompressed_size,
+            Method::storage(power.compressed_size),
+        )
+    };
+
+    for power in &info.storage_compress {
+        if power.decompressed_size <= to_byte - i + 1 {
+            let is_storage_range_more_than_copy_compress =
+                power.range() > info.copy_compress.range();
+
+            if !is_zero_compress
+                && !is_storage_range_more_than_copy_compress
+                && !is_padding_with_copy
+            {
+                continue;
+            }
+
+            emit_just_copy(emit, copy_start, just_copy_amount);
+            if is_zero_compress {
+                if power.range() > info.zero_compress.range() {
+                    emit(power, storage(i, power));
+                    i += power.decompressed_size;
+                } else {
+                    emit(&info.zero_compress, zeros(i));
+                    i += zero_bytes_amount;
+                }
+            } else if is_storage_range_more_than_copy_compress {
+                emit(power, storage(i, power));
+                i += power.decompressed_size;
+            } else {
+                emit(&info.copy_compress, padded(i));
+                i += info.copy_compress.decompressed_size;
+            }
+            return Some((i, 0));
+        }
+    }
+
+    if is_zero_compress {
+        emit_just_copy(emit, copy_start, just_copy_amount);
+        emit(&info.zero_compress, zeros(i));
+        return Some((i + zero_bytes_amount, 0));
+    }
+    if is_padding_with_copy {
+        emit_just_copy(emit, copy_start, just_copy_amount);
+        emit(&info.copy_compress, padded(i));
+        return Some((i + info.copy_compress.decompressed_size, 0));
+    }
+
+    let new_just_copy_amount =
+        core::cmp::min(info.copy_compress.decompressed_size, to_byte - i + 1);
+    just_copy_amount += new_just_copy_amount;
+    if just_copy_amount > 32 {
+        emit_just_copy(emit, copy_start, 32);
+        just_copy_amount -= 32;
+    }
+    Some((i + new_just_copy_amount, just_copy_amount))
+}
+
+// `add_just_copy_compress` for `part_step`: the copy of the `amount` bytes from `start_byte`
+fn emit_just_copy(
+    emit: &mut impl FnMut(&CompressDataPower, CompressDataDescription),
+    start_byte: usize,
+    amount: usize,
+) {
+    if amount != 0 {
+        emit(
+            &CompressDataPower::new(amount, 1 + amount),
+            CompressDataDescription::new(start_byte, amount, Method::Copy),
+        );
+    }
+}
+
+// the DP of `OptimalDpPlanner`: the best plan of the first `i + 1` bytes is the best of the
+// plans of a part ending at `i` following the best plan of the bytes preceding that part, the
+// best saving the most of what `opts.objective` measures.
+//
+// The parts ending at `i` are built by `PartTails` from the last to the first, each from the
+// step at its first byte and the part already built where that step leads, instead of being
+// planned byte by byte with `compress_part_into`. Only the winning part is planned that way,
+// to get its descriptions. On test-data/calldata.json (72256 bytes) this takes
+// `compress_analysed` from about 320ms to about 120ms, see benches/compress.rs.
+pub(crate) fn optimal_plan(
+    bytes_info: &[ByteInfo],
+    data: &[u8],
+    opts: &CompressOptions,
+) -> CompressData {
+    if bytes_info.is_empty() {
+        return CompressData::default();
+    }
+    let saving = |part: &CompressData| part_saving(opts, data, part);
+    let mut best_compress_for_first_n_bytes: Vec<BestPrefix> = Vec::with_capacity(bytes_info.len());
+
+    let first_method = if bytes_info[0].zero_compress.decompressed_size != 0 {
+        Method::Zeros
+    } else {
+        Method::Copy
+    };
+    let first = CompressData::new(
+        CompressDataPower::new(1, if first_method == Method::Zeros { 1 } else { 2 }),
+        vec![CompressDataDescription::new(0, 1, first_method)],
+    );
+    best_compress_for_first_n_bytes.push(BestPrefix {
+        saved: saving(&first),
+        power: first.power,
+        prefix_end: None,
+        descriptions: first.descriptions,
+    });
+
+    let model = match opts.objective {
+        CompressObjective::Bytes => None,
+        CompressObjective::Gas => Some(opts.cost_model.as_deref().unwrap_or(&MainnetCalldata)),
+    };
+    let mut tails = PartTails::new(bytes_info.len(), opts.max_lookback);
+    let mut part_compress = CompressData::default();
+    for i in 1..bytes_info.len() {
+        let prefix = &best_compress_for_first_n_bytes[i - 1];
+        let copy = CompressData::new(
+            CompressDataPower::new(1, 2),
+            vec![CompressDataDescription::new(i, 1, Method::Copy)],
+        );
+        let mut best = BestPrefix {
+            saved: prefix.saved + saving(&copy),
+            power: CompressDataPower::new(
+                prefix.power.decompressed_size + 1,
+                prefix.power.compressed_size + 2,
+            ),
+            prefix_end: Some(i - 1),
+            descriptions: copy.descriptions,
+        };
+
+        // no instruction decompresses to more than 64 bytes, so starting parts up to 63 bytes
+        // back covers every instruction ending at `i`, dictionary references included
+        tails.start(bytes_info, i, opts.max_lookback);
+        let mut winner = None;
+        for j in (i.saturating_sub(opts.max_lookback)..=i).rev() {
+            let tail = tails.part(bytes_info, data, opts, model, i, j);
+            let mut power = tail.power.clone();
+            if tail.ends_with_zeros {
+                power.decompressed_size += i - j + 1;
+            }
+            let part_saved = match opts.objective {
+                CompressObjective::Bytes => power.range(),
+                CompressObjective::Gas => tail.gas_saved,
+            };
+
+            let prefix_end = j.checked_sub(1);
+            let prefix_saved =
+                prefix_end.map_or(0, |end| best_compress_for_first_n_bytes[end].saved);
+            let saved = prefix_saved + part_saved;
+            if saved > best.saved {
+                best.saved = saved;
+                winner = Some(j);
+            }
+        }
+        if let Some(j) = winner {
+            // only the winning part is planned step by step
+            compress_part_into(bytes_info, j, i, &mut part_compress);
+            let prefix_end = j.checked_sub(1);
+            let mut power = prefix_end.map_or_else(CompressDataPower::default, |end| {
+                best_compress_for_first_n_bytes[end].power.clone()
+            });
+            power.add(&part_compress.power);
+            best.power = power;
+            best.prefix_end = prefix_end;
+            best.descriptions = part_compress.descriptions.clone();
+        }
+        best_compress_for_first_n_bytes.push(best);
+    }
+
+    // follow the prefixes back from the last byte to collect the parts of the best plan
+    let last = best_compress_for_first_n_bytes.len() - 1;
+    let power = best_compress_for_first_n_bytes[last].power.clone();
+    let mut parts = Vec::new();
+    let mut end = Some(last);
+    while let Some(i) = end {
+        parts.push(core::mem::take(
+            &mut best_compress_for_first_n_bytes[i].descriptions,
+        ));
+        end = best_compress_for_first_n_bytes[i].prefix_end;
+    }
+    parts.reverse();
+    CompressData::new(power, parts.concat())
+}
+
+// what encoding `part` saves over the bytes it covers, as measured by `opts.objective`
+fn part_saving(opts: &CompressOptions, data: &[u8], part: &CompressData) -> i64 {
+    match opts.objective {
+        CompressObjective::Bytes => part.power.range(),
+        CompressObjective::Gas => {
+            let model = opts.cost_model.as_deref().unwrap_or(&MainnetCalldata);
+            part.descriptions
+                .iter()
+                .map(|d| description_gas_saving(model, data, d, opts))
+                .sum()
+        }
+    }
+}
+
+// the gas `d` saves over the bytes it covers under `model`
+fn description_gas_saving(
+    model: &dyn CostModel,
+    data: &[u8],
+    d: &CompressDataDescription,
+    opts: &CompressOptions,
+) -> i64 {
+    let bytes = &data[d.start_byte..d.start_byte + d.amount_bytes];
+    model.data_cost(bytes) as i64 - instruction_cost(model, d.method, bytes, opts) as i64
+}
+
+// cost under `model` of the instruction encoding `bytes` with `method`, as `zip` emits it. The
+// indices of storage references aren't part of the analysis, their bytes are priced as 0xff.
+fn instruction_cost(
+    model: &dyn CostModel,
+    method: Method,
+    bytes: &[u8],
+    opts: &CompressOptions,
+) -> u64 {
+    let mut encoded = [0xff; 33];
+    let len = match method {
+        Method::Zeros => {
+            encoded[0] = (bytes.len() - 1) as u8;
+            1
+        }
+        Method::Copy => {
+            let pad = match bytes.len() {
+                32 if opts.enable_copy_padding => {
+                    bytes.iter().position(|&b| b != 0x00).unwrap_or(0)
+                }
+                _ => 0,
+            };
+            let tail = &bytes[pad..];
+            encoded[0] = 0x40 | if pad == 0 { 0 } else { 0x20 } | (tail.len() - 1) as u8;
+            encoded[1..=tail.len()].copy_from_slice(tail);
+            1 + tail.len()
+        }
+        Method::DictShort => 2,
+        Method::DictLong => 3,
+    };
+    model.data_cost(&encoded[..len]) + model.instruction_overhead(method)
+}
+
+// best plan found for the first bytes of the data, as its last part and a link to the best
+// plan of the bytes preceding that part
+#[derive(Debug)]
+struct BestPrefix {
+    saved: i64,                                 // of the whole plan, see `part_saving`
+    power: CompressDataPower,                   // of the whole plan
+    prefix_end: Option<usize>,                  // last byte of the preceding plan, if any
+    descriptions: Vec<CompressDataDescription>, // of the last part only
+}
+
+// one slot per copy amount a `part_step` state can be waiting for, 0 to `MAX_COPY`
+const JUST_COPY_SLOTS: usize = MAX_COPY + 1;
+
+// the parts ending at `to_byte`, built from the last part to the first: a part is the
+// `part_step` at its first byte followed by the part starting where that step leads, already
+// built. The bytes copied as they are, whatever the copy amount waiting, are skipped in one
+// go, so the parts starting among them share their step at the first byte after them.
+struct PartTails {
+    first_byte: usize,          // of the first part ending at `to_byte`
+    just_copy_ends: Vec<usize>, // by byte from `first_byte`, the first byte not copied as is
+    parts: Vec<PartTail>,       // by first byte from `first_byte`
+    stamps: Vec<usize>,         // `to_byte + 1` where `steps` is up to date
+    steps: Vec<PartTail>,       // by byte from `first_byte`, then by copy amount waiting
+}
+
+// what some `part_step`s add to a part, the zero run ending the part counting for nothing
+#[derive(Debug, Clone, Default)]
+struct PartTail {
+    power: CompressDataPower,
+    gas_saved: i64, // under `CompressObjective::Gas` only
+    ends_with_zeros: bool,
+}
+
+impl PartTail {
+    fn add(
+        &mut self,
+        model: Option<&dyn CostModel>,
+        data: &[u8],
+        opts: &CompressOptions,
+        power: &CompressDataPower,
+        desc: &CompressDataDescription,
+    ) {
+        self.power.add(power);
+        if let Some(model) = model {
+            self.gas_saved += description_gas_saving(model, data, desc, opts);
+        }
+    }
+
+    fn add_tail(&mut self, other: &Self) {
+        self.power.add(&other.power);
+        self.gas_saved += other.gas_saved;
+        self.ends_with_zeros |= other.ends_with_zeros;
+    }
+}
+
+impl PartTails {
+    fn new(len: usize, max_lookback: usize) -> Self {
+        // a part ending at `to_byte` runs up to `to_byte + 1` at most
+        let bytes = core::cmp::min(max_lookback, len) + 2;
+        Self {
+            first_byte: 0,
+            just_copy_ends: vec![0; bytes],
+            parts: vec![PartTail::default(); bytes],
+            stamps: vec![0; bytes * JUST_COPY_SLOTS],
+            steps: vec![PartTail::default(); bytes * JUST_COPY_SLOTS],
+        }
+    }
+
+    // forgets the parts of the previous `to_byte`, the parts ending at `to_byte` are then to be
+    // built with `part`, the last one first
+    fn start(&mut self, bytes_info: &[ByteInfo], to_byte: usize, max_lookback: usize) {
+        self.first_byte = to_byte.saturating_sub(max_lookback);
+        self.just_copy_ends[to_byte + 1 - self.first_byte] = to_byte + 1;
+        self.parts[to_byte + 1 - self.first_byte] = PartTail::default();
+        for i in (self.first_byte..=to_byte).rev() {
+            let info = &bytes_info[i];
+            // the branches of `part_step` ending with the copy of the byte as it is
+            let is_just_copy = info.zero_compress.decompressed_size == 0
+                && !info.storage_compress.iter().any(|power| {
+                    power.decompressed_size <= to_byte - i + 1
+                        && power.range() > info.copy_compress.range()
+                });
+            self.just_copy_ends[i - self.first_byte] = if is_just_copy {
+                let next =
+                    i + core::cmp::min(info.copy_compress.decompressed_size, to_byte - i + 1);
+                self.just_copy_ends[next - self.first_byte]
+            } else {
+                i
+            };
+        }
+    }
+
+    // the part from `from_byte` to `to_byte`, once the parts starting after it are built
+    fn part(
+        &mut self,
+        bytes_info: &[ByteInfo],
+        data: &[u8],
+        opts: &CompressOptions,
+        model: Option<&dyn CostModel>,
+        to_byte: usize,
+        from_byte: usize,
+    ) -> &PartTail {
+        // the bytes copied as they are, all but the last `just_copy_amount` of them `MAX_COPY`
+        // at a time
+        let end = self.just_copy_ends[from_byte - self.first_byte];
+        let just_copy_amount = match end - from_byte {
+            0 => 0,
+            amount => (amount - 1) % MAX_COPY + 1,
+        };
+        let slot = (end - self.first_byte) * JUST_COPY_SLOTS + just_copy_amount;
+        if self.stamps[slot] != to_byte + 1 {
+            let mut step = PartTail::default();
+            let mut emit =
+                |power: &CompressDataPower, desc| step.add(model, data, opts, power, &desc);
+            let next = if end > to_byte {
+                emit_just_copy(&mut emit, end - just_copy_amount, just_copy_amount);
+                None
+            } else {
+                part_step(bytes_info, to_byte, end, just_copy_amount, &mut emit)
+            };
+            match next {
+                Some((next, waiting)) => {
+                    debug_assert_eq!(waiting, 0, "only a copy as is leaves bytes waiting");
+                    step.add_tail(&self.parts[next - self.first_byte]);
+                }
+                None => step.ends_with_zeros = end <= to_byte,
+            }
+            self.stamps[slot] = to_byte + 1;
+            self.steps[slot] = step;
+        }
+
+        let mut part = self.steps[slot].clone();
+        for start in (from_byte..end - just_copy_amount).step_by(MAX_COPY) {
+            emit_just_copy(
+                &mut |power, desc| part.add(model, data, opts, power, &desc),
+                start,
+                MAX_COPY,
+            );
+        }
+        let index = from_byte - self.first_byte;
+        self.parts[index] = part;
+        &self.parts[index]
+    }
+}
+
+/// Bytes produced by the compressor, to be decoded by the decompressor.
+/// Kept apart from plain `Bytes` so compressed output can't be fed back into `compress()` or
+/// shipped as regular calldata by accident, `into_raw` is the explicit way out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct CompressedCalldata(Bytes);
+
+impl CompressedCalldata {
+    // wraps bytes known to be compressed, e.g. a payload read back from a transaction
+    pub fn from_raw(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+
+    pub fn into_raw(self) -> Bytes {
+        self.0
+    }
+
+    pub fn as_raw(&self) -> &Bytes {
+        &self.0
+    }
+}
+
+impl core::ops::Deref for CompressedCalldata {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for CompressedCalldata {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The output of `compress` with everything it was obtained from. Serialized, the calldata are
+/// `0x` prefixed hex strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompressResult {
+    pub uncompressed_data: Bytes,
+    pub compressed_data: CompressedCalldata, /* instruction stream, then the checksum trailer if enabled */
+    pub power: CompressDataPower,            // sizes of the instruction stream alone
+    pub description: Vec<CompressDataDescription>,
+    pub checksum: bool, // whether `compressed_data` ends with a checksum of `uncompressed_data`
+    pub reverse: bool,  // whether the instruction stream is emitted back to front
+    pub objective: CompressObjective, // objective the plan was chosen for
+    pub profile: Option<CompressProfile>, // profile of `CompressOptions::auto`, if used
+    // decompressor execution gas of the plan with the default `DecompressionCosts`
+    pub decompression_gas_estimate: u64,
+}
+
+impl fmt::Display for CompressResult {
+    /// One line summary for logs, its format is stable, e.g. `800 -> 277 bytes, 523 saved
+    /// (65.38%); zero-run: 20 segments / 206 bytes, copy: 25 segments / 594 bytes`. Methods the
+    /// plan doesn't use are left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let original = self.uncompressed_data.len();
+        let percent = if original == 0 {
+            0.0
+        } else {
+            self.saved_bytes() as f64 * 100.0 / original as f64
+        };
+        write!(
+            f,
+            "{original} -> {} bytes, {} saved ({percent:.2}%)",
+            self.compressed_data.len(),
+            self.saved_bytes()
+        )?;
+        for (i, (method, totals)) in self.method_stats().iter().enumerate() {
+            let separator = if i == 0 { "; " } else { ", " };
+            write!(
+                f,
+                "{separator}{method}: {} segments / {} bytes",
+                totals.instructions, totals.decompressed_bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CompressResult {
+    /// Size of the instruction stream over the size of the calldata, without the checksum
+    /// trailer, unlike `compression_ratio` and `saved_bytes` which measure what is sent. `NaN`
+    /// for empty calldata.
+    ///
+    /// ```
+    /// use alloy::primitives::Bytes;
+    /// use calldata_compressor::compress;
+    ///
+    /// // `transfer(address,uint256)` of 42 tokens
+    /// let calldata: Bytes = "0xa9059cbb\
+    ///     000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045\
+    ///     000000000000000000000000000000000000000000000000000000000000002a"
+    ///     .parse()
+    ///     .unwrap();
+    /// let result = compress(calldata, [0xaa; 32], [0xbb; 32], &[]).unwrap();
+    /// assert!(result.stream_ratio() < 1.0);
+    /// assert_eq!(
+    ///     result.saved_bytes(),
+    ///     68 - result.power.compressed_size as i64
+    /// );
+    /// ```
+    pub fn stream_ratio(&self) -> f64 {
+        let trailer = if self.checksum { CHECKSUM_SIZE } else { 0 };
+        self.compressed_data.len().saturating_sub(trailer) as f64
+            / self.uncompressed_data.len() as f64
+    }
+
+    /// `stream_ratio` under its former name
+    #[deprecated(since = "0.5.0", note = "renamed to `stream_ratio`")]
+    pub fn ratio(&self) -> f64 {
+        self.stream_ratio()
+    }
+
+    /// Size of `compressed_data` over the size of the calldata, the checksum trailer included
+    /// as in `saved_bytes`, see `stream_ratio` for the instructions alone. `NaN` for empty
+    /// calldata.
+    pub fn compression_ratio(&self) -> f64 {
+        self.compressed_data.len() as f64 / self.uncompressed_data.len() as f64
+    }
+
+    /// Calldata bytes saved by sending `compressed_data`, the checksum trailer included,
+    /// negative when it inflates the calldata
+    pub fn saved_bytes(&self) -> i64 {
+        self.uncompressed_data.len() as i64 - self.compressed_data.len() as i64
+    }
+
+    /// zero bytes of `uncompressed_data`
+    pub fn zero_bytes_before(&self) -> usize {
+        count_zero_bytes(&self.uncompressed_data)
+    }
+
+    /// zero bytes of `compressed_data`
+    pub fn zero_bytes_after(&self) -> usize {
+        count_zero_bytes(&self.compressed_data)
+    }
+
+    /// Number of instructions of `compressed_data`, as the decompressor reads them
+    pub fn instruction_count(&self) -> usize {
+        self.stats().instructions
+    }
+
+    /// EVM calldata gas of `uncompressed_data`, 4 per zero byte and 16 per non-zero byte
+    pub fn gas_uncompressed(&self) -> u64 {
+        calldata_gas(&self.uncompressed_data)
+    }
+
+    /// EVM calldata gas of `compressed_data`, including the checksum trailer if enabled
+    pub fn gas_compressed(&self) -> u64 {
+        calldata_gas(&self.compressed_data)
+    }
+
+    /// Calldata gas saved by `compressed_data`, negative when it costs more than the calldata
+    pub fn saved_gas(&self) -> i64 {
+        self.gas_uncompressed() as i64 - self.gas_compressed() as i64
+    }
+
+    /// Costs under `model` of sending the calldata and of sending `compressed_data` instead,
+    /// the overhead of every instruction of the compressed stream included
+    pub fn evaluate(&self, model: &dyn CostModel) -> CostReport {
+        self.evaluate_with(model, 0)
+    }
+
+    /// Whether sending `compressed_data` to the decompressor costs less under `model` than
+    /// sending the calldata directly. The compressed side pays the instruction overheads of
+    /// `model`, `decompression_gas_estimate` and `overhead_gas`, the fixed cost of routing
+    /// through the decompressor such as its `CALL`.
+    pub fn is_worth_it(&self, model: &dyn CostModel, overhead_gas: u64) -> bool {
+        self.evaluate_with(model, self.decompression_gas_estimate + overhead_gas)
+            .saved
+            > 0
+    }
+
+    // `evaluate` with `extra` gas added to the decompression of `compressed_data`
+    fn evaluate_with(&self, model: &dyn CostModel, extra: u64) -> CostReport {
+        let stream = self.instruction_stream();
+        let mut overhead = extra;
+        let mut offset = 0;
+        while let Ok((instruction, size)) = Instruction::decode(&stream[offset..]) {
+            overhead += model.instruction_overhead(instruction.method());
+            offset += size;
+        }
+        CostReport::new(
+            model.transaction_cost(&self.uncompressed_data, None),
+            model.transaction_cost(&self.compressed_data, Some(overhead)),
+        )
+    }
+
+    /// Transaction gas saved by sending `compressed_data` instead of the calldata, as priced by
+    /// `model`, negative when compression doesn't pay
+    pub fn gas_saved(&self, model: &dyn CostModel) -> i64 {
+        self.evaluate(model).saved
+    }
+
+    // the instructions of `compressed_data` in decoding order, without the checksum trailer
+    pub(crate) fn instruction_stream(&self) -> Vec<u8> {
+        let mut stream = self.compressed_data.to_vec();
+        if self.checksum {
+            stream.truncate(stream.len().saturating_sub(CHECKSUM_SIZE));
+        }
+        if self.reverse {
+            stream.reverse();
+        }
+        stream
+    }
+}
+
+// size of the instruction at `offset` of `stream`, the rest of the stream if it's cut short
+pub(crate) fn instruction_size(stream: &[u8], offset: usize) -> usize {
+    let rest = stream.get(offset..).unwrap_or_default();
+    Instruction::decode(rest).map_or(rest.len(), |(_, size)| size)
+}
+
+// dictionary index of the storage reference at `offset` of `stream`, `None` for other methods
+pub(crate) fn storage_index(stream: &[u8], offset: usize) -> Option<usize> {
+    Instruction::decode(stream.get(offset..)?)
+        .ok()?
+        .0
+        .dict_index()
+}
+
+pub fn compress(
+    calldata: Bytes,
+    wallet_addr: impl AddressLike,
+    contract_addr: impl AddressLike,
+    dict: &[Bytes32],
+) -> Result<CompressResult, CompressorError> {
+    let mut calldata = Calldata::new(calldata, wallet_addr, contract_addr)?;
+    calldata.init_dict(dict);
+    calldata.compress()
+}
+
+/// size of the checksum trailer appended by `CompressOptions::checksum`
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// checksum of the original calldata: the first 4 bytes of its keccak256 hash
+pub fn checksum(data: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let hash = keccak256(data);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Reverses `compress`: rebuilds the original calldata from a compressed payload
+pub fn decompress(
+    compressed: &Bytes,
+    wallet_addr: impl AddressLike,
+    contract_addr: impl AddressLike,
+    dict: &[Bytes32],
+) -> Result<Bytes, CompressorError> {
+    decompress_with(
+        compressed,
+        &Dict::new(wallet_addr.to_word(), contract_addr.to_word(), dict),
+        &CompressOptions::default(),
+    )
+}
+
+/// Decodes a payload emitted with `CompressOptions::reverse`, for decompressors that consume the
+/// stream starting from its last byte.
+///
+/// The reversed stream is the regular instruction stream with its bytes in reverse order: the
+/// instruction of the last description comes first and the one of the first description last,
+/// and within every instruction the method byte comes after its index or literal bytes, which
+/// are themselves reversed. Reading from the end thus yields the method byte of the first
+/// description, followed by its operand bytes in their regular order, and so on. The checksum
+/// trailer, if any, is not reversed and stays at the very end.
+pub fn decompress_reverse(
+    compressed: &[u8],
+    dict: &Dict,
+    opts: &CompressOptions,
+) -> Result<Bytes, CompressorError> {
+    decompress_with(
+        compressed,
+        dict,
+        &CompressOptions {
+            reverse: true,
+            ..opts.clone()
+        },
+    )
+}
+
+/// An instruction of a compressed payload decoded by `unzip`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnzippedInstruction {
+    pub description: CompressDataDescription, // as `zip` consumes it, offsets of the output
+    pub stream_offset: usize,                 // of the instruction in the instruction stream
+    pub dict_index: Option<usize>,            // index read by a storage reference
+}
+
+/// Parses an instruction stream, without checksum trailer and in regular order, back into the
+/// descriptions `zip` encoded it from. A padded copy is described by the 32 bytes it expands to.
+/// No dictionary is needed: the storage references are reported with the index they read.
+pub fn unzip(stream: &[u8]) -> Result<Vec<UnzippedInstruction>, CompressorError> {
+    let mut instructions = Vec::new();
+    let mut start_byte = 0;
+    let mut stream_offset = 0;
+    while stream_offset < stream.len() {
+        let (instruction, size) = Instruction::decode(&stream[stream_offset..])?;
+        let dict_index = instruction.dict_index();
+        let amount_bytes = instruction.decompressed_len();
+        instructions.push(UnzippedInstruction {
+            description: CompressDataDescription::new(
+                start_byte,
+                amount_bytes,
+                instruction.method(),
+            ),
+            stream_offset,
+            dict_index,
+        });
+        start_byte += amount_bytes;
+        stream_offset += size;
+    }
+    Ok(instructions)
+}
+
+/// Decodes a compressed payload against an already built dictionary.
+/// With `opts.checksum` the trailing checksum is stripped and verified against the output,
+/// with `opts.reverse` the stream is read back to front, see `decompress_reverse`.
+pub fn decompress_with(
+    compressed: &[u8],
+    dict: &Dict,
+    opts: &CompressOptions,
+) -> Result<Bytes, CompressorError> {
+    let (instructions, expected_checksum) = if opts.checksum {
+        if compressed.len() < CHECKSUM_SIZE {
+            return Err(CompressorError::InvalidRange);
+        }
+        let (instructions, trailer) = compressed.split_at(compressed.len() - CHECKSUM_SIZE);
+        (instructions, Some(trailer))
+    } else {
+        (compressed, None)
+    };
+    let reversed: Vec<u8>;
+    let instructions = if opts.reverse {
+        reversed = instructions.iter().rev().copied().collect();
+        &reversed[..]
+    } else {
+        instructions
+    };
+
+    let mut result: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < instructions.len() {
+        let (instruction, size) = Instruction::decode(&instructions[i..])?;
+        match instruction {
+            Instruction::ZeroRun { len } => result.resize(result.len() + len as usize, 0x00),
+            // the leading zeros of a padded copy, stripped by `zip`, are put back first
+            Instruction::Copy { pad, bytes } => {
+                result.resize(result.len() + pad as usize, 0x00);
+                result.extend(bytes);
+            }
+            Instruction::DictShort { table, index } => {
+                result.extend(dict.read_tail(table, index as usize)?);
+            }
+            Instruction::DictLong { table, index } => {
+                result.extend(dict.read_tail(table, index as usize)?);
+            }
+        }
+        i += size;
+    }
+
+    if let Some(expected) = expected_checksum {
+        if checksum(&result) != expected {
+            return Err(CompressorError::ChecksumMismatch);
+        }
+    }
+    Ok(Bytes::from(result))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs::File, io::Read, str::FromStr};
+
+    use serde::Deserialize;
 
     use super::*;
-    use crate::assert_json_eq;
+    use crate::{
+        assert_json_eq, options::DEFAULT_MAX_LOOKBACK, planner::GreedyPlanner,
+        utils::assert_snapshot,
+    };
     #[allow(dead_code)]
     #[derive(Debug, Deserialize)]
     struct TestData {
@@ -690,6 +1740,10 @@ mod tests {
         pub uncompress: String,
     }
 
+    // calldata of `test_compress_small` and its compressed output
+    pub(crate) const SMALL_CALLDATA: &str = "0xf433d35e04bf7fea9df9ef9f80d4a91a3c3dec84540583b7103c7a69f7bbd4b7585ef752847ebec11584e73282b6dec46dd8ea6464d69f4003581960f39d8492000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000003a13000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000000258220761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e89000000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000033390598000000000000000000000000000000000000000000000000000000000010f7df4000000000000000000000000000000000000000000000000000000000001cab680000000000000000000000000000000000000000000000000000dcc54f790800000000000000000000000000000000000000000000000000000000000001ba1700000000000000000000000000000000000000000000000017ac92ba438492fe0000000000000000000000000000000000000000000000000000018d2f8b7e8858220761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e89000000000000000000000000000000000000000000000000000000000000000fffffffffffffffffffffffffffffffffffffffffffffffffffffffccc6fa68000000000000000000000000000000000000000000000000000000000010f7df4000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000dcc54f790800000000000000000000000000000000000000000000000000000000000001ba1800000000000000000000000000000000000000000000000017ac92ba438492fe0000000000000000000000000000000000000000000000000000018d2f8b7e88";
+    const SMALL_COMPRESSED: &str = "40f45f33d35e04bf7fea9df9ef9f80d4a91a3c3dec84540583b7103c7a69f7bbd4b7585e5ef752847ebec11584e73282b6dec46dd8ea6464d69f4003581960f39d849200611b001c413a13006102001d40c0006102585e220761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e8900610f00194333390598006310f7df4000631cab68001844dcc54f7908006201ba17006817ac92ba438492fe000067018d2f8b7e8858225d0761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e8900610fff5dffffffffffffffffffffffffffffffffffffffffffffffffffffccc6fa68006310f7df40003844dcc54f7908006201ba18006817ac92ba438492fe001845018d2f8b7e88";
+
     fn read_calldata_file(file_path: &str) -> Result<TestData, Box<dyn std::error::Error>> {
         let mut file = File::open(file_path)?;
 
@@ -701,6 +1755,14 @@ mod tests {
         Ok(data)
     }
 
+    // one `start amount method` line per description
+    fn describe(descriptions: &[CompressDataDescription]) -> String {
+        descriptions
+            .iter()
+            .map(|d| format!("{} {} {}\n", d.start_byte, d.amount_bytes, d.method))
+            .collect()
+    }
+
     fn read_json_file(file_path: &str) -> Result<String, Box<dyn std::error::Error>> {
         let mut file = File::open(file_path)?;
 
@@ -723,7 +1785,7 @@ mod tests {
         let result = compress(calldata, wallet_addr, contract_addr, &empty_dict);
         assert!(result.is_ok());
         assert_eq!(
-            hex::encode(result.unwrap().compressed_data.to_vec()),
+            hex::encode(&result.unwrap().compressed_data),
             expected_compress
         );
     }
@@ -763,8 +1825,1330 @@ mod tests {
         let result = compress(calldata, wallet_addr, contract_addr, &empty_dict);
         assert!(result.is_ok());
         assert_eq!(
-            hex::encode(result.unwrap().compressed_data.to_vec()),
+            hex::encode(&result.unwrap().compressed_data),
          "40f45f33d35e04bf7fea9df9ef9f80d4a91a3c3dec84540583b7103c7a69f7bbd4b7585e5ef752847ebec11584e73282b6dec46dd8ea6464d69f4003581960f39d849200611b001c413a13006102001d40c0006102585e220761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e8900610f00194333390598006310f7df4000631cab68001844dcc54f7908006201ba17006817ac92ba438492fe000067018d2f8b7e8858225d0761ade872c94f85e62f1b24a74eec792aaa3677b6201071fd05c1698e8900610fff5dffffffffffffffffffffffffffffffffffffffffffffffffffffccc6fa68006310f7df40003844dcc54f7908006201ba18006817ac92ba438492fe001845018d2f8b7e88"
         );
     }
+
+    #[test]
+    fn test_compress_big_descriptions() {
+        let test_data = read_calldata_file("test-data/calldata.json").unwrap();
+        let calldata = Bytes::from_str(&test_data.uncompress).unwrap();
+        let result = compress(
+            calldata,
+            Bytes32::default(),
+            Bytes32::default(),
+            &[Bytes32::default()],
+        )
+        .unwrap();
+        assert_snapshot("compress_big_descriptions", &describe(&result.description));
+    }
+
+    #[test]
+    fn test_compress_small_result() {
+        let calldata = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let result = compress(
+            calldata,
+            Bytes32::default(),
+            Bytes32::default(),
+            &[Bytes32::default()],
+        )
+        .unwrap();
+        assert_eq!(hex::encode(&result.compressed_data), SMALL_COMPRESSED);
+        assert_eq!(
+            (result.gas_uncompressed(), result.gas_compressed()),
+            (574 * 4 + 226 * 16, 20 * 4 + 257 * 16)
+        );
+        assert_eq!(
+            (result.zero_bytes_before(), result.zero_bytes_after()),
+            (574, 20)
+        );
+        assert_eq!(result.saved_bytes(), 800 - 277);
+        assert_eq!(result.compression_ratio(), 277.0 / 800.0);
+        assert_eq!(result.instruction_count(), result.description.len());
+        // sizes are read from the buffers, whatever `power` says
+        let tampered = CompressResult {
+            power: CompressDataPower::default(),
+            ..result.clone()
+        };
+        assert_eq!(tampered.saved_bytes(), result.saved_bytes());
+        assert_eq!(tampered.stream_ratio(), result.stream_ratio());
+        assert_eq!(tampered.compression_ratio(), result.compression_ratio());
+        assert_eq!(tampered.instruction_count(), result.instruction_count());
+        assert_snapshot(
+            "compress_small_descriptions",
+            &describe(&result.description),
+        );
+    }
+
+    #[test]
+    fn test_compress_with_strategy() {
+        let small = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let fixtures = ["test-data/calldata.json", "test-data/big_calldata.json"].map(|path| {
+            let fixture: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            Bytes::from_str(fixture["uncompress"].as_str().unwrap()).unwrap()
+        });
+        let mut sparse = vec![0x00; 4096];
+        for i in (31..sparse.len()).step_by(97) {
+            sparse[i] = 0x01;
+        }
+        let sparse = Bytes::from(sparse);
+        let dict = [small[4..36].try_into().unwrap()];
+        for data in [&small, &fixtures[0], &fixtures[1], &sparse] {
+            let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+            cb.init_dict(&dict);
+            let optimal = cb
+                .compress_with_strategy(CompressionStrategy::Optimal)
+                .unwrap();
+            assert_eq!(
+                optimal.compressed_data,
+                cb.compress().unwrap().compressed_data
+            );
+            let fast = cb
+                .compress_with_strategy(CompressionStrategy::Fast)
+                .unwrap();
+            assert_eq!(
+                fast.description,
+                cb.plan_with(&GreedyPlanner, &CompressOptions::default())
+                    .unwrap()
+            );
+            assert_eq!(
+                decompress(fast.compressed_data.as_raw(), [0xaa; 32], [0xbb; 32], &dict),
+                Ok(data.clone())
+            );
+            if data == &sparse {
+                // the greedy pass loses on zero-heavy data, see `CompressProfile`
+                assert!(fast.compressed_data.len() > optimal.compressed_data.len());
+            }
+            if data == &small {
+                // but the DP overrates the parts ending with zero runs, see `OptimalDpPlanner`
+                assert!(fast.compressed_data.len() < optimal.compressed_data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_method_toggles() {
+        let calldata = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let word: Bytes32 = calldata[224..256].try_into().unwrap();
+        let near = Dict::new([0xaa; 32], [0xbb; 32], &[word]);
+        let far = Dict::new(
+            [0xaa; 32],
+            [0xbb; 32],
+            &[vec![[0x99; 32]; 5000], vec![word]].concat(),
+        );
+        let default = CompressOptions::default();
+
+        for toggles in 0..16 {
+            let opts = CompressOptions {
+                enable_zero: toggles & 1 != 0,
+                enable_copy_padding: toggles & 2 != 0,
+                enable_dict_short: toggles & 4 != 0,
+                enable_dict_long: toggles & 8 != 0,
+                gas_golf: toggles == 0,
+                ..CompressOptions::default()
+            };
+            for (dict, max_lookback) in [
+                (&near, DEFAULT_MAX_LOOKBACK),
+                (&far, DEFAULT_MAX_LOOKBACK),
+                (&near, 8),
+                (&near, 0),
+            ] {
+                let opts = CompressOptions {
+                    max_lookback,
+                    ..opts.clone()
+                };
+                let mut cb =
+                    Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr())
+                        .unwrap();
+                cb.set_dict(dict);
+                let result = cb.compress_with(&opts).unwrap();
+                assert_eq!(
+                    decompress_with(&result.compressed_data, dict, &opts),
+                    Ok(calldata.clone()),
+                    "{opts:?}"
+                );
+
+                let enabled = |method| match method {
+                    Method::Zeros => opts.enable_zero,
+                    Method::Copy => true,
+                    Method::DictShort => opts.enable_dict_short,
+                    Method::DictLong => opts.enable_dict_long,
+                };
+                assert!(result.description.iter().all(|d| enabled(d.method)));
+                let mut stream = &result.compressed_data[..];
+                while !stream.is_empty() {
+                    let (instruction, size) = Instruction::decode(stream).unwrap();
+                    assert!(
+                        opts.enable_copy_padding
+                            || !matches!(instruction, Instruction::Copy { pad: 1.., .. })
+                    );
+                    stream = &stream[size..];
+                }
+                // the repeated word is referenced as long as a method can reach its index
+                if max_lookback == DEFAULT_MAX_LOOKBACK {
+                    let referenced = result.description.iter().any(|d| d.method.is_storage());
+                    assert_eq!(
+                        referenced,
+                        opts.enable_dict_long || (opts.enable_dict_short && dict == &near),
+                        "{opts:?}"
+                    );
+                }
+            }
+        }
+
+        // without `0x10`, the word takes 3 bytes instead of 2
+        let mut cb =
+            Calldata::new(calldata.clone(), near.wallet_addr(), near.contract_addr()).unwrap();
+        cb.set_dict(&near);
+        let short = cb.compress_with(&default).unwrap();
+        let long = cb
+            .compress_with(&CompressOptions {
+                enable_dict_short: false,
+                ..default.clone()
+            })
+            .unwrap();
+        assert_eq!(long.compressed_data.len(), short.compressed_data.len() + 2);
+        // a shorter lookback only restricts the plans the DP considers
+        let hasty = cb
+            .compress_with(&CompressOptions {
+                max_lookback: 0,
+                ..default
+            })
+            .unwrap();
+        assert!(hasty.compressed_data.len() >= short.compressed_data.len());
+    }
+
+    #[test]
+    fn test_power_range_inflating() {
+        // a single non-zero byte costs a copy instruction byte on top of itself
+        assert_eq!(CompressDataPower::new(1, 2).range(), -1);
+        let mut cb = Calldata::new(Bytes::from(vec![0x07]), [0xee; 32], [0xff; 32]).unwrap();
+        cb.init_dict(&[]);
+        let result = cb.compress().unwrap();
+        assert_eq!(result.power.range(), -1);
+        assert_eq!(
+            result.compressed_data.as_raw(),
+            &Bytes::from(vec![0x40, 0x07])
+        );
+    }
+
+    #[test]
+    fn test_forbid_inflation() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let data: Vec<u8> = (0..256)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 56) as u8 | 0x01
+            })
+            .collect();
+        let mut cb = Calldata::new(Bytes::from(data), [0xee; 32], [0xff; 32]).unwrap();
+        cb.init_dict(&[]);
+        let opts = CompressOptions {
+            forbid_inflation: true,
+            ..CompressOptions::default()
+        };
+        // 8 literal copies of 32 bytes
+        assert_eq!(
+            cb.compress_with(&opts).unwrap_err(),
+            CompressorError::NoCompression {
+                uncompressed: 256,
+                compressed: 264,
+            }
+        );
+        assert_eq!(cb.compress().unwrap().compressed_data.len(), 264);
+
+        let mut zeros = Calldata::new(Bytes::from(vec![0x00; 64]), [0xee; 32], [0xff; 32]).unwrap();
+        zeros.init_dict(&[]);
+        assert_eq!(zeros.compress_with(&opts).unwrap().compressed_data.len(), 1);
+    }
+
+    #[test]
+    fn test_get_bytes_zero_length() {
+        let cb = Calldata::new(
+            Bytes::from(vec![0x01, 0x02, 0x03]),
+            Bytes32::default(),
+            Bytes32::default(),
+        )
+        .unwrap();
+        assert_eq!(cb.get_bytes(0, 0), Ok(&[][..]));
+        assert_eq!(cb.get_bytes(3, 0), Ok(&[][..]));
+        assert_eq!(cb.get_bytes(4, 0), Err(CompressorError::InvalidRange));
+        assert_eq!(cb.get_bytes(1, 5), Ok(&[0x02, 0x03][..]));
+        assert_eq!(cb.get_bytes(3, 1), Err(CompressorError::InvalidRange));
+    }
+
+    #[test]
+    fn test_zip_storage_width() {
+        // a hit at index 0, whose reference has no bits set but the method and the length
+        let wallet = [0x42; 32];
+        let mut cb = Calldata::new(Bytes::from(wallet.to_vec()), wallet, [0x77; 32]).unwrap();
+        cb.init_dict(&[]);
+        let result = cb.compress().unwrap();
+        assert_eq!(
+            result.description,
+            [CompressDataDescription::new(0, 32, Method::DictShort)]
+        );
+        assert_eq!(result.compressed_data[..], [0x80, 0x00]);
+
+        for (method, amount_bytes, expected) in [
+            (Method::DictShort, 32, &[0x80, 0x00][..]),
+            (Method::DictShort, 4, &[0xa0, 0x00]),
+            (Method::DictLong, 32, &[0xc0, 0x00, 0x00]),
+            (Method::DictLong, 20, &[0xd0, 0x00, 0x00]),
+        ] {
+            let description = CompressDataDescription::new(32 - amount_bytes, amount_bytes, method);
+            assert_eq!(cb.zip(&[description]).unwrap(), expected, "{method}");
+        }
+
+        // indices past the bits of the reference
+        let word = CompressDataDescription::new(0, 32, Method::DictShort);
+        Arc::make_mut(&mut cb.dict)
+            .lookup
+            .insert(wallet.to_vec(), MAX_SHORT_INDEX as usize + 1);
+        assert_eq!(cb.zip(&[word]), Err(CompressorError::DictTooLarge(4096)));
+        let word = CompressDataDescription::new(0, 32, Method::DictLong);
+        assert_eq!(
+            cb.zip(std::slice::from_ref(&word)).unwrap(),
+            [0xc0, 0x10, 0x00]
+        );
+        Arc::make_mut(&mut cb.dict)
+            .lookup
+            .insert(wallet.to_vec(), MAX_LONG_INDEX as usize + 1);
+        assert_eq!(cb.zip(&[word]), Err(CompressorError::DictTooLarge(1 << 20)));
+        // which the planner doesn't reference
+        for suffix in Arc::make_mut(&mut cb.dict).lookup.values_mut() {
+            *suffix = MAX_LONG_INDEX as usize + 1;
+        }
+        let result = cb.compress().unwrap();
+        assert_eq!(result.stats().storage_references, 0);
+        assert_eq!(
+            result.compressed_data[..],
+            [[0x5f].as_slice(), &wallet].concat()
+        );
+    }
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let entries = [[0x11; 32], [0x22; 32]];
+        let data = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &entries[0]].concat());
+        let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&entries);
+        let opts = CompressOptions {
+            verify_roundtrip: true,
+            ..CompressOptions::default()
+        };
+        for opts in [
+            opts.clone(),
+            CompressOptions {
+                checksum: true,
+                reverse: true,
+                ..opts.clone()
+            },
+        ] {
+            assert!(cb.compress_with(&opts).is_ok());
+        }
+
+        // a lookup out of sync with the entries: `zip` references the second entry where the
+        // data holds the first one
+        for index in Arc::make_mut(&mut cb.dict).lookup.values_mut() {
+            if *index == 2 {
+                *index = 3;
+            }
+        }
+        let unchecked = cb.compress().unwrap();
+        assert_ne!(
+            decompress(
+                unchecked.compressed_data.as_raw(),
+                [0xaa; 32],
+                [0xbb; 32],
+                &entries
+            ),
+            Ok(data.clone())
+        );
+        let Err(CompressorError::RoundtripMismatch(mismatch)) = cb.compress_with(&opts) else {
+            panic!("mis-compression not detected");
+        };
+        assert_eq!(mismatch.offset, 4);
+        assert_eq!(
+            mismatch.description,
+            Some(CompressDataDescription::new(4, 32, Method::DictShort))
+        );
+        assert_eq!(mismatch.window_start, 0);
+        assert_eq!(mismatch.expected[..], data[..32]);
+        assert_eq!(mismatch.actual[..4], data[..4]);
+        assert_eq!(mismatch.actual[4..], [0x22; 28]);
+    }
+
+    #[test]
+    fn test_compress_with_dict() {
+        let entries: Vec<Bytes32> = (0..64_u8).map(|i| [i | 0x80; 32]).collect();
+        let dict = Arc::new(Dict::new([0xaa; 32], [0xbb; 32], &entries));
+        let payloads: Vec<Bytes> = (0..32_u8)
+            .map(|i| {
+                let entry = &entries[i as usize * 2];
+                Bytes::from(
+                    [
+                        [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                        entry,
+                        &[0x00; 31],
+                        &[i],
+                    ]
+                    .concat(),
+                )
+            })
+            .collect();
+        let expected: Vec<CompressResult> = payloads
+            .iter()
+            .map(|data| {
+                let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+                cb.set_dict(&dict);
+                cb.compress().unwrap()
+            })
+            .collect();
+        assert!(expected
+            .iter()
+            .all(|result| result.stats().storage_references == 1));
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let dict = Arc::clone(&dict);
+                let (payloads, expected) = (&payloads, &expected);
+                scope.spawn(move || {
+                    for (data, expected) in payloads.iter().zip(expected).skip(thread).step_by(4) {
+                        let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+                        let result = cb
+                            .compress_with_dict(&dict, &CompressOptions::default())
+                            .unwrap();
+                        assert_eq!(&result, expected);
+                        // the dictionary is shared, not copied, and `zip` reads it too
+                        assert!(Arc::ptr_eq(&cb.dict, &dict));
+                        assert_eq!(
+                            cb.zip(&result.description).unwrap(),
+                            result.compressed_data.as_raw()[..]
+                        );
+                    }
+                });
+            }
+        });
+        assert_eq!(Arc::strong_count(&dict), 1);
+    }
+
+    #[test]
+    fn test_compress_short_inputs() {
+        let wallet = [0xaa; 32];
+        let contract = [0xbb; 32];
+        let planners: [Option<Arc<dyn Planner>>; 2] = [None, Some(Arc::new(GreedyPlanner))];
+        for data in [
+            &[][..],
+            &[0x00],
+            &[0x07],
+            &[0x00, 0x00],
+            &[0x07, 0x00],
+            &[0xaa, 0xaa],
+        ] {
+            for dict in [&[][..], &[[0x07; 32]]] {
+                for planner in &planners {
+                    for (checksum, reverse, gas_golf) in [(false, false, false), (true, true, true)]
+                    {
+                        let opts = CompressOptions {
+                            planner: planner.clone(),
+                            checksum,
+                            reverse,
+                            gas_golf,
+                            ..CompressOptions::default()
+                        };
+                        let dict = Dict::new(wallet, contract, dict);
+                        let mut cb =
+                            Calldata::new(Bytes::copy_from_slice(data), wallet, contract).unwrap();
+                        cb.set_dict(&dict);
+                        let result = cb.compress_with(&opts).unwrap();
+                        assert_eq!(result.power.decompressed_size, data.len());
+                        assert_eq!(
+                            decompress_with(&result.compressed_data, &dict, &opts),
+                            Ok(Bytes::copy_from_slice(data)),
+                            "{data:?} {opts:?}"
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut cb = Calldata::new(Bytes::new(), wallet, contract).unwrap();
+        cb.init_dict(&[]);
+        let result = cb.compress().unwrap();
+        assert!(result.compressed_data.is_empty());
+        assert!(result.description.is_empty());
+        assert_eq!(result.power, CompressDataPower::default());
+        assert_eq!(
+            compress(Bytes::new(), wallet, contract, &[])
+                .unwrap()
+                .description,
+            []
+        );
+    }
+
+    #[test]
+    fn test_compress_empty() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[[0x11; 32]]);
+        let result = compress(Bytes::new(), dict.wallet_addr(), dict.contract_addr(), &[]).unwrap();
+        assert!(result.compressed_data.is_empty());
+        assert_eq!(result.uncompressed_data, Bytes::new());
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+            Ok(Bytes::new())
+        );
+
+        let mut cb = Calldata::new(Bytes::new(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        cb.analyse();
+        let opts = CompressOptions::auto(&[]);
+        assert_eq!(opts.profile, Some(CompressProfile::Tiny));
+        assert!(cb.compress_analysed(&opts).unwrap().description.is_empty());
+        // nothing can be saved on nothing
+        assert_eq!(
+            cb.compress_with(&CompressOptions {
+                forbid_inflation: true,
+                ..CompressOptions::default()
+            })
+            .unwrap_err(),
+            CompressorError::NoCompression {
+                uncompressed: 0,
+                compressed: 0,
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_compress_result_serde() {
+        let calldata = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x42; 32]].concat());
+        let mut cb = Calldata::new(calldata, [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[[0x42; 32]]);
+        let data = cb.data().clone();
+        let result = cb
+            .compress_with(&CompressOptions {
+                checksum: true,
+                ..CompressOptions::auto(&data)
+            })
+            .unwrap();
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["uncompressed_data"], data.to_string());
+        assert_eq!(
+            json["compressed_data"],
+            result.compressed_data.as_raw().to_string()
+        );
+        assert_eq!(json["description"][1]["method"], "storage-2");
+        assert_eq!(json["profile"], "Tiny");
+        let decoded: CompressResult = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, result);
+
+        // the deserialized plan encodes the same payload again
+        let small = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let result = compress(small, [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let json = serde_json::to_string(&result).unwrap();
+        let decoded: CompressResult = serde_json::from_str(&json).unwrap();
+        let mut cb =
+            Calldata::new(decoded.uncompressed_data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[]);
+        assert_eq!(
+            cb.zip(&decoded.description).unwrap(),
+            result.compressed_data.to_vec()
+        );
+
+        let part = CompressData::new(result.power, result.description.clone());
+        let json = serde_json::to_value(&part).unwrap();
+        assert_eq!(
+            json["descriptions"][0]["method"],
+            part.descriptions[0].method.name()
+        );
+        let decoded: CompressData = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.descriptions, part.descriptions);
+        assert_eq!(decoded.power, part.power);
+        assert!(serde_json::from_str::<Method>("\"dict2\"").is_err());
+    }
+
+    #[test]
+    fn test_address_like() {
+        let wallet = [0x5a; 20];
+        let contract = Address::repeat_byte(0xbb);
+        let mut word = Bytes32::default();
+        word[12..].copy_from_slice(&wallet);
+        assert_eq!(wallet.to_word(), word);
+        assert_eq!(contract.to_word(), contract.into_word().0);
+        let dict = Dict::new(wallet, contract, &[]);
+        assert_eq!(
+            (dict.index_of(&wallet), dict.index_of(&word)),
+            (Some(0), Some(0))
+        );
+
+        // the wallet as an abi argument, then packed as by `abi.encodePacked`
+        let calldata =
+            Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &word, &[0x07], &wallet].concat());
+        let result = compress(calldata.clone(), wallet, contract, &[]).unwrap();
+        // both copies of the address are read from the wallet slot
+        let references: Vec<_> = unzip(&result.instruction_stream())
+            .unwrap()
+            .into_iter()
+            .filter(|instruction| instruction.dict_index == Some(0))
+            .map(|instruction| {
+                let description = instruction.description;
+                description.start_byte..description.start_byte + description.amount_bytes
+            })
+            .collect();
+        assert!(references
+            .iter()
+            .any(|r| r.contains(&16) && r.contains(&35)));
+        assert!(references.contains(&(37..57)));
+        assert_eq!(
+            decompress(result.compressed_data.as_raw(), wallet, contract, &[]),
+            Ok(calldata.clone())
+        );
+        // the padded word is the same dictionary slot
+        assert_eq!(
+            compress(calldata, word, contract, &[])
+                .unwrap()
+                .compressed_data,
+            result.compressed_data
+        );
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let cb = Calldata::from_hex(SMALL_CALLDATA, [0xaa; 32], [0xbb; 32]).unwrap();
+        assert_eq!(cb.data(), &Bytes::from_str(SMALL_CALLDATA).unwrap());
+        assert_eq!((cb.wallet_addr, cb.contract_addr), ([0xaa; 32], [0xbb; 32]));
+        let upper = SMALL_CALLDATA[2..].to_uppercase();
+        assert_eq!(
+            Calldata::from_hex(&upper, [0xaa; 32], [0xbb; 32])
+                .unwrap()
+                .data(),
+            cb.data()
+        );
+        // digits are checked one by one, no length limit
+        let long: String = (0..200_u8).map(|b| format!("{b:02x}")).collect();
+        let cb = Calldata::from_hex(&long, [0xaa; 32], [0xbb; 32]).unwrap();
+        assert_eq!(cb.data(), &Bytes::from((0..200_u8).collect::<Vec<_>>()));
+        assert_eq!(
+            Calldata::from_hex("0xa9059cb", [0xaa; 32], [0xbb; 32]).unwrap_err(),
+            CompressorError::OddHexLength(7)
+        );
+        assert_eq!(
+            Calldata::from_hex("0xzz", [0xaa; 32], [0xbb; 32]).unwrap_err(),
+            CompressorError::InvalidHex { position: 2 }
+        );
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let small = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let result = compress(small, [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "800 -> 277 bytes, 523 saved (65.38%); \
+             zero-run: 20 segments / 206 bytes, copy: 25 segments / 594 bytes"
+        );
+        let calldata = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x42; 32]].concat());
+        let result = compress(calldata, [0xaa; 32], [0xbb; 32], &[[0x42; 32]]).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "36 -> 7 bytes, 29 saved (80.56%); \
+             copy: 1 segments / 4 bytes, storage-2: 1 segments / 32 bytes"
+        );
+        let result = compress(Bytes::new(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert_eq!(result.to_string(), "0 -> 0 bytes, 0 saved (0.00%)");
+    }
+
+    #[test]
+    fn test_ratio() {
+        let result = compress(Bytes::from(vec![0x00; 64]), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert_eq!(
+            (result.stream_ratio(), result.saved_bytes()),
+            (1.0 / 64.0, 63)
+        );
+        // a single byte is copied with its tag
+        let result = compress(Bytes::from(vec![0x2a]), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert_eq!((result.stream_ratio(), result.saved_bytes()), (2.0, -1));
+        let result = compress(Bytes::new(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert!(result.stream_ratio().is_nan());
+        assert!(result.compression_ratio().is_nan());
+        assert_eq!(result.saved_bytes(), 0);
+        assert_eq!(result.instruction_count(), 0);
+
+        // the checksum trailer is sent too, but isn't an instruction
+        let mut cb = Calldata::new(Bytes::from(vec![0x00; 64]), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[]);
+        let result = cb
+            .compress_with(&CompressOptions {
+                checksum: true,
+                ..CompressOptions::default()
+            })
+            .unwrap();
+        assert_eq!(result.stream_ratio(), 1.0 / 64.0);
+        #[allow(deprecated)]
+        let ratio = result.ratio();
+        assert_eq!(ratio, result.stream_ratio());
+        assert_eq!(result.compression_ratio(), 5.0 / 64.0);
+        assert_eq!(result.saved_bytes(), 64 - 5);
+        assert_eq!(result.instruction_count(), 1);
+    }
+
+    #[test]
+    fn test_set_data() {
+        let first = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let second = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x42; 32]].concat());
+        let dict = [first[4..36].try_into().unwrap(), [0x42; 32]];
+        let fresh = |data: &Bytes| {
+            let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+            cb.init_dict(&dict);
+            cb.compress().unwrap().compressed_data
+        };
+
+        let mut cb = Calldata::new(first.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&dict);
+        let lookup = cb.dict.lookup.clone();
+        assert_eq!(cb.compress().unwrap().compressed_data, fresh(&first));
+        // a shorter payload, then back to the first one
+        for data in [&second, &first] {
+            cb.set_data(data);
+            assert_eq!(cb.bytes_info.len(), data.len());
+            assert_eq!(cb.dict.lookup, lookup);
+            assert_eq!(cb.compress().unwrap().compressed_data, fresh(data));
+        }
+    }
+
+    #[test]
+    fn test_zip_run_too_long() {
+        let mut cb = Calldata::new(Bytes::from(vec![0x00; 100]), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[]);
+        assert_eq!(
+            cb.zip(&[CompressDataDescription::new(0, 100, Method::Zeros)]),
+            Err(CompressorError::RunTooLong(100))
+        );
+        assert_eq!(
+            cb.zip(&[
+                CompressDataDescription::new(0, 64, Method::Zeros),
+                CompressDataDescription::new(64, 36, Method::Zeros),
+            ]),
+            Ok(vec![0x3f, 0x23])
+        );
+        // nor can a run of no byte
+        assert_eq!(
+            cb.zip(&[
+                CompressDataDescription::new(0, 64, Method::Zeros),
+                CompressDataDescription::new(64, 0, Method::Zeros),
+            ]),
+            Err(CompressorError::InvalidPlan(1))
+        );
+        // the planner splits the run itself
+        let result = cb.compress().unwrap();
+        assert_eq!(result.description.len(), 2);
+        assert!(result.description.iter().all(|d| d.amount_bytes <= 64));
+    }
+
+    #[test]
+    fn test_zip_copy_length() {
+        let data = Bytes::from((1..=40).collect::<Vec<u8>>());
+        let mut cb = Calldata::new(data, [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[]);
+        // a copy longer than the 5 bits of its tag count, or of no byte, is rejected
+        for plan in [
+            vec![CompressDataDescription::new(0, 40, Method::Copy)],
+            vec![CompressDataDescription::new(0, 33, Method::Copy)],
+            vec![
+                CompressDataDescription::new(0, 4, Method::Copy),
+                CompressDataDescription::new(4, 0, Method::Copy),
+            ],
+        ] {
+            let invalid = Err(CompressorError::InvalidPlan(plan.len() - 1));
+            assert_eq!(cb.zip(&plan), invalid, "{plan:?}");
+            #[cfg(feature = "std")]
+            assert_eq!(
+                cb.zip_into(&plan, &mut Vec::new()),
+                invalid.clone().map(|_| 0)
+            );
+            #[cfg(feature = "bytes")]
+            assert_eq!(
+                cb.zip_into_buf(&plan, &mut bytes::BytesMut::new()),
+                invalid.map(|_| 0)
+            );
+        }
+        let plan = [
+            CompressDataDescription::new(0, 32, Method::Copy),
+            CompressDataDescription::new(32, 8, Method::Copy),
+        ];
+        assert_eq!(cb.zip(&plan).unwrap()[..2], [0x5f, 0x01]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_zip_into() {
+        struct FailingWriter(usize); // accepts this many bytes
+
+        impl std::io::Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let data =
+            Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x00; 31], &[0x07]].concat());
+        let mut cb = Calldata::new(data, Bytes32::default(), Bytes32::default()).unwrap();
+        cb.init_dict(&[]);
+        let result = cb.compress().unwrap();
+        let zipped = cb.zip(&result.description).unwrap();
+
+        // appends to what the sink already holds
+        let mut sink = vec![0xff];
+        assert_eq!(
+            cb.zip_into(&result.description, &mut sink),
+            Ok(zipped.len())
+        );
+        assert_eq!(sink, [[0xff].as_slice(), &zipped].concat());
+        #[cfg(feature = "bytes")]
+        {
+            let mut buf = bytes::BytesMut::new();
+            assert_eq!(
+                cb.zip_into_buf(&result.description, &mut buf),
+                Ok(zipped.len())
+            );
+            assert_eq!(buf.as_ref(), zipped.as_slice());
+        }
+
+        assert_eq!(
+            cb.zip_into(&result.description, &mut FailingWriter(zipped.len() - 1)),
+            Err(CompressorError::Io("full".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_suggest_dict_additions() {
+        let known = [0x11; 32];
+        let frequent = [0x22; 32];
+        let rare = [0x33; 32];
+        let data = [
+            [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+            &frequent,
+            &known,
+            &[0x00; 32],
+            &frequent,
+            &rare,
+            &known,
+            &[0x00; 32],
+            &frequent,
+        ]
+        .concat();
+        let mut cb = Calldata::new(Bytes::from(data), [0xee; 32], [0xff; 32]).unwrap();
+        cb.init_dict(&[known]);
+        cb.analyse();
+
+        assert_eq!(cb.suggest_dict_additions(5), vec![(frequent, 3), (rare, 1)]);
+        assert_eq!(cb.suggest_dict_additions(1), vec![(frequent, 3)]);
+    }
+
+    #[test]
+    fn test_copy_accumulation_boundaries() {
+        for (len, expected) in [
+            (32, vec![32]),
+            (33, vec![32, 1]),
+            (64, vec![32, 32]),
+            (65, vec![32, 32, 1]),
+        ] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 255 + 1) as u8).collect();
+            let mut cb = Calldata::new(Bytes::from(data), [0xee; 32], [0xff; 32]).unwrap();
+            cb.init_dict(&[]);
+            cb.analyse();
+
+            let part = cb.compress_part(0, len - 1);
+            let amounts: Vec<usize> = part.descriptions.iter().map(|d| d.amount_bytes).collect();
+            assert_eq!(amounts, expected, "copy split of {len} bytes");
+            assert!(part.descriptions.iter().all(|d| d.method == Method::Copy));
+            let mut next_byte = 0;
+            for description in &part.descriptions {
+                assert_eq!(description.start_byte, next_byte);
+                next_byte += description.amount_bytes;
+            }
+            assert_eq!(part.power.decompressed_size, len);
+            assert_eq!(part.power.compressed_size, len + expected.len());
+
+            let result = cb.compress().unwrap();
+            assert_eq!(result.compressed_data.len(), len + expected.len());
+            assert_eq!(
+                result
+                    .description
+                    .iter()
+                    .map(|d| d.amount_bytes)
+                    .sum::<usize>(),
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_part_tails_match_compress_part() {
+        let fixture = read_calldata_file("test-data/calldata.json").unwrap();
+        for data in [
+            Bytes::from_str(SMALL_CALLDATA).unwrap(),
+            Bytes::from_str(&fixture.uncompress).unwrap().slice(..4096),
+        ] {
+            let mut cb = Calldata::new(data, [0xaa; 32], [0xbb; 32]).unwrap();
+            cb.init_dict(&[]);
+            cb.analyse();
+            for objective in [CompressObjective::Bytes, CompressObjective::Gas] {
+                let opts = CompressOptions {
+                    objective,
+                    ..Default::default()
+                };
+                let model = (objective == CompressObjective::Gas).then_some(&MainnetCalldata as _);
+                let mut tails = PartTails::new(cb.bytes_info.len(), opts.max_lookback);
+                for to_byte in 0..cb.bytes_info.len() {
+                    tails.start(&cb.bytes_info, to_byte, opts.max_lookback);
+                    for from_byte in (to_byte.saturating_sub(opts.max_lookback)..=to_byte).rev() {
+                        let tail = tails
+                            .part(&cb.bytes_info, &cb.data, &opts, model, to_byte, from_byte)
+                            .clone();
+                        let part = cb.compress_part(from_byte, to_byte);
+                        let mut power = tail.power;
+                        if tail.ends_with_zeros {
+                            power.decompressed_size += to_byte - from_byte + 1;
+                        }
+                        assert_eq!(power, part.power, "part {from_byte}..={to_byte}");
+                        if objective == CompressObjective::Gas {
+                            assert_eq!(tail.gas_saved, part_saving(&opts, &cb.data, &part));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dict_word_across_window_boundary() {
+        // a 32-byte entry placed at every offset around the first two 63-byte DP window edges
+        let entry: Bytes32 = core::array::from_fn(|i| 0x80 + i as u8);
+        let dict = Dict::new([0xee; 32], [0xff; 32], &[entry]);
+        let filler: Vec<u8> = (0..200_u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8 | 0x01)
+            .collect();
+        for offset in (63 - 32..=63).chain(126 - 32..=126) {
+            let mut data = filler.clone();
+            data[offset..offset + 32].copy_from_slice(&entry);
+            let mut cb = Calldata::new(
+                Bytes::from(data.clone()),
+                dict.wallet_addr(),
+                dict.contract_addr(),
+            )
+            .unwrap();
+            cb.set_dict(&dict);
+            let result = cb.compress().unwrap();
+            assert!(
+                result.description.contains(&CompressDataDescription::new(
+                    offset,
+                    32,
+                    Method::DictShort
+                )),
+                "entry at {offset} not referenced"
+            );
+            // the rest is copied in as few instructions as possible
+            let literals = data.len() - 32;
+            let copies = offset.div_ceil(32) + (data.len() - offset - 32).div_ceil(32);
+            assert_eq!(result.compressed_data.len(), 2 + literals + copies);
+            assert_eq!(
+                decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+                Ok(Bytes::from(data))
+            );
+        }
+    }
+
+    #[test]
+    fn test_reverse() {
+        let entry: Bytes32 = [0x42; 32];
+        let calldata = Bytes::from(
+            [
+                [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                &entry,
+                &[0x00; 31],
+                &[0x07],
+            ]
+            .concat(),
+        );
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[entry]);
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        let plain = cb.compress().unwrap();
+
+        for checksum in [false, true] {
+            let opts = CompressOptions {
+                checksum,
+                reverse: true,
+                ..CompressOptions::default()
+            };
+            let result = cb.compress_with(&opts).unwrap();
+            assert!(result.reverse);
+            assert_eq!(result.description, plain.description);
+            let stream_len = plain.compressed_data.len();
+            let stream: Vec<u8> = result.compressed_data[..stream_len]
+                .iter()
+                .rev()
+                .copied()
+                .collect();
+            assert_eq!(stream, plain.compressed_data.to_vec());
+            // the method byte of the first description is read first from the end
+            assert_eq!(result.compressed_data[stream_len - 1] >> 6, 0b01);
+
+            assert_eq!(
+                decompress_reverse(&result.compressed_data, &dict, &opts),
+                Ok(calldata.clone())
+            );
+            assert_eq!(
+                decompress_with(&result.compressed_data, &dict, &opts),
+                Ok(calldata.clone())
+            );
+            assert_eq!(result.verify(&dict), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_gas_objective() {
+        let gas = CompressOptions {
+            objective: CompressObjective::Gas,
+            ..CompressOptions::default()
+        };
+        let compress = |data: &Bytes, opts: &CompressOptions| {
+            let mut cb = Calldata::new(data.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+            cb.init_dict(&[]);
+            cb.compress_with(opts).unwrap()
+        };
+
+        // a run of two zeros is a non-zero tag, two runs of one are zero tags
+        let zeros = Bytes::from(vec![0x00; 2]);
+        let bytes = compress(&zeros, &CompressOptions::default());
+        assert_eq!(bytes.compressed_data.as_raw(), &Bytes::from(vec![0x01]));
+        let result = compress(&zeros, &gas);
+        assert_eq!(result.compressed_data.as_raw(), &Bytes::from(vec![0x00; 2]));
+        assert_eq!(result.objective, CompressObjective::Gas);
+        assert_eq!((bytes.gas_compressed(), result.gas_compressed()), (16, 8));
+
+        let fixture = read_calldata_file("test-data/calldata.json").unwrap();
+        for data in [
+            Bytes::from_str(SMALL_CALLDATA).unwrap(),
+            Bytes::from_str(&fixture.uncompress).unwrap().slice(..8192),
+        ] {
+            let bytes = compress(&data, &CompressOptions::default());
+            let result = compress(&data, &gas);
+            assert!(result.gas_compressed() < bytes.gas_compressed());
+            assert_eq!(
+                decompress(result.compressed_data.as_raw(), [0xaa; 32], [0xbb; 32], &[]),
+                Ok(data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_gas_objective_fixture() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("test-data/big_calldata.json").unwrap())
+                .unwrap();
+        let data = Bytes::from_str(fixture["uncompress"].as_str().unwrap()).unwrap();
+        let mut cb = Calldata::new(data.clone(), Bytes32::default(), Bytes32::default()).unwrap();
+        cb.init_dict(&[]);
+        let bytes = cb.compress().unwrap();
+        let gas = cb
+            .compress_with(&CompressOptions {
+                objective: CompressObjective::Gas,
+                ..CompressOptions::default()
+            })
+            .unwrap();
+        assert!(gas.saved_gas() >= 0);
+        assert!(gas.saved_gas() >= bytes.saved_gas());
+        assert_eq!(
+            gas.saved_gas(),
+            calldata_gas(&data) as i64 - calldata_gas(&gas.compressed_data) as i64
+        );
+        assert_eq!(
+            decompress(
+                gas.compressed_data.as_raw(),
+                Bytes32::default(),
+                Bytes32::default(),
+                &[]
+            ),
+            Ok(data)
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let entry: Bytes32 = [0x42; 32];
+        let calldata = Bytes::from(
+            [
+                [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                &entry,
+                &[0x00; 31],
+                &[0x07],
+            ]
+            .concat(),
+        );
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[entry]);
+        let opts = CompressOptions {
+            checksum: true,
+            ..CompressOptions::default()
+        };
+
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        let plain = cb.compress().unwrap();
+        let result = cb.compress_with(&opts).unwrap();
+        assert!(result.checksum);
+        assert_eq!(result.objective, CompressObjective::Bytes);
+        assert_eq!(
+            result.compressed_data.len(),
+            plain.compressed_data.len() + CHECKSUM_SIZE
+        );
+        assert_eq!(
+            &result.compressed_data[plain.compressed_data.len()..],
+            &checksum(&calldata)
+        );
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &opts),
+            Ok(calldata)
+        );
+
+        // a dictionary mismatch decodes to different calldata
+        let other_dict = Dict::new(Bytes32::default(), Bytes32::default(), &[[0x24; 32]]);
+        assert!(decompress_with(
+            &plain.compressed_data,
+            &other_dict,
+            &CompressOptions::default()
+        )
+        .is_ok());
+        assert_eq!(
+            decompress_with(&result.compressed_data, &other_dict, &opts),
+            Err(CompressorError::ChecksumMismatch)
+        );
+        // corrupted trailer
+        let mut corrupted = result.compressed_data.to_vec();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            decompress_with(&corrupted, &dict, &opts),
+            Err(CompressorError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn test_decompress_big() {
+        let empty_dict = vec![Bytes32::default(); 1];
+        let test_data = read_calldata_file("test-data/calldata.json").unwrap();
+        let calldata = test_data.uncompress.strip_prefix("0x").unwrap();
+        let compressed = test_data.compress.strip_prefix("0x").unwrap();
+        let calldata = Bytes::from(hex::decode(calldata).unwrap());
+        let compressed = Bytes::from(hex::decode(compressed).unwrap());
+        let wallet_addr = Bytes32::default();
+        let contract_addr = Bytes32::default();
+        let result = decompress(&compressed, wallet_addr, contract_addr, &empty_dict);
+        assert_eq!(result, Ok(calldata));
+    }
+
+    #[test]
+    fn test_decompress_compress_output() {
+        let test_data = read_calldata_file("test-data/calldata.json").unwrap();
+        let calldata = Bytes::from_str(&test_data.uncompress).unwrap();
+        let (wallet_addr, contract_addr) = ([0xaa; 32], [0xbb; 32]);
+        for dict in [vec![], vec![calldata[4..36].try_into().unwrap()]] {
+            let result = compress(calldata.clone(), wallet_addr, contract_addr, &dict).unwrap();
+            assert_eq!(
+                decompress(
+                    result.compressed_data.as_raw(),
+                    wallet_addr,
+                    contract_addr,
+                    &dict
+                ),
+                Ok(calldata.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_errors() {
+        let (wallet_addr, contract_addr) = ([0xaa; 32], [0xbb; 32]);
+        let dict = [[0x11; 32]];
+        let decode = |hex: &str| {
+            decompress(
+                &Bytes::from_str(hex).unwrap(),
+                wallet_addr,
+                contract_addr,
+                &dict,
+            )
+        };
+
+        assert_eq!(decode("0x3f").unwrap().len(), 64);
+        assert_eq!(decode("0x4042").unwrap(), Bytes::from_str("0x42").unwrap());
+        assert_eq!(decode("0x8002").unwrap(), Bytes::from([0x11; 32]));
+        // truncated copy, 2-byte and 3-byte references
+        assert_eq!(decode("0x4142"), Err(CompressorError::InvalidRange));
+        assert_eq!(decode("0x80"), Err(CompressorError::InvalidRange));
+        assert_eq!(decode("0xc00002"), Ok(Bytes::from([0x11; 32])));
+        assert_eq!(decode("0xc000"), Err(CompressorError::InvalidRange));
+        assert_eq!(decode("0xc0"), Err(CompressorError::InvalidRange));
+        // a zero run never reads past its tag, the truncation is in what follows it
+        assert_eq!(decode("0x3f3fc0"), Err(CompressorError::InvalidRange));
+        // index 3 is past the end of the wallet, contract and single entry
+        assert_eq!(decode("0x8003"), Err(CompressorError::LookupNotFound));
+        assert_eq!(decode("0xcfffff"), Err(CompressorError::LookupNotFound));
+    }
+
+    #[test]
+    fn test_decompress_fixtures() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("test-data/big_calldata.json").unwrap())
+                .unwrap();
+        let calldata = Bytes::from_str(fixture["uncompress"].as_str().unwrap()).unwrap();
+        let compressed = Bytes::from_str(fixture["compressed"].as_str().unwrap()).unwrap();
+        let (wallet_addr, contract_addr) = (Bytes32::default(), Bytes32::default());
+        assert_eq!(
+            decompress(&compressed, wallet_addr, contract_addr, &[]),
+            Ok(calldata.clone())
+        );
+
+        let small = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        for data in [calldata, small] {
+            let result = compress(data.clone(), wallet_addr, contract_addr, &[]).unwrap();
+            assert_eq!(
+                decompress(
+                    result.compressed_data.as_raw(),
+                    wallet_addr,
+                    contract_addr,
+                    &[]
+                ),
+                Ok(data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_zero_run() {
+        let plain = |stream: &[u8]| decompress_with(stream, &Dict::default(), &Default::default());
+        assert_eq!(plain(&[0x00]), Ok(Bytes::from(vec![0x00])));
+        // the longest run
+        assert_eq!(plain(&[0x3f]), Ok(Bytes::from(vec![0x00; 64])));
+        // runs follow each other without merging their lengths
+        assert_eq!(plain(&[0x3f, 0x00]), Ok(Bytes::from(vec![0x00; 65])));
+        assert_eq!(
+            CompressorError::UnexpectedTag(0x40).to_string(),
+            "Unexpected instruction tag: `0x40`"
+        );
+    }
+
+    #[test]
+    fn test_method() {
+        for (byte, method, name) in [
+            (0x00, Method::Zeros, "zero-run"),
+            (0x01, Method::Copy, "copy"),
+            (0x10, Method::DictShort, "storage-2"),
+            (0x11, Method::DictLong, "storage-3"),
+        ] {
+            assert_eq!(Method::try_from(byte), Ok(method));
+            assert_eq!(u8::from(method), byte);
+            assert_eq!(method.to_string(), name);
+        }
+        // the literal 2 isn't the bit pattern `10`
+        for byte in [0x02, 0x0a, 0x12, 0xff] {
+            assert_eq!(
+                Method::try_from(byte),
+                Err(CompressorError::UnsupportedMethod(byte))
+            );
+        }
+    }
+
+    #[test]
+    fn test_unzip() {
+        let test_data = read_calldata_file("test-data/calldata.json").unwrap();
+        for data in [
+            Bytes::from_str(SMALL_CALLDATA).unwrap(),
+            Bytes::from_str(&test_data.uncompress).unwrap(),
+        ] {
+            let dict = [data[4..36].try_into().unwrap()];
+            let result = compress(data, [0xaa; 32], [0xbb; 32], &dict).unwrap();
+            let unzipped = unzip(&result.compressed_data).unwrap();
+            let descriptions: Vec<_> = unzipped.iter().map(|i| i.description.clone()).collect();
+            assert_eq!(descriptions, result.description);
+            assert!(unzipped
+                .iter()
+                .all(|i| i.dict_index.is_some() == i.description.method.is_storage()));
+        }
+
+        // a zero run, a padded copy of `0x2a`, then a 4-byte tail of index 4097
+        assert_eq!(
+            unzip(&[0x1f, 0x60, 0x2a, 0xe0, 0x10, 0x01]),
+            Ok(vec![
+                UnzippedInstruction {
+                    description: CompressDataDescription::new(0, 32, Method::Zeros),
+                    stream_offset: 0,
+                    dict_index: None,
+                },
+                UnzippedInstruction {
+                    description: CompressDataDescription::new(32, 32, Method::Copy),
+                    stream_offset: 1,
+                    dict_index: None,
+                },
+                UnzippedInstruction {
+                    description: CompressDataDescription::new(64, 4, Method::DictLong),
+                    stream_offset: 3,
+                    dict_index: Some(4097),
+                },
+            ])
+        );
+        assert_eq!(unzip(&[0x1f, 0x42]), Err(CompressorError::InvalidRange));
+    }
+
+    #[test]
+    fn test_read_copy() {
+        let plain = |stream: &[u8]| decompress_with(stream, &Dict::default(), &Default::default());
+        // `0x20` padded back to a whole abi word
+        let mut word = [0x00; 32];
+        word[31] = 0x20;
+        assert_eq!(plain(&[0x60, 0x20]), Ok(Bytes::from(word)));
+        // unpadded, 32 literal bytes
+        let literal: Vec<u8> = (1..=32).collect();
+        assert_eq!(
+            plain(&[[0x5f].as_slice(), &literal].concat()),
+            Ok(Bytes::from(literal.clone()))
+        );
+        // a padded copy of 32 bytes adds no padding
+        assert_eq!(
+            plain(&[[0x7f].as_slice(), &literal].concat()),
+            Ok(Bytes::from(literal))
+        );
+
+        assert_eq!(plain(&[0x41, 0x01]), Err(CompressorError::InvalidRange));
+    }
+
+    #[test]
+    fn test_decompress_small() {
+        // the exact output `test_compress_small` expects, decoded back
+        let compressed = Bytes::from_str(SMALL_COMPRESSED).unwrap();
+        let empty_dict = vec![Bytes32::default(); 1];
+        assert_eq!(
+            decompress(
+                &compressed,
+                Bytes32::default(),
+                Bytes32::default(),
+                &empty_dict
+            ),
+            Ok(Bytes::from_str(SMALL_CALLDATA).unwrap())
+        );
+    }
 }
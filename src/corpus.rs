@@ -0,0 +1,596 @@
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use alloy::primitives::Bytes;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::planner::Planner;
+use crate::{
+    compressor::{instruction_size, storage_index, Calldata, CompressResult, Method},
+    dict::Dict,
+    errors::CompressorError,
+    options::CompressOptions,
+};
+
+/// size of the function selector that precedes the abi.encoded arguments
+pub const SELECTOR_SIZE: usize = 4;
+/// size of an abi.encoded word
+pub const WORD_SIZE: usize = 32;
+
+/// byte range of the `bucket`-th word-aligned region of calldata.
+/// bucket 0 is the selector, bucket `k` is the `k`-th abi.encoded word after it.
+pub fn bucket_range(bucket: usize) -> Range<usize> {
+    if bucket == 0 {
+        return 0..SELECTOR_SIZE;
+    }
+    let start = SELECTOR_SIZE + (bucket - 1) * WORD_SIZE;
+    start..start + WORD_SIZE
+}
+
+/// index of the word-aligned bucket containing byte `offset`
+pub fn bucket_of(offset: usize) -> usize {
+    if offset < SELECTOR_SIZE {
+        0
+    } else {
+        (offset - SELECTOR_SIZE) / WORD_SIZE + 1
+    }
+}
+
+/// A word-aligned region of calldata that was emitted verbatim across a corpus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offender {
+    pub bucket: usize,         // bucket index, see `bucket_range`
+    pub range: Range<usize>,   // byte range of the bucket in the calldata
+    pub payloads: usize,       // number of payloads that copied at least one byte of the bucket
+    pub corpus_size: usize,    // number of payloads in the analysed corpus
+    pub copied_bytes: usize,   // verbatim bytes emitted for the bucket across the corpus
+    pub example: Bytes,        // contents of the bucket in the first offending payload
+    pub label: Option<String>, // argument name from the abi hint, if any
+}
+
+impl Offender {
+    // fraction of the corpus in which this bucket was (at least partially) copied verbatim
+    #[allow(clippy::cast_precision_loss)] // exact up to 2^53 payloads
+    pub fn share(&self) -> f64 {
+        if self.corpus_size == 0 {
+            return 0.0;
+        }
+        self.payloads as f64 / self.corpus_size as f64
+    }
+}
+
+/// Aligns the plans of `results` by word-aligned offset and reports the `top_n` buckets
+/// that cost the most verbatim-copy bytes, most expensive first.
+///
+/// Only the literal bytes a `0x01` instruction actually emits are counted, so the leading zeros
+/// of a 32-byte copy, stripped by the padding flag, are not blamed on their bucket. Those of a
+/// shorter copy are emitted, and counted. `arg_names` optionally labels
+/// the buckets after the selector, in abi argument order (`arg_names[0]` labels bytes `4..36`).
+pub fn worst_offenders(
+    results: &[CompressResult],
+    top_n: usize,
+    arg_names: Option<&[&str]>,
+) -> Vec<Offender> {
+    // bucket -> (payloads, copied bytes, example)
+    let mut buckets: BTreeMap<usize, (usize, usize, Bytes)> = BTreeMap::new();
+
+    for result in results {
+        let data = &result.uncompressed_data;
+        let mut copied: BTreeMap<usize, usize> = BTreeMap::new();
+        for description in result
+            .description
+            .iter()
+            .filter(|d| d.method == Method::Copy)
+        {
+            let end = core::cmp::min(
+                description.start_byte + description.amount_bytes,
+                data.len(),
+            );
+            // only word copies are padded, see `Calldata::zip`
+            let literal_start = match description.amount_bytes {
+                WORD_SIZE => (description.start_byte..end)
+                    .find(|&i| data[i] != 0x00)
+                    .unwrap_or(end),
+                _ => description.start_byte,
+            };
+            for i in literal_start..end {
+                *copied.entry(bucket_of(i)).or_default() += 1;
+            }
+        }
+
+        for (bucket, amount) in copied {
+            let range = bucket_range(bucket);
+            let entry = buckets.entry(bucket).or_insert_with(|| {
+                let example = data
+                    .get(range.start..core::cmp::min(range.end, data.len()))
+                    .unwrap_or_default();
+                (0, 0, Bytes::copy_from_slice(example))
+            });
+            entry.0 += 1;
+            entry.1 += amount;
+        }
+    }
+
+    let mut offenders: Vec<Offender> = buckets
+        .into_iter()
+        .map(|(bucket, (payloads, copied_bytes, example))| Offender {
+            bucket,
+            range: bucket_range(bucket),
+            payloads,
+            corpus_size: results.len(),
+            copied_bytes,
+            example,
+            label: match bucket {
+                0 => Some("selector".to_string()),
+                _ => arg_names
+                    .and_then(|names| names.get(bucket - 1))
+                    .map(ToString::to_string),
+            },
+        })
+        .collect();
+    offenders.sort_by(|a, b| {
+        b.copied_bytes
+            .cmp(&a.copied_bytes)
+            .then(a.bucket.cmp(&b.bucket))
+    });
+    offenders.truncate(top_n);
+    offenders
+}
+
+/// Totals of the instructions emitted with one compression method, see
+/// `CompressResult::method_stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MethodTotals {
+    pub instructions: usize,       // number of instructions using the method
+    pub decompressed_bytes: usize, // bytes of original data covered by those instructions
+    pub compressed_bytes: usize,   // bytes of those instructions, tags included
+}
+
+/// Aggregate numbers of a corpus compressed against one dictionary
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CorpusStats {
+    pub payloads: usize,
+    pub input_bytes: usize,
+    pub compressed_bytes: usize,
+    pub input_gas: u64,      // calldata gas of the original payloads
+    pub compressed_gas: u64, // calldata gas of the compressed payloads
+    pub methods: BTreeMap<Method, MethodTotals>, // method -> totals
+    pub dict_hits: BTreeMap<usize, usize>, // dict index -> number of references
+}
+
+impl CorpusStats {
+    // calldata gas saved over the whole corpus, negative if compression inflated it
+    pub fn gas_saved(&self) -> i64 {
+        let gas = |gas: u64| i64::try_from(gas).unwrap_or(i64::MAX);
+        gas(self.input_gas) - gas(self.compressed_gas)
+    }
+
+    // accounts one compressed payload, its storage references by the dictionary index they emit
+    pub fn add(&mut self, result: &CompressResult) {
+        self.payloads += 1;
+        self.input_bytes += result.uncompressed_data.len();
+        self.compressed_bytes += result.compressed_data.len();
+        self.input_gas += result.gas_uncompressed();
+        self.compressed_gas += result.gas_compressed();
+
+        for (method, stats) in result.method_stats() {
+            let totals = self.methods.entry(method).or_default();
+            totals.instructions += stats.instructions;
+            totals.decompressed_bytes += stats.decompressed_bytes;
+            totals.compressed_bytes += stats.compressed_bytes;
+        }
+        // `gas_golf` may point a reference at another index than the first holding its bytes
+        let stream = result.instruction_stream();
+        let mut offset = 0;
+        while offset < stream.len() {
+            if let Some(index) = storage_index(&stream, offset) {
+                *self.dict_hits.entry(index).or_default() += 1;
+            }
+            offset += instruction_size(&stream, offset);
+        }
+    }
+}
+
+/// Compresses every payload of `calldatas` against `dict`, accumulating `CorpusStats` on the way.
+/// Results are returned in input order.
+pub fn compress_corpus(
+    calldatas: &[Bytes],
+    dict: &Dict,
+) -> Result<(Vec<CompressResult>, CorpusStats), CompressorError> {
+    let mut stats = CorpusStats::default();
+    let mut results = Vec::with_capacity(calldatas.len());
+    for data in calldatas {
+        let mut calldata = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr())?;
+        calldata.set_dict(dict);
+        let result = calldata.compress()?;
+        stats.add(&result);
+        results.push(result);
+    }
+    Ok((results, stats))
+}
+
+/// Totals of one candidate dictionary in a `DictComparisonReport`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DictEvaluation {
+    pub name: String,
+    pub stats: CorpusStats,
+    pub wins: usize, // payloads for which this dictionary produced the smallest output
+}
+
+/// Outcome of compressing a corpus against several candidate dictionaries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DictComparisonReport {
+    pub dicts: Vec<DictEvaluation>, // in candidate order
+    pub winners: Vec<usize>,        // per payload, index into `dicts` of the smallest output
+    pub best: Option<usize>,        // index into `dicts` of the smallest total output
+}
+
+/// Compresses every payload against every candidate dictionary and reports which one wins.
+/// Ties go to the candidate listed first. The dictionary independent zero and copy analysis is
+/// done once per payload and shared by all candidates.
+pub fn evaluate_dicts(
+    calldatas: &[Bytes],
+    dicts: &[(&str, &Dict)],
+    opts: &CompressOptions,
+) -> Result<DictComparisonReport, CompressorError> {
+    let mut report = DictComparisonReport {
+        dicts: dicts
+            .iter()
+            .map(|(name, _)| DictEvaluation {
+                name: (*name).to_string(),
+                ..DictEvaluation::default()
+            })
+            .collect(),
+        ..DictComparisonReport::default()
+    };
+    if dicts.is_empty() {
+        return Ok(report);
+    }
+
+    for data in calldatas {
+        let mut calldata = Calldata::new(
+            data.clone(),
+            dicts[0].1.wallet_addr(),
+            dicts[0].1.contract_addr(),
+        )?;
+        calldata.analyse_data();
+
+        let mut winner: Option<(usize, usize)> = None; // (dict, compressed size)
+        for (i, (_, dict)) in dicts.iter().enumerate() {
+            calldata.set_dict(dict);
+            calldata.analyse_dict_for(opts)?;
+            let result = calldata.compress_analysed(opts)?;
+            report.dicts[i].stats.add(&result);
+            if winner.is_none_or(|(_, size)| result.compressed_data.len() < size) {
+                winner = Some((i, result.compressed_data.len()));
+            }
+        }
+        let (winner, _) = winner.unwrap();
+        report.dicts[winner].wins += 1;
+        report.winners.push(winner);
+    }
+
+    report.best = report
+        .dicts
+        .iter()
+        .enumerate()
+        .min_by_key(|(i, evaluation)| (evaluation.stats.compressed_bytes, *i))
+        .map(|(i, _)| i);
+    Ok(report)
+}
+
+/// Totals of one planner in `compare_planners`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlannerEvaluation {
+    pub name: String,
+    pub stats: CorpusStats,
+    pub elapsed: Duration, // spent planning and encoding the corpus, analysis excluded
+}
+
+/// Compresses every payload with every planner, reporting the output size and the time each
+/// planner takes so that the size/speed tradeoff between them is visible. The analysis is done
+/// once per payload and shared by all planners.
+#[cfg(feature = "std")]
+pub fn compare_planners(
+    calldatas: &[Bytes],
+    dict: &Dict,
+    planners: &[(&str, Arc<dyn Planner>)],
+    opts: &CompressOptions,
+) -> Result<Vec<PlannerEvaluation>, CompressorError> {
+    let mut evaluations: Vec<PlannerEvaluation> = planners
+        .iter()
+        .map(|(name, _)| PlannerEvaluation {
+            name: (*name).to_string(),
+            ..PlannerEvaluation::default()
+        })
+        .collect();
+    for data in calldatas {
+        let mut calldata = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr())?;
+        calldata.set_dict(dict);
+        calldata.analyse_data();
+        calldata.analyse_dict_for(opts)?;
+        for ((_, planner), evaluation) in planners.iter().zip(&mut evaluations) {
+            let opts = CompressOptions {
+                planner: Some(planner.clone()),
+                ..opts.clone()
+            };
+            let start = Instant::now();
+            let result = calldata.compress_analysed(&opts)?;
+            evaluation.elapsed += start.elapsed();
+            evaluation.stats.add(&result);
+        }
+    }
+    Ok(evaluations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::vector::TestVector;
+    use crate::{
+        compressor::{compress, Bytes32, CompressDataDescription},
+        cost::calldata_gas,
+    };
+
+    // deterministic xorshift so the corpus is stable across runs
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    // every transaction vendored by the `fetch_corpus` example roundtrips
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_vendored_corpus() {
+        let mut calldatas = Vec::new();
+        for entry in std::fs::read_dir("test-data/corpus").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            calldatas.push(TestVector::load(&path).unwrap().calldata);
+        }
+
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[]);
+        let (results, stats) = compress_corpus(&calldatas, &dict).unwrap();
+        assert_eq!(stats.payloads, calldatas.len());
+        for result in &results {
+            assert_eq!(result.verify(&dict), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_compress_corpus() {
+        let recipient: Bytes32 = [0x11; 32];
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[recipient]);
+        let calldatas: Vec<Bytes> = vec![
+            Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &recipient, &[0x00; 32]].concat()),
+            Bytes::from([[0x00; 40].as_slice(), &recipient].concat()),
+        ];
+
+        let (results, stats) = compress_corpus(&calldatas, &dict).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(stats.payloads, 2);
+        assert_eq!(stats.input_bytes, 68 + 72);
+        assert_eq!(
+            stats.compressed_bytes,
+            results
+                .iter()
+                .map(|r| r.compressed_data.len())
+                .sum::<usize>()
+        );
+        assert_eq!(
+            stats.input_gas,
+            calldatas.iter().map(|c| calldata_gas(c)).sum::<u64>()
+        );
+        assert!(stats.gas_saved() > 0);
+        assert_eq!(stats.dict_hits.get(&2), Some(&2));
+        assert_eq!(
+            stats
+                .methods
+                .values()
+                .map(|totals| totals.decompressed_bytes)
+                .sum::<usize>(),
+            stats.input_bytes
+        );
+    }
+
+    #[test]
+    fn test_corpus_stats_warm_index() {
+        // the address is entry 2 on its own and the tail of entry 3
+        let address = [0x5a; 20];
+        let mut padded = [0x00; 32];
+        padded[12..].copy_from_slice(&address);
+        let mut tagged = [0x11; 32];
+        tagged[12..].copy_from_slice(&address);
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[padded, tagged]);
+        let mut calldata = Calldata::new(
+            Bytes::from([tagged.as_slice(), &address].concat()),
+            dict.wallet_addr(),
+            dict.contract_addr(),
+        )
+        .unwrap();
+        calldata.set_dict(&dict);
+
+        let mut stats = CorpusStats::default();
+        stats.add(&calldata.compress().unwrap());
+        assert_eq!(stats.dict_hits, BTreeMap::from([(2, 1), (3, 1)]));
+
+        // the address is read again from entry 3
+        let opts = CompressOptions {
+            gas_golf: true,
+            reverse: true,
+            checksum: true,
+            ..CompressOptions::default()
+        };
+        let mut stats = CorpusStats::default();
+        stats.add(&calldata.compress_with(&opts).unwrap());
+        assert_eq!(stats.dict_hits, BTreeMap::from([(3, 2)]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_corpus_stats_serde() {
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[]);
+        let (_, stats) = compress_corpus(&[Bytes::from(vec![0x01, 0x00, 0x00])], &dict).unwrap();
+        let json = serde_json::to_string(&stats).unwrap();
+        let decoded: CorpusStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, stats);
+    }
+
+    #[test]
+    fn test_evaluate_dicts() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let token: Bytes32 = [0x11; 32];
+        let router: Bytes32 = [0x22; 32];
+        let calldatas = vec![
+            Bytes::from([selector.as_slice(), &token].concat()),
+            Bytes::from([selector.as_slice(), &router, &router].concat()),
+        ];
+        let tokens = Dict::new(Bytes32::default(), Bytes32::default(), &[token]);
+        let routers = Dict::new(Bytes32::default(), Bytes32::default(), &[router]);
+
+        let report = evaluate_dicts(
+            &calldatas,
+            &[("tokens", &tokens), ("routers", &routers)],
+            &CompressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.winners, vec![0, 1]);
+        assert_eq!(report.dicts[0].name, "tokens");
+        assert_eq!(report.dicts[0].wins, 1);
+        assert_eq!(report.dicts[1].wins, 1);
+        assert_eq!(report.best, Some(1));
+
+        // sharing the analysis doesn't change the outcome
+        for (evaluation, dict) in report.dicts.iter().zip([&tokens, &routers]) {
+            let (_, stats) = compress_corpus(&calldatas, dict).unwrap();
+            assert_eq!(evaluation.stats, stats);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compare_planners() {
+        let recipient: Bytes32 = [0x11; 32];
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[recipient]);
+        let calldatas: Vec<Bytes> = (0..4_u8)
+            .map(|i| {
+                Bytes::from(
+                    [
+                        [0xa9, 0x05, 0x9c, 0xbb, i].as_slice(),
+                        &recipient,
+                        &[0x00; 31],
+                    ]
+                    .concat(),
+                )
+            })
+            .collect();
+        let planners: [(&str, Arc<dyn Planner>); 2] = [
+            ("optimal", Arc::new(crate::planner::OptimalDpPlanner)),
+            ("greedy", Arc::new(crate::planner::GreedyPlanner)),
+        ];
+
+        let evaluations =
+            compare_planners(&calldatas, &dict, &planners, &CompressOptions::default()).unwrap();
+        assert_eq!(evaluations[0].name, "optimal");
+        assert_eq!(
+            evaluations[0].stats,
+            compress_corpus(&calldatas, &dict).unwrap().1
+        );
+        assert_eq!(evaluations[1].stats.payloads, 4);
+        assert!(evaluations[0].stats.compressed_bytes <= evaluations[1].stats.compressed_bytes);
+    }
+
+    #[test]
+    fn test_worst_offenders() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let constant: Bytes32 = [0x5a; 32];
+        let mut state = 0x2545_f491_4f6c_dd1d;
+
+        let results: Vec<CompressResult> = (0..8)
+            .map(|_| {
+                let mut calldata = selector.to_vec();
+                for _ in 0..WORD_SIZE {
+                    // keep every byte non-zero so the whole word is a literal
+                    calldata.push(next_random(&mut state) as u8 | 0x01);
+                }
+                calldata.extend_from_slice(&constant);
+                compress(
+                    Bytes::from(calldata),
+                    Bytes32::default(),
+                    Bytes32::default(),
+                    &[constant],
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let offenders = worst_offenders(&results, 2, Some(&["amount", "recipient"]));
+        assert_eq!(offenders.len(), 2);
+
+        assert_eq!(offenders[0].range, 4..36);
+        assert_eq!(offenders[0].label.as_deref(), Some("amount"));
+        assert_eq!(offenders[0].payloads, 8);
+        assert_eq!(offenders[0].copied_bytes, 8 * WORD_SIZE);
+        assert!((offenders[0].share() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(
+            offenders[0].example,
+            results[0].uncompressed_data.slice(4..36)
+        );
+
+        assert_eq!(offenders[1].range, 0..4);
+        assert_eq!(offenders[1].label.as_deref(), Some("selector"));
+        assert_eq!(offenders[1].copied_bytes, 8 * SELECTOR_SIZE);
+
+        // the constant argument always hits the dictionary
+        let all = worst_offenders(&results, usize::MAX, None);
+        assert!(all.iter().all(|offender| offender.bucket != 2));
+    }
+
+    #[test]
+    fn test_worst_offenders_short_copy_zeros() {
+        let mut word = [0x00; WORD_SIZE];
+        word[28..].fill(0x77);
+        let mut result = compress(
+            Bytes::from([[0x00, 0x00, 0x05].as_slice(), &word].concat()),
+            Bytes32::default(),
+            Bytes32::default(),
+            &[],
+        )
+        .unwrap();
+        result.description = vec![
+            CompressDataDescription::new(0, 3, Method::Copy),
+            CompressDataDescription::new(3, WORD_SIZE, Method::Copy),
+        ];
+
+        // the leading zeros of the short copy are emitted, those of the word are padding
+        let offenders = worst_offenders(&[result], 2, None);
+        assert_eq!(
+            offenders
+                .iter()
+                .map(|offender| (offender.bucket, offender.copied_bytes))
+                .collect::<Vec<_>>(),
+            [(1, 4), (0, 3)]
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use core::fmt;
+
+use crate::compressor::{CompressDataDescription, Method};
+
+/// gas charged per zero byte of transaction calldata
+pub const ZERO_BYTE_GAS: u64 = 4;
+/// gas charged per non-zero byte of transaction calldata
+pub const NON_ZERO_BYTE_GAS: u64 = 16;
+
+/// number of zero bytes in `data`
+pub fn count_zero_bytes(data: &[u8]) -> usize {
+    // fixed size chunks let the compiler vectorize the comparison without any unsafe code
+    let mut chunks = data.chunks_exact(32);
+    let full: usize = chunks
+        .by_ref()
+        .map(|chunk| chunk.iter().map(|&b| usize::from(b == 0x00)).sum::<usize>())
+        .sum();
+    full + chunks.remainder().iter().filter(|&&b| b == 0x00).count()
+}
+
+/// number of non-zero bytes in `data`
+pub fn count_nonzero_bytes(data: &[u8]) -> usize {
+    data.len() - count_zero_bytes(data)
+}
+
+/// EVM calldata gas of `data` (4 per zero byte, 16 per non-zero byte)
+pub fn calldata_gas(data: &[u8]) -> u64 {
+    let zero_bytes = count_zero_bytes(data) as u64;
+    let nonzero_bytes = data.len() as u64 - zero_bytes;
+    zero_bytes * ZERO_BYTE_GAS + nonzero_bytes * NON_ZERO_BYTE_GAS
+}
+
+/// Pricing of calldata on some chain, see `CompressResult::evaluate`. It also sets what
+/// `CompressObjective::Gas` minimizes, see `CompressOptions::cost_model`.
+pub trait CostModel: fmt::Debug + Send + Sync {
+    // cost of sending `bytes` as calldata
+    fn data_cost(&self, bytes: &[u8]) -> u64;
+
+    // cost of decoding one instruction of `method`, on top of the cost of its bytes
+    fn instruction_overhead(&self, method: Method) -> u64 {
+        let _ = method;
+        0
+    }
+
+    // cost of a transaction sending `calldata`. `decompression` is the overhead of its
+    // instructions when the calldata is compressed, `None` for the original calldata.
+    fn transaction_cost(&self, calldata: &[u8], decompression: Option<u64>) -> u64 {
+        self.data_cost(calldata) + decompression.unwrap_or(0)
+    }
+}
+
+/// The calldata gas of Ethereum mainnet before EIP-7623, see `calldata_gas`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MainnetCalldata;
+
+impl CostModel for MainnetCalldata {
+    fn data_cost(&self, bytes: &[u8]) -> u64 {
+        calldata_gas(bytes)
+    }
+}
+
+/// Costs of a calldata and of its compressed form under a `CostModel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostReport {
+    pub before: u64, // of the original calldata
+    pub after: u64,  // of the compressed calldata, decompression included
+    pub saved: i64,  // negative when compression doesn't pay
+}
+
+impl CostReport {
+    pub fn new(before: u64, after: u64) -> Self {
+        Self {
+            before,
+            after,
+            saved: before as i64 - after as i64,
+        }
+    }
+
+    /// Whether the compressed calldata costs more than the original, a free model is no loss
+    pub fn is_net_loss(&self) -> bool {
+        self.saved < 0
+    }
+}
+
+/// Execution gas a decompressor spends per instruction, see `estimate_decompression_gas`. The
+/// defaults are rough figures for a Solidity decoding loop, calibrate them against the deployed
+/// decompressor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressionCosts {
+    pub zero_run: u64,    // decoding a `0x00` instruction
+    pub copy: u64,        // decoding a `0x01` instruction and copying its bytes
+    pub dict_lookup: u64, // decoding a `0x10` or `0x11` reference, its `SLOAD` included
+    pub per_word: u64,    // writing the output to memory, per 32 bytes or part of it
+}
+
+impl Default for DecompressionCosts {
+    fn default() -> Self {
+        Self {
+            zero_run: 40,
+            copy: 60,
+            dict_lookup: 2_160, // a cold `SLOAD` and the decoding
+            per_word: 6,
+        }
+    }
+}
+
+impl DecompressionCosts {
+    pub fn instruction_gas(&self, description: &CompressDataDescription) -> u64 {
+        let base = match description.method {
+            Method::Zeros => self.zero_run,
+            Method::Copy => self.copy,
+            Method::DictShort | Method::DictLong => self.dict_lookup,
+        };
+        base + description.amount_bytes.div_ceil(32) as u64 * self.per_word
+    }
+
+    pub fn estimate(&self, descriptions: &[CompressDataDescription]) -> u64 {
+        descriptions.iter().map(|d| self.instruction_gas(d)).sum()
+    }
+}
+
+/// Execution gas of decompressing the instructions of `descriptions`, with the default
+/// `DecompressionCosts`
+pub fn estimate_decompression_gas(descriptions: &[CompressDataDescription]) -> u64 {
+    DecompressionCosts::default().estimate(descriptions)
+}
+
+/// base gas of every transaction
+pub const TX_BASE_GAS: u64 = 21_000;
+/// gas per calldata token of the standard rule of EIP-7623
+pub const STANDARD_TOKEN_COST: u64 = 4;
+/// gas per calldata token of the floor rule of EIP-7623
+pub const TOTAL_COST_FLOOR_PER_TOKEN: u64 = 10;
+
+/// calldata tokens of EIP-7623: 1 per zero byte, 4 per non-zero byte
+pub fn calldata_tokens(data: &[u8]) -> u64 {
+    let zero_bytes = count_zero_bytes(data) as u64;
+    zero_bytes + (data.len() as u64 - zero_bytes) * 4
+}
+
+/// Rule of EIP-7623 setting the gas of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasRule {
+    Standard, // calldata at the standard price plus execution
+    Floor,    // calldata at the floor price, execution is free below it
+}
+
+/// Gas of a transaction under both rules of EIP-7623, the higher one is charged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Eip7623Gas {
+    pub standard: u64,
+    pub floor: u64,
+}
+
+impl Eip7623Gas {
+    pub fn used(&self) -> u64 {
+        self.standard.max(self.floor)
+    }
+
+    pub fn binding(&self) -> GasRule {
+        if self.floor > self.standard {
+            GasRule::Floor
+        } else {
+            GasRule::Standard
+        }
+    }
+}
+
+/// EIP-7623 pricing of a transaction calling a contract, which spends `execution_gas` on the
+/// call itself and another `decompression_gas` when given compressed calldata, on top of the
+/// instruction overheads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Eip7623CostModel {
+    pub execution_gas: u64,
+    pub decompression_gas: u64,
+}
+
+impl Eip7623CostModel {
+    pub fn gas(&self, calldata: &[u8], decompression: Option<u64>) -> Eip7623Gas {
+        let tokens = calldata_tokens(calldata);
+        let execution_gas = self.execution_gas
+            + decompression.map_or(0, |overhead| self.decompression_gas + overhead);
+        Eip7623Gas {
+            standard: TX_BASE_GAS + tokens * STANDARD_TOKEN_COST + execution_gas,
+            floor: TX_BASE_GAS + tokens * TOTAL_COST_FLOOR_PER_TOKEN,
+        }
+    }
+}
+
+impl CostModel for Eip7623CostModel {
+    // the standard price, the floor only applies to whole transactions
+    fn data_cost(&self, bytes: &[u8]) -> u64 {
+        calldata_tokens(bytes) * STANDARD_TOKEN_COST
+    }
+
+    fn transaction_cost(&self, calldata: &[u8], decompression: Option<u64>) -> u64 {
+        self.gas(calldata, decompression).used()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{compress, Calldata},
+        options::{CompressObjective, CompressOptions},
+    };
+
+    // every byte at the same price, dictionary references at `reference` extra
+    #[derive(Debug)]
+    struct FlatCost {
+        per_byte: u64,
+        reference: u64,
+    }
+
+    impl CostModel for FlatCost {
+        fn data_cost(&self, bytes: &[u8]) -> u64 {
+            bytes.len() as u64 * self.per_byte
+        }
+
+        fn instruction_overhead(&self, method: Method) -> u64 {
+            if method.is_storage() {
+                self.reference
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_bytes() {
+        assert_eq!(count_zero_bytes(&[]), 0);
+        assert_eq!(count_nonzero_bytes(&[]), 0);
+
+        let data: Vec<u8> = (0..100)
+            .map(|i| if i % 3 == 0 { 0x00 } else { 0x01 })
+            .collect();
+        assert_eq!(count_zero_bytes(&data), 34);
+        assert_eq!(count_nonzero_bytes(&data), 66);
+        assert_eq!(calldata_gas(&data), 34 * 4 + 66 * 16);
+    }
+
+    #[test]
+    fn test_eip7623_cost_model() {
+        // a selector and zero-padded small integers, mostly zero bytes
+        let word = [[0x00; 31].as_slice(), &[0x01]].concat();
+        let calldata =
+            Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &word.repeat(10)].concat());
+        assert_eq!(calldata_tokens(&calldata), 310 + 14 * 4);
+        let result = compress(calldata.clone(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let compressed = calldata_tokens(&result.compressed_data);
+        assert!(compressed < 366);
+
+        let model = Eip7623CostModel {
+            execution_gas: 1_000,
+            decompression_gas: 3_000,
+        };
+        // the zeros make the uncompressed calldata expensive enough for the floor to bind
+        let before = model.gas(&calldata, None);
+        assert_eq!(before.binding(), GasRule::Floor);
+        assert_eq!(before.used(), TX_BASE_GAS + 366 * 10);
+        // the compressed calldata is cheap, execution and decompression set its price
+        let after = model.gas(&result.compressed_data, Some(0));
+        assert_eq!(after.binding(), GasRule::Standard);
+        assert_eq!(after.used(), TX_BASE_GAS + compressed * 4 + 4_000);
+        // which the decompressor makes pricier than sending the calldata as is
+        assert!(result.gas_saved(&model) < 0);
+        assert_eq!(
+            result.gas_saved(&model),
+            before.used() as i64 - after.used() as i64
+        );
+
+        let cheap = Eip7623CostModel {
+            decompression_gas: 500,
+            ..model
+        };
+        assert!(result.gas_saved(&cheap) > 0);
+        // plain calls are charged the floor
+        assert_eq!(
+            Eip7623CostModel::default()
+                .gas(&calldata, Some(0))
+                .binding(),
+            GasRule::Floor
+        );
+    }
+
+    #[test]
+    fn test_custom_cost_model() {
+        let word = [0x42; 32];
+        let calldata = Bytes::from(
+            [
+                [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                &word,
+                &[0x00; 31],
+                &[0x07],
+                &word,
+            ]
+            .concat(),
+        );
+        let compress_with = |opts: &CompressOptions| {
+            let mut cb = Calldata::new(calldata.clone(), [0xaa; 32], [0xbb; 32]).unwrap();
+            cb.init_dict(&[word]);
+            cb.compress_with(opts).unwrap()
+        };
+        let plain = compress_with(&CompressOptions::default());
+
+        let flat = FlatCost {
+            per_byte: 3,
+            reference: 0,
+        };
+        assert_eq!(
+            plain.evaluate(&flat),
+            CostReport::new(
+                calldata.len() as u64 * 3,
+                plain.compressed_data.len() as u64 * 3
+            )
+        );
+        assert_eq!(plain.evaluate(&flat).saved, plain.saved_bytes() * 3);
+        assert_eq!(
+            plain.evaluate(&MainnetCalldata),
+            CostReport::new(plain.gas_uncompressed(), plain.gas_compressed())
+        );
+
+        // a flat price makes the gas objective a byte count
+        let flat = Arc::new(flat);
+        let sized = compress_with(&CompressOptions {
+            objective: CompressObjective::Gas,
+            cost_model: Some(flat.clone()),
+            ..CompressOptions::default()
+        });
+        assert!(sized.compressed_data.len() <= plain.compressed_data.len());
+
+        // references priced out of the plan
+        let costly = FlatCost {
+            per_byte: 1,
+            reference: 100,
+        };
+        assert_eq!(plain.stats().storage_references, 2);
+        assert_eq!(
+            plain.evaluate(&costly).after,
+            plain.compressed_data.len() as u64 + 200
+        );
+        let result = compress_with(&CompressOptions {
+            objective: CompressObjective::Gas,
+            cost_model: Some(Arc::new(costly)),
+            ..CompressOptions::default()
+        });
+        assert_eq!(result.stats().storage_references, 0);
+        assert!(
+            result.gas_saved(&FlatCost {
+                per_byte: 1,
+                reference: 100
+            }) > 0
+        );
+    }
+
+    #[test]
+    fn test_estimate_decompression_gas() {
+        let costs = DecompressionCosts::default();
+        assert_eq!(
+            costs.instruction_gas(&CompressDataDescription::new(0, 33, Method::Zeros)),
+            40 + 2 * 6
+        );
+        assert_eq!(
+            costs.instruction_gas(&CompressDataDescription::new(0, 20, Method::DictShort)),
+            2_160 + 6
+        );
+
+        // every instruction adds to the estimate, whatever its method
+        let methods = [Method::Copy, Method::Zeros, Method::DictLong, Method::Copy];
+        let mut plan = Vec::new();
+        let mut estimate = estimate_decompression_gas(&plan);
+        assert_eq!(estimate, 0);
+        for (i, method) in methods.into_iter().cycle().take(40).enumerate() {
+            plan.push(CompressDataDescription::new(i, 1, method));
+            let next = estimate_decompression_gas(&plan);
+            assert!(next > estimate, "{plan:?}");
+            estimate = next;
+        }
+
+        let calibrated = DecompressionCosts {
+            dict_lookup: 100, // a warm `SLOAD`
+            ..costs
+        };
+        assert!(calibrated.estimate(&plan) < estimate);
+        let result = compress(
+            Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x42; 32]].concat()),
+            [0xaa; 32],
+            [0xbb; 32],
+            &[[0x42; 32]],
+        )
+        .unwrap();
+        assert_eq!(result.decompression_gas_estimate, 60 + 6 + 2_160 + 6);
+    }
+
+    #[test]
+    fn test_is_worth_it() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let word = [0x00; 32];
+        let zero_words = |words: usize| {
+            let data = Bytes::from([selector.as_slice(), &word.repeat(words)].concat());
+            compress(data, [0xaa; 32], [0xbb; 32], &[]).unwrap()
+        };
+
+        // a single zero word saves less calldata gas than the decompressor spends
+        let small = zero_words(1);
+        assert!(small.saved_gas() > 0);
+        assert!(small.saved_gas() < small.decompression_gas_estimate as i64);
+        assert!(!small.is_worth_it(&MainnetCalldata, 0));
+
+        let large = zero_words(64);
+        assert!(large.is_worth_it(&MainnetCalldata, 0));
+        assert!(large.is_worth_it(&MainnetCalldata, 2_600));
+        // the overhead is counted once, on the compressed side
+        let saved = large.evaluate(&MainnetCalldata).saved;
+        let threshold = (saved - large.decompression_gas_estimate as i64) as u64;
+        assert!(large.is_worth_it(&MainnetCalldata, threshold - 1));
+        assert!(!large.is_worth_it(&MainnetCalldata, threshold));
+
+        // on a chain with pricey calldata, unless its references are even pricier
+        let references = FlatCost {
+            per_byte: 1_000,
+            reference: 1_000_000,
+        };
+        let result = compress(
+            Bytes::from([selector.as_slice(), &[0x42; 32]].concat()),
+            [0xaa; 32],
+            [0xbb; 32],
+            &[[0x42; 32]],
+        )
+        .unwrap();
+        assert!(result.is_worth_it(
+            &FlatCost {
+                reference: 0,
+                ..references
+            },
+            0
+        ));
+        assert!(!result.is_worth_it(&references, 0));
+    }
+}
@@ -0,0 +1,289 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use alloy::primitives::Bytes;
+
+use crate::{
+    compressor::{Calldata, CompressDataDescription, CompressResult},
+    corpus::WORD_SIZE,
+    dict::Dict,
+    errors::CompressorError,
+    options::CompressOptions,
+};
+
+// lookback of the optimal planner on the initcode: bytecode has long runs of distinct bytes, so
+// longer parts pay off. On the fixture initcode, 593 bytes compress to 530 on the default 63,
+// 525 on 128 and 522 on 256, at about 7 ms per KB, which the 48 KB cap on initcode bounds.
+const CODE_MAX_LOOKBACK: usize = 256;
+
+// an abi word of the constructor arguments found by the fallback starts with at least as many
+// zero bytes as an address
+const ARG_WORD_LEADING_ZEROS: usize = 12;
+
+/// How `find_deployment_boundary` located the end of the initcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryDetection {
+    Metadata, // the initcode ends with the CBOR metadata trailer appended by solc
+    Fallback, // no trailer, the arguments are the trailing words that look like abi words
+}
+
+/// Where the initcode of a deployment payload ends and its abi encoded constructor arguments start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeploymentBoundary {
+    pub offset: usize, // length of the initcode
+    pub detection: BoundaryDetection,
+}
+
+// whether `code` ends with a solc metadata trailer: a CBOR map holding the `solc` version or a
+// source hash, followed by its length on 2 bytes
+fn ends_with_metadata(code: &[u8]) -> bool {
+    let Some(len_bytes) = code.len().checked_sub(2).map(|start| &code[start..]) else {
+        return false;
+    };
+    let cbor_len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+    let Some(cbor_start) = (code.len() - 2).checked_sub(cbor_len) else {
+        return false;
+    };
+    let cbor = &code[cbor_start..code.len() - 2];
+    let contains = |key: &[u8]| cbor.windows(key.len()).any(|window| window == key);
+    // maps of 1 to 5 entries, keys are CBOR text strings
+    cbor.first()
+        .is_some_and(|&head| (0xa1..=0xa5).contains(&head))
+        && (contains(b"\x64solc") || contains(b"\x64ipfs") || contains(b"\x65bzzr"))
+}
+
+/// Splits a deployment payload, `initcode ++ abi.encode(constructor args)`, at the end of the
+/// initcode.
+///
+/// The arguments are a whole number of abi words, so the boundary is looked for at every word
+/// from the end: the last offset preceded by a solc metadata trailer wins, which skips the
+/// trailers of contracts whose creation code is embedded in the initcode, as a factory's is.
+/// Without any trailer, the boundary falls before the trailing words with at least 12 leading
+/// zero bytes, which catches addresses and small integers but not negative numbers or the tails
+/// of dynamic arguments.
+pub fn find_deployment_boundary(payload: &[u8]) -> DeploymentBoundary {
+    let word_offsets =
+        (0..=payload.len() / WORD_SIZE).map(|words| payload.len() - words * WORD_SIZE);
+    for offset in word_offsets.clone() {
+        if ends_with_metadata(&payload[..offset]) {
+            return DeploymentBoundary {
+                offset,
+                detection: BoundaryDetection::Metadata,
+            };
+        }
+    }
+
+    let offset = word_offsets
+        .take_while(|&offset| {
+            offset == payload.len() || {
+                let word = &payload[offset..offset + WORD_SIZE];
+                word.iter().take_while(|&&b| b == 0x00).count() >= ARG_WORD_LEADING_ZEROS
+            }
+        })
+        .last()
+        .unwrap_or(payload.len());
+    DeploymentBoundary {
+        offset,
+        detection: BoundaryDetection::Fallback,
+    }
+}
+
+/// One side of a compressed deployment payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentSide {
+    pub range: Range<usize>,    // bytes of the payload
+    pub compressed_size: usize, // size of their instructions, without the checksum trailer
+}
+
+impl DeploymentSide {
+    pub fn saved_bytes(&self) -> i64 {
+        self.range.len() as i64 - self.compressed_size as i64
+    }
+}
+
+/// A deployment payload compressed side by side, see `compress_deployment`
+#[derive(Debug, Clone)]
+pub struct DeploymentCompression {
+    pub boundary: DeploymentBoundary,
+    pub code: DeploymentSide,
+    pub args: DeploymentSide,
+    pub result: CompressResult, // the whole payload, decompressed like any calldata
+}
+
+// plans `range` of `payload` on its own, the descriptions are offsets of the whole payload
+fn plan_side(
+    payload: &Bytes,
+    range: Range<usize>,
+    dict: &Dict,
+    opts: &CompressOptions,
+) -> Result<Vec<CompressDataDescription>, CompressorError> {
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+    let start = range.start;
+    let mut side = Calldata::new(
+        payload.slice(range),
+        dict.wallet_addr(),
+        dict.contract_addr(),
+    )?;
+    side.set_dict(dict);
+    side.analyse_data();
+    side.analyse_dict_for(opts)?;
+    let mut plan = side.plan_analysed(opts)?;
+    for description in &mut plan {
+        description.start_byte += start;
+    }
+    Ok(plan)
+}
+
+/// Compresses a deployment payload, splitting it with `find_deployment_boundary` and planning
+/// each side on its own.
+///
+/// The constructor arguments are planned with `opts` like any calldata. The initcode is planned
+/// on a lookback of at least 256 bytes, which pays off on bytecode, and without
+/// `opts.extra_detectors`, which look for calldata patterns that bytecode doesn't have. The
+/// output and checksum options apply to the whole payload, which decompresses as usual.
+pub fn compress_deployment(
+    payload: Bytes,
+    dict: &Dict,
+    opts: &CompressOptions,
+) -> Result<DeploymentCompression, CompressorError> {
+    let boundary = find_deployment_boundary(&payload);
+    let code_opts = CompressOptions {
+        extra_detectors: Vec::new(),
+        max_lookback: core::cmp::max(opts.max_lookback, CODE_MAX_LOOKBACK),
+        ..opts.clone()
+    };
+    let code_plan = plan_side(&payload, 0..boundary.offset, dict, &code_opts)?;
+    let args_plan = plan_side(&payload, boundary.offset..payload.len(), dict, opts)?;
+
+    let mut calldata = Calldata::new(payload.clone(), dict.wallet_addr(), dict.contract_addr())?;
+    calldata.set_dict(dict);
+    let code = DeploymentSide {
+        range: 0..boundary.offset,
+        compressed_size: calldata.zip(&code_plan)?.len(),
+    };
+    let args = DeploymentSide {
+        range: boundary.offset..payload.len(),
+        compressed_size: calldata.zip(&args_plan)?.len(),
+    };
+    let result = calldata.encode_plan([code_plan, args_plan].concat(), opts)?;
+    Ok(DeploymentCompression {
+        boundary,
+        code,
+        args,
+        result,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::{decompress_with, Bytes32, Method};
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("test-data/deployment.json").unwrap())
+                .unwrap();
+        fixture[name]
+            .as_str()
+            .unwrap()
+            .parse::<Bytes>()
+            .unwrap()
+            .to_vec()
+    }
+
+    fn owner() -> Bytes32 {
+        let mut word = Bytes32::default();
+        word[12..].fill(0x42);
+        word
+    }
+
+    // `constructor(address owner, uint256 count)`
+    fn args() -> Vec<u8> {
+        let mut count = Bytes32::default();
+        count[31] = 42;
+        [owner(), count].concat()
+    }
+
+    #[test]
+    fn test_find_deployment_boundary() {
+        let initcode = fixture("initcode");
+        for (code, args) in [(initcode.clone(), args()), (initcode.clone(), Vec::new())] {
+            assert_eq!(
+                find_deployment_boundary(&[code.as_slice(), &args].concat()),
+                DeploymentBoundary {
+                    offset: code.len(),
+                    detection: BoundaryDetection::Metadata,
+                }
+            );
+        }
+
+        // stripped of its trailer, the initcode ends before the address-like words
+        let stripped = &initcode[..initcode.len() - 53];
+        assert_eq!(
+            find_deployment_boundary(&[stripped, &args()].concat()),
+            DeploymentBoundary {
+                offset: stripped.len(),
+                detection: BoundaryDetection::Fallback,
+            }
+        );
+        assert_eq!(
+            find_deployment_boundary(stripped),
+            DeploymentBoundary {
+                offset: stripped.len(),
+                detection: BoundaryDetection::Fallback,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compress_deployment() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[owner()]);
+        let opts = CompressOptions {
+            checksum: true,
+            ..CompressOptions::default()
+        };
+        let initcode = fixture("initcode");
+        let stripped = initcode[..initcode.len() - 53].to_vec();
+        for code in [initcode, stripped] {
+            let payload = Bytes::from([code.as_slice(), &args()].concat());
+            let deployment = compress_deployment(payload.clone(), &dict, &opts).unwrap();
+            assert_eq!(deployment.code.range, 0..code.len());
+            assert_eq!(deployment.args.range, code.len()..payload.len());
+            // the owner is a dictionary reference
+            assert!(deployment
+                .result
+                .description
+                .iter()
+                .any(|d| d.start_byte >= code.len() && d.method == Method::DictShort));
+            assert!(deployment.args.saved_bytes() > 56);
+            assert!(deployment.code.saved_bytes() > 0);
+            assert_eq!(
+                deployment.code.compressed_size + deployment.args.compressed_size + 4,
+                deployment.result.compressed_data.len()
+            );
+            assert_eq!(
+                decompress_with(&deployment.result.compressed_data, &dict, &opts),
+                Ok(payload)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compress_deployment_smaller() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[owner()]);
+        for code in [fixture("initcode"), fixture("initcode_via_ir")] {
+            let payload = Bytes::from([code.as_slice(), &args()].concat());
+            let deployment =
+                compress_deployment(payload.clone(), &dict, &CompressOptions::default()).unwrap();
+            let mut calldata =
+                Calldata::new(payload, dict.wallet_addr(), dict.contract_addr()).unwrap();
+            calldata.set_dict(&dict);
+            assert!(
+                deployment.result.compressed_data.len()
+                    < calldata.compress().unwrap().compressed_data.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use smallvec::SmallVec;
+
+use crate::{
+    compressor::{ByteInfo, CompressDataPower, Method},
+    dict::{DictView, LOOKUP_LENGTHS},
+    errors::CompressorError,
+    instruction::{MAX_LONG_INDEX, MAX_SHORT_INDEX},
+};
+
+/// A way to encode the bytes starting at some position, found by a `Detector`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub method: Method, // compression method(decompress mask) of the instruction
+    pub power: CompressDataPower, // bytes covered and size of the instruction
+}
+
+impl Candidate {
+    pub fn new(method: Method, decompressed_size: usize, compressed_size: usize) -> Self {
+        Self {
+            method,
+            power: CompressDataPower::new(decompressed_size, compressed_size),
+        }
+    }
+}
+
+/// State shared by the detectors of one analysis
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisCtx<'a> {
+    pub dict: DictView<'a>, // the dictionary with the calldata wallet, see `dict_view`
+    pub zero_runs: &'a [usize], // zero bytes starting at every byte, see `zero_runs`
+}
+
+/// Proposes candidate instructions for every byte of the calldata during `Calldata::analyse`.
+///
+/// User supplied detectors, see `CompressOptions::extra_detectors`, are run after the built-in
+/// ones and can only propose dictionary references (`0x10` and `0x11`): the planner weighs them
+/// like the references of `StorageDetector`, and `zip` fails with `LookupNotFound` if the
+/// referenced bytes aren't in the dictionary.
+pub trait Detector: fmt::Debug + Send + Sync {
+    fn candidates(
+        &self,
+        data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+    ) -> SmallVec<[Candidate; 4]>;
+}
+
+/// `00XXXXXX`, a run of up to 64 zero bytes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZeroRunDetector;
+
+impl Detector for ZeroRunDetector {
+    fn candidates(
+        &self,
+        _data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+    ) -> SmallVec<[Candidate; 4]> {
+        let power = zeros_case(ctx.zero_runs, pos);
+        if power.decompressed_size == 0 {
+            return SmallVec::new();
+        }
+        SmallVec::from_elem(
+            Candidate {
+                method: Method::Zeros,
+                power,
+            },
+            1,
+        )
+    }
+}
+
+/// `01PXXXXX`, a copy of up to 32 literal bytes, leading zeros of a 32-byte copy stripped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyDetector;
+
+impl Detector for CopyDetector {
+    fn candidates(
+        &self,
+        data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+    ) -> SmallVec<[Candidate; 4]> {
+        SmallVec::from_elem(
+            Candidate {
+                method: Method::Copy,
+                power: copy_case(data, ctx.zero_runs, pos),
+            },
+            1,
+        )
+    }
+}
+
+/// `10BBXXXX XXXXXXXX` and `11BBXXXX XXXXXXXX XXXXXXXX`, a reference to a dictionary entry
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageDetector;
+
+impl StorageDetector {
+    // `candidates` among the first `depth` of the `LOOKUP_LENGTHS`, see
+    // `CompressOptions::dict_scan_depth`
+    pub(crate) fn candidates_up_to(
+        data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+        depth: usize,
+    ) -> SmallVec<[Candidate; 4]> {
+        let mut candidates = SmallVec::new();
+        for &len in LOOKUP_LENGTHS.iter().take(depth) {
+            let tail = &data[pos..core::cmp::min(pos + len, data.len())];
+            if let Some(index) = ctx.dict.index_of(tail) {
+                // entries past the 20 bits of a 3-byte reference can't be referenced
+                if tail.len() >= len && index <= MAX_LONG_INDEX as usize {
+                    // 11BBXXXX XXXXXXXX XXXXXXXX or 10BBXXXX XXXXXXXX
+                    candidates.push(if index > MAX_SHORT_INDEX as usize {
+                        Candidate::new(Method::DictLong, len, 3)
+                    } else {
+                        Candidate::new(Method::DictShort, len, 2)
+                    });
+                }
+            }
+        }
+        candidates
+    }
+}
+
+impl Detector for StorageDetector {
+    fn candidates(
+        &self,
+        data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+    ) -> SmallVec<[Candidate; 4]> {
+        Self::candidates_up_to(data, pos, ctx, LOOKUP_LENGTHS.len())
+    }
+}
+
+// records `candidate` in the analysis of its byte, as the only zero run or copy, or as one
+// more dictionary reference
+pub(crate) fn add_candidate(info: &mut ByteInfo, candidate: Candidate) {
+    match candidate.method {
+        Method::Zeros => info.zero_compress = candidate.power,
+        Method::Copy => info.copy_compress = candidate.power,
+        Method::DictShort | Method::DictLong => info.storage_compress.push(candidate.power),
+    }
+}
+
+// the planner tells the two reference sizes apart by their compressed size only, and `zip` can
+// only encode references to the suffix lengths of the lookup
+pub(crate) fn check_extra_candidate(candidate: &Candidate) -> Result<(), CompressorError> {
+    match (candidate.method, candidate.power.compressed_size) {
+        (Method::DictShort, 2) | (Method::DictLong, 3)
+            if LOOKUP_LENGTHS.contains(&candidate.power.decompressed_size) =>
+        {
+            Ok(())
+        }
+        _ => Err(CompressorError::UnsupportedMethod(candidate.method.into())),
+    }
+}
+
+// the number of consecutive zero bytes starting at every byte of `data`, in a single backward
+// pass so that the zero runs and copies of every byte are found in constant time
+pub(crate) fn zero_runs(data: &[u8]) -> Vec<usize> {
+    let mut runs = vec![0; data.len()];
+    let mut run = 0;
+    for (i, &byte) in data.iter().enumerate().rev() {
+        run = if byte == 0x00 { run + 1 } else { 0 };
+        runs[i] = run;
+    }
+    runs
+}
+
+pub(crate) fn zeros_case(zero_runs: &[usize], n: usize) -> CompressDataPower {
+    match zero_runs.get(n) {
+        None | Some(0) => CompressDataPower {
+            decompressed_size: 0,
+            compressed_size: 0,
+        },
+        // 00XXXXXX case, XXXXXX max value is 2**6-1=63
+        Some(&run) => CompressDataPower {
+            decompressed_size: core::cmp::min(run, 64),
+            compressed_size: 1,
+        },
+    }
+}
+
+pub(crate) fn copy_case(data: &[u8], zero_runs: &[usize], n: usize) -> CompressDataPower {
+    let run = zero_runs.get(n).copied().unwrap_or_default();
+    if run == 0 {
+        // decompressed: 0xXX, 1 Byte
+        // compressed: 01000000 0xXX, 2 Byte
+        return CompressDataPower {
+            decompressed_size: 1,
+            compressed_size: 2,
+        };
+    }
+    // 01PXXXXX case, XXXXX max value is 2**5-1=31
+    if run > 32 {
+        return CompressDataPower {
+            decompressed_size: 31,
+            compressed_size: 32,
+        };
+    }
+    let decompressed_bytes_amount = core::cmp::min(data.len() - n, 32);
+    CompressDataPower {
+        decompressed_size: decompressed_bytes_amount,
+        compressed_size: if decompressed_bytes_amount == 32 {
+            32 - run
+        } else {
+            1 + decompressed_bytes_amount
+        },
+    }
+}
+
+pub(crate) fn storage_case(
+    data: &[u8],
+    n: usize,
+    dict: DictView<'_>,
+) -> Result<Vec<CompressDataPower>, CompressorError> {
+    if dict.base.is_empty() || dict.base.lookup.is_empty() {
+        return Err(CompressorError::DictNotInit);
+    }
+    if n > data.len() {
+        return Err(CompressorError::InvalidRange);
+    }
+
+    // references don't depend on the zero runs
+    let ctx = AnalysisCtx {
+        dict,
+        zero_runs: &[],
+    };
+    let best = StorageDetector
+        .candidates(data, n, &ctx)
+        .into_iter()
+        .map(|candidate| candidate.power)
+        .collect();
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{decompress_with, Bytes32, Calldata, CompressDataDescription},
+        dict::Dict,
+        options::CompressOptions,
+    };
+
+    const MAGIC: [u8; 8] = *b"\xca\xfe\xba\xbe\xde\xad\xbe\xef";
+
+    // routes abi words starting with `MAGIC` to their dictionary entry, remembering where it
+    // fired
+    #[derive(Debug, Default)]
+    struct MagicDetector {
+        fired: Mutex<Vec<usize>>,
+    }
+
+    impl Detector for MagicDetector {
+        fn candidates(
+            &self,
+            data: &[u8],
+            pos: usize,
+            ctx: &AnalysisCtx<'_>,
+        ) -> SmallVec<[Candidate; 4]> {
+            let Some(word) = data.get(pos..pos + 32) else {
+                return SmallVec::new();
+            };
+            if word[..8] != MAGIC {
+                return SmallVec::new();
+            }
+            let Some(index) = ctx.dict.index_of(word) else {
+                return SmallVec::new();
+            };
+            self.fired.lock().unwrap().push(pos);
+            let size = if index <= MAX_SHORT_INDEX as usize {
+                2
+            } else {
+                3
+            };
+            SmallVec::from_elem(Candidate::new(Method::storage(size), 32, size), 1)
+        }
+    }
+
+    // proposes the same candidate everywhere
+    #[derive(Debug)]
+    struct FixedDetector(Candidate);
+
+    impl Detector for FixedDetector {
+        fn candidates(
+            &self,
+            _data: &[u8],
+            pos: usize,
+            _ctx: &AnalysisCtx<'_>,
+        ) -> SmallVec<[Candidate; 4]> {
+            if pos != 0 {
+                return SmallVec::new();
+            }
+            SmallVec::from_elem(self.0.clone(), 1)
+        }
+    }
+
+    fn magic_word() -> Bytes32 {
+        let mut word = [0x42; 32];
+        word[..8].copy_from_slice(&MAGIC);
+        word
+    }
+
+    fn magic_calldata() -> Bytes {
+        Bytes::from(
+            [
+                [0x12, 0x34, 0x56, 0x78].as_slice(),
+                &magic_word(),
+                &[0x77; 32],
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn test_extra_detector() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[[0x11; 32], magic_word()]);
+        let data = magic_calldata();
+        let mut cb = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+
+        let magic = Arc::new(MagicDetector::default());
+        let opts = CompressOptions {
+            extra_detectors: vec![magic.clone()],
+            ..CompressOptions::default()
+        };
+        let result = cb.compress_with(&opts).unwrap();
+        assert_eq!(*magic.fired.lock().unwrap(), vec![4]);
+        assert!(result
+            .description
+            .iter()
+            .any(|d| d.start_byte == 4 && d.amount_bytes == 32 && d.method == Method::DictShort));
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &opts),
+            Ok(data)
+        );
+        assert_eq!(opts.clone(), opts);
+        assert_ne!(
+            opts,
+            CompressOptions::default().extra_detectors(vec![Box::new(MagicDetector::default())])
+        );
+    }
+
+    #[test]
+    fn test_extra_detector_errors() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[]);
+        let compress = |candidate: Candidate| {
+            let mut cb =
+                Calldata::new(magic_calldata(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+            cb.set_dict(&dict);
+            let opts = CompressOptions::default()
+                .extra_detectors(vec![Box::new(FixedDetector(candidate))]);
+            cb.compress_with(&opts)
+        };
+
+        // zero runs and copies are the built-ins' business
+        assert_eq!(
+            compress(Candidate::new(Method::Copy, 4, 5)).unwrap_err(),
+            CompressorError::UnsupportedMethod(0x01)
+        );
+        // a reference must have the size of its method and a length the lookup indexes
+        assert_eq!(
+            compress(Candidate::new(Method::DictShort, 32, 3)).unwrap_err(),
+            CompressorError::UnsupportedMethod(0x10)
+        );
+        assert_eq!(
+            compress(Candidate::new(Method::DictShort, 8, 2)).unwrap_err(),
+            CompressorError::UnsupportedMethod(0x10)
+        );
+        // the planner takes the reference, but its bytes aren't in the dictionary
+        assert_eq!(
+            compress(Candidate::new(Method::DictShort, 32, 2)).unwrap_err(),
+            CompressorError::LookupNotFound
+        );
+    }
+
+    #[test]
+    fn test_storage_threshold() {
+        // 4097 entries, the reserved ones included: index 4095 is the last that fits the 12 bits
+        // of a 2-byte reference, 4096 takes a 3-byte one
+        let words: Vec<Bytes32> = (2..4097_u32)
+            .map(|i| {
+                let mut word = [0xee; 32];
+                word[28..].copy_from_slice(&i.to_be_bytes());
+                word
+            })
+            .collect();
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &words);
+        assert_eq!(dict.len(), 4097);
+        let calldata = Bytes::from([dict.entries[4095], dict.entries[4096]].concat());
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        assert_eq!(
+            cb.check_storage_case(0).unwrap()[0],
+            CompressDataPower::new(32, 2)
+        );
+        assert_eq!(
+            cb.check_storage_case(32).unwrap()[0],
+            CompressDataPower::new(32, 3)
+        );
+
+        let plan = [
+            CompressDataDescription::new(0, 32, Method::DictShort),
+            CompressDataDescription::new(32, 32, Method::DictLong),
+        ];
+        let compressed = cb.zip(&plan).unwrap();
+        assert_eq!(compressed[..], [0x8f, 0xff, 0xc0, 0x10, 0x00]);
+        let opts = CompressOptions::default();
+        assert_eq!(
+            decompress_with(&compressed, &dict, &opts),
+            Ok(calldata.clone())
+        );
+        let result = cb.compress().unwrap();
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &opts),
+            Ok(calldata)
+        );
+    }
+
+    // the byte by byte scans `zeros_case` and `copy_case` replaced
+    fn scan_zeros(data: &[u8], n: usize) -> (usize, usize) {
+        if data.get(n) != Some(&0x00) {
+            return (0, 0);
+        }
+        let mut end = n + 1;
+        while data.get(end) == Some(&0x00) && end - n <= 63 {
+            end += 1;
+        }
+        (end - n, 1)
+    }
+
+    fn scan_copy(data: &[u8], n: usize) -> (usize, usize) {
+        if data.get(n) != Some(&0x00) {
+            return (1, 2);
+        }
+        let mut end = n + 1;
+        while data.get(end) == Some(&0x00) {
+            if end - n == 32 {
+                return (31, 32);
+            }
+            end += 1;
+        }
+        let amount = std::cmp::min(data.len() - n, 32);
+        (
+            amount,
+            if amount == 32 {
+                32 - (end - n)
+            } else {
+                1 + amount
+            },
+        )
+    }
+
+    #[test]
+    fn test_zero_runs_match_scans() {
+        let mut data = Vec::new();
+        for run in [1, 2, 31, 32, 33, 63, 64, 65, 130] {
+            data.resize(data.len() + run, 0x00);
+            data.extend([0x07, 0x08]);
+        }
+        data.extend([0x00; 40]);
+        let runs = zero_runs(&data);
+        for n in 0..data.len() {
+            let zeros = zeros_case(&runs, n);
+            let copy = copy_case(&data, &runs, n);
+            assert_eq!(
+                (zeros.decompressed_size, zeros.compressed_size),
+                scan_zeros(&data, n),
+                "zero run at {n}"
+            );
+            assert_eq!(
+                (copy.decompressed_size, copy.compressed_size),
+                scan_copy(&data, n),
+                "copy at {n}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1061 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use alloy::primitives::{Address, Bytes, B256, U256};
+use serde_json::Value;
+
+use crate::{
+    compressor::{AddressLike, Bytes32, CompressResult},
+    corpus::{SELECTOR_SIZE, WORD_SIZE},
+    errors::CompressorError,
+    hex_str::decode_hex,
+    instruction::{Instruction, TABLE_LENGTHS},
+    HashMap,
+};
+
+/// version of the JSON document of `Dict::to_json`, bumped whenever the meaning of an entry
+/// changes
+pub const DICT_FORMAT_VERSION: u64 = 1;
+
+/// suffix lengths of a dictionary entry that can be referenced by the storage methods
+pub const LOOKUP_LENGTHS: [usize; 4] = [32, 31, 20, 4];
+
+/// A dictionary value of its meaningful length, such as a 4-byte selector or a 20-byte address.
+/// It is deployed left-padded to a 32-byte word, only the suffixes that fit within `bytes` can
+/// be referenced so the padding doesn't match zeros of the calldata.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DictEntry {
+    pub bytes: Vec<u8>,
+}
+
+impl DictEntry {
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Result<Self, CompressorError> {
+        let bytes = bytes.into();
+        if bytes.is_empty() || bytes.len() > 32 {
+            return Err(CompressorError::InvalidDictEntry(bytes.len()));
+        }
+        Ok(Self { bytes })
+    }
+
+    // the 32-byte word the entry is deployed as
+    pub fn word(&self) -> Bytes32 {
+        let mut word = Bytes32::default();
+        word[32 - self.bytes.len()..].copy_from_slice(&self.bytes);
+        word
+    }
+}
+
+impl From<Bytes32> for DictEntry {
+    fn from(word: Bytes32) -> Self {
+        Self {
+            bytes: word.to_vec(),
+        }
+    }
+}
+
+/// The entry of `addr`, left-padded to a word as the abi encodes it, so its 20-byte suffix
+/// matches the address in calldata
+pub fn dict_entry_from_address(addr: &Address) -> Bytes32 {
+    addr.to_word()
+}
+
+/// The entry of `value`, a big-endian word as the abi encodes it
+pub fn dict_entry_from_u256(value: &U256) -> Bytes32 {
+    value.to_be_bytes()
+}
+
+/// The entry of a value given as hex, with or without the `0x` prefix, left-padded to a word.
+/// Values of no byte or of more than 32 bytes are rejected, see `DictEntry::new`.
+pub fn dict_entry_from_hex(hex: &str) -> Result<Bytes32, CompressorError> {
+    DictEntry::new(decode_hex(hex)?).map(|entry| entry.word())
+}
+
+// indexes the suffixes of `word` no longer than the `len` meaningful bytes of its entry.
+// Entries are indexed in order and a suffix shared by several of them, such as the 4-byte tail
+// of two different words, keeps the smallest index, the likeliest to fit a 2-byte reference.
+// The reserved slots only keep the suffixes no later entry shares, see `DictView`.
+fn index_word(lookup: &mut HashMap<Vec<u8>, usize>, word: &Bytes32, len: usize, index: usize) {
+    for suffix in LOOKUP_LENGTHS.into_iter().filter(|&suffix| suffix <= len) {
+        let slot = lookup
+            .entry(word[word.len() - suffix..].to_vec())
+            .or_insert(index);
+        if *slot < 2 {
+            *slot = index;
+        }
+    }
+}
+
+/// A dictionary shared by the compressor and the on-chain decompressor.
+/// Index 0 is reserved for the wallet address and index 1 for the contract address,
+/// the remaining entries follow in order.
+///
+/// A dictionary can be made of several segments deployed separately, see `Dict::chain`.
+/// Indices are global: the entries of a segment follow those of the preceding segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dict {
+    pub entries: Vec<Bytes32>,           // index -> value
+    pub lookup: HashMap<Vec<u8>, usize>, // value (or one of its suffixes) -> index
+    pub segments: Vec<Range<usize>>,     // indices of every segment, in order
+}
+
+impl Dict {
+    pub fn new(
+        wallet_addr: impl AddressLike,
+        contract_addr: impl AddressLike,
+        dict: &[Bytes32],
+    ) -> Self {
+        let mut entries = vec![wallet_addr.to_word(), contract_addr.to_word()];
+        entries.extend(dict);
+        Self::from_entries(entries)
+    }
+
+    // builds the lookup for already laid out entries, including the reserved slots
+    pub fn from_entries(entries: Vec<Bytes32>) -> Self {
+        let lookup = entries_lookup(&entries);
+        let segments = core::iter::once(0..entries.len()).collect();
+        Self {
+            entries,
+            lookup,
+            segments,
+        }
+    }
+
+    /// Builds a dictionary out of entries of their actual lengths, see `DictEntry`
+    pub fn with_entries(
+        wallet_addr: impl AddressLike,
+        contract_addr: impl AddressLike,
+        dict: &[DictEntry],
+    ) -> Self {
+        let mut built = Self::new(wallet_addr, contract_addr, &[]);
+        for entry in dict {
+            let index = built.len();
+            index_word(&mut built.lookup, &entry.word(), entry.bytes.len(), index);
+            built.entries.push(entry.word());
+        }
+        built.segments = core::iter::once(0..built.len()).collect();
+        built
+    }
+
+    /// Chains a hot `primary` dictionary with a large `fallback` one: indices below
+    /// `primary.len()` resolve to the primary entries and the following ones to the fallback
+    /// entries, reserved slots included. A value found in both is referenced through the
+    /// primary, whose smaller indices are more likely to fit the 2-byte storage method.
+    pub fn chain(primary: &Self, fallback: &Self) -> Self {
+        let offset = primary.len();
+        let mut lookup: HashMap<Vec<u8>, usize> = fallback
+            .lookup
+            .iter()
+            .map(|(value, index)| (value.clone(), offset + index))
+            .collect();
+        lookup.extend(primary.lookup.clone());
+
+        let segments = primary
+            .segments
+            .iter()
+            .cloned()
+            .chain(
+                fallback
+                    .segments
+                    .iter()
+                    .map(|segment| offset + segment.start..offset + segment.end),
+            )
+            .collect();
+        Self {
+            entries: [primary.entries.as_slice(), &fallback.entries].concat(),
+            lookup,
+            segments,
+        }
+    }
+
+    // position in `segments` of the segment holding `index`
+    pub fn segment_of(&self, index: usize) -> Option<usize> {
+        self.segments
+            .iter()
+            .position(|segment| segment.contains(&index))
+    }
+
+    pub fn wallet_addr(&self) -> Bytes32 {
+        self.entries.first().copied().unwrap_or_default()
+    }
+
+    pub fn contract_addr(&self) -> Bytes32 {
+        self.entries.get(1).copied().unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bytes32> {
+        self.entries.get(index)
+    }
+
+    /// Decodes the storage reference, `10BBXXXX XXXXXXXX` or with `three_byte`
+    /// `11BBXXXX XXXXXXXX XXXXXXXX`, starting `bytes` into the tail of the entry it reads
+    pub fn read_storage(&self, bytes: &[u8], three_byte: bool) -> Result<Vec<u8>, CompressorError> {
+        let (table, index) = match Instruction::decode(bytes)?.0 {
+            Instruction::DictShort { table, index } if !three_byte => (table, index as usize),
+            Instruction::DictLong { table, index } if three_byte => (table, index as usize),
+            _ => return Err(CompressorError::UnexpectedTag(bytes[0])),
+        };
+        Ok(self.read_tail(table, index)?.to_vec())
+    }
+
+    // the tail `TABLE_LENGTHS[table]` of entry `index`, as a storage reference reads it
+    pub(crate) fn read_tail(&self, table: u8, index: usize) -> Result<&[u8], CompressorError> {
+        let entry = self.get(index).ok_or(CompressorError::LookupNotFound)?;
+        Ok(&entry[32 - TABLE_LENGTHS[(table & 0b11) as usize]..])
+    }
+
+    // index of the entry that `value` (a full entry or one of its suffixes) refers to
+    pub fn index_of(&self, value: &[u8]) -> Option<usize> {
+        self.lookup.get(value).copied()
+    }
+
+    /// A view of the dictionary with `wallet` in the reserved wallet slot, for compressing the
+    /// payloads of another signer without cloning or rebuilding the lookup
+    pub fn retarget_wallet(&self, wallet: Address) -> DictView<'_> {
+        DictView {
+            base: self,
+            wallet_addr: wallet.into_word().0,
+        }
+    }
+
+    /// Compares the dictionary with `deployed`, the one a decompressor holds
+    pub fn drift_from(&self, deployed: &Self) -> DriftReport {
+        let first_index = |entries: &[Bytes32]| {
+            let mut indices = HashMap::new();
+            for (i, entry) in entries.iter().enumerate().skip(2) {
+                indices.entry(*entry).or_insert(i);
+            }
+            indices
+        };
+        let local_indices = first_index(&self.entries);
+        let deployed_indices = first_index(&deployed.entries);
+
+        let mut report = DriftReport::default();
+        for i in 2..core::cmp::max(self.entries.len(), deployed.entries.len()) {
+            let local = self.entries.get(i);
+            let on_chain = deployed.entries.get(i);
+            if local == on_chain {
+                continue;
+            }
+            report.diverging.push(i);
+            if let Some(local) = local {
+                match deployed_indices.get(local) {
+                    Some(&j) => report.mismatched.push((i, j)),
+                    None => report.missing.push(i),
+                }
+            }
+            if on_chain.is_some_and(|entry| !local_indices.contains_key(entry)) {
+                report.extra.push(i);
+            }
+        }
+        report
+    }
+
+    // a copy of the dictionary with `extra` appended after the existing entries, as part of
+    // the last segment
+    pub fn extended(&self, extra: &[Bytes32]) -> Self {
+        let mut extended = self.clone();
+        for (i, value) in extra.iter().enumerate() {
+            index_word(&mut extended.lookup, value, 32, self.len() + i);
+        }
+        extended.entries.extend(extra);
+        match extended.segments.last_mut() {
+            Some(last) => last.end = extended.entries.len(),
+            None => extended.segments.push(0..extended.entries.len()),
+        }
+        extended
+    }
+}
+
+/// Differences between a local dictionary and the one a decompressor holds, see `Dict::drift_from`.
+/// The reserved wallet and contract slots are filled by the decompressor and never drift.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DriftReport {
+    pub missing: Vec<usize>, // local indices whose entry isn't deployed
+    pub extra: Vec<usize>,   // deployed indices whose entry isn't in the local dictionary
+    pub mismatched: Vec<(usize, usize)>, // (local, deployed) indices of entries deployed elsewhere
+    pub diverging: Vec<usize>, // indices resolving to different entries, or to none on one side
+}
+
+impl DriftReport {
+    pub fn is_clean(&self) -> bool {
+        self.diverging.is_empty()
+    }
+
+    /// Whether every storage reference of `result` reads the same entry from the deployed
+    /// dictionary as from the local one, so that it decompresses on chain despite the drift
+    pub fn is_safe_for(&self, result: &CompressResult) -> bool {
+        let stream = result.instruction_stream();
+        let mut offset = 0;
+        while let Ok((instruction, size)) = Instruction::decode(&stream[offset..]) {
+            if let Some(index) = instruction.dict_index() {
+                if self.diverging.binary_search(&index).is_ok() {
+                    return false;
+                }
+            }
+            offset += size;
+        }
+        true
+    }
+}
+
+/// A dictionary whose wallet slot is overridden, see `Dict::retarget_wallet`. It resolves values
+/// as `Dict::new` would with the other wallet: the suffixes of the wallet refer to index 0 unless
+/// a later entry shares them, the suffixes of the replaced wallet no longer resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DictView<'a> {
+    pub base: &'a Dict,
+    pub wallet_addr: Bytes32,
+}
+
+impl DictView<'_> {
+    pub fn wallet_addr(&self) -> Bytes32 {
+        self.wallet_addr
+    }
+
+    pub fn contract_addr(&self) -> Bytes32 {
+        self.base.contract_addr()
+    }
+
+    pub fn len(&self) -> usize {
+        core::cmp::max(self.base.len(), 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bytes32> {
+        match index {
+            0 => Some(&self.wallet_addr),
+            _ => self.base.get(index),
+        }
+    }
+
+    pub fn index_of(&self, value: &[u8]) -> Option<usize> {
+        // the wallet of the base itself, whose lookup already resolves it
+        if self.base.entries.first() == Some(&self.wallet_addr) {
+            return self.base.index_of(value);
+        }
+        match self.base.index_of(value) {
+            Some(index) if index != 0 => Some(index),
+            _ => (LOOKUP_LENGTHS.contains(&value.len()) && self.wallet_addr.ends_with(value))
+                .then_some(0),
+        }
+    }
+
+    // the dictionary the view stands for, as a decompressor holding the other wallet sees it
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = self.base.clone();
+        dict.lookup.retain(|_, index| *index != 0);
+        match dict.entries.first_mut() {
+            Some(wallet) => *wallet = self.wallet_addr,
+            None => dict.entries.push(self.wallet_addr),
+        }
+        for len in LOOKUP_LENGTHS {
+            dict.lookup
+                .entry(self.wallet_addr[32 - len..].to_vec())
+                .or_insert(0);
+        }
+        dict
+    }
+}
+
+// the lookup of `Dict::from_entries`, every entry indexed as a full word
+fn entries_lookup(entries: &[Bytes32]) -> HashMap<Vec<u8>, usize> {
+    let mut lookup = HashMap::new();
+    for (i, value) in entries.iter().enumerate() {
+        index_word(&mut lookup, value, 32, i);
+    }
+    lookup
+}
+
+fn invalid_format(reason: impl core::fmt::Display) -> CompressorError {
+    CompressorError::DictFormat(reason.to_string())
+}
+
+// a 32-byte entry of a dictionary document, shorter or longer hex strings are rejected rather
+// than padded so that a truncated value can't shift the bytes of the entry
+fn parse_entry(index: usize, entry: &Value) -> Result<Bytes32, CompressorError> {
+    let hex = entry
+        .as_str()
+        .ok_or_else(|| invalid_format(format!("entry {index} is not a string")))?;
+    if !hex.starts_with("0x") {
+        return Err(invalid_format(format!("entry {index} is not 0x prefixed")));
+    }
+    let bytes = decode_hex(hex).map_err(|e| invalid_format(format!("entry {index}: {e}")))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        invalid_format(format!("entry {index} is {} bytes, not 32", bytes.len()))
+    })
+}
+
+// the `segments` of a dictionary document, consecutive `[start,end]` ranges from index 0 to
+// the last entry
+fn parse_segments(segments: &Value, len: usize) -> Result<Vec<Range<usize>>, CompressorError> {
+    let segments = segments
+        .as_array()
+        .ok_or_else(|| invalid_format("`segments` is not an array"))?;
+    let mut parsed = Vec::with_capacity(segments.len());
+    let mut end = 0;
+    for (i, segment) in segments.iter().enumerate() {
+        let bounds: Option<Vec<usize>> = segment
+            .as_array()
+            .filter(|bounds| bounds.len() == 2)
+            .and_then(|bounds| {
+                bounds
+                    .iter()
+                    .map(|bound| bound.as_u64().map(|bound| bound as usize))
+                    .collect()
+            });
+        match bounds.as_deref() {
+            Some(&[start, next]) if start == end && start <= next => {
+                parsed.push(start..next);
+                end = next;
+            }
+            _ => {
+                return Err(invalid_format(format!(
+                    "segment {i} doesn't follow the previous one"
+                )))
+            }
+        }
+    }
+    if end != len {
+        return Err(invalid_format(format!(
+            "the segments end at {end}, not at {len}"
+        )));
+    }
+    Ok(parsed)
+}
+
+// the `lookup` of a dictionary document, every value a referenceable suffix of its entry
+fn parse_lookup(
+    lookup: &Value,
+    entries: &[Bytes32],
+) -> Result<HashMap<Vec<u8>, usize>, CompressorError> {
+    let lookup = lookup
+        .as_object()
+        .ok_or_else(|| invalid_format("`lookup` is not an object"))?;
+    let mut parsed = HashMap::with_capacity(lookup.len());
+    for (value, index) in lookup {
+        let bytes = decode_hex(value)
+            .ok()
+            .filter(|bytes| value.starts_with("0x") && LOOKUP_LENGTHS.contains(&bytes.len()));
+        let entry = index
+            .as_u64()
+            .and_then(|index| entries.get(index as usize).map(|entry| (index, entry)));
+        match (bytes, entry) {
+            (Some(bytes), Some((index, entry))) if entry.ends_with(&bytes) => {
+                parsed.insert(bytes, index as usize);
+            }
+            _ => {
+                return Err(invalid_format(format!(
+                    "lookup {value} isn't a suffix of entry {index}"
+                )))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+impl Dict {
+    /// The canonical form of the dictionary, `{"version":1,"entries":["0x…",…]}` with every
+    /// entry as a 32-byte word in index order, the reserved wallet and contract slots included,
+    /// so the index of an entry in the document is the index instructions reference.
+    ///
+    /// `Dict::from_json` rebuilds the lookup and the single segment of `Dict::from_entries`.
+    /// Whatever differs from them, the segments of `Dict::chain` or the lookup of
+    /// `Dict::with_entries` shorter entries, is written as well: `"segments":[[0,3],[3,7],…]`
+    /// and `"lookup":{"0x…":3,…}`, the whole lookup sorted by value.
+    pub fn to_json(&self) -> String {
+        // written by hand so the fields keep their order whatever the features of `serde_json`
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| format!("\"{}\"", B256::from(*entry)))
+            .collect();
+        let mut json = format!(
+            r#"{{"version":{DICT_FORMAT_VERSION},"entries":[{}]"#,
+            entries.join(",")
+        );
+        if !matches!(self.segments.as_slice(), [single] if *single == (0..self.len())) {
+            let segments: Vec<String> = self
+                .segments
+                .iter()
+                .map(|segment| format!("[{},{}]", segment.start, segment.end))
+                .collect();
+            json += &format!(r#","segments":[{}]"#, segments.join(","));
+        }
+        if self.lookup != entries_lookup(&self.entries) {
+            let mut lookup: Vec<(&Vec<u8>, &usize)> = self.lookup.iter().collect();
+            lookup.sort();
+            let lookup: Vec<String> = lookup
+                .into_iter()
+                .map(|(value, index)| format!("\"{}\":{index}", Bytes::copy_from_slice(value)))
+                .collect();
+            json += &format!(r#","lookup":{{{}}}"#, lookup.join(","));
+        }
+        json + "}"
+    }
+
+    /// Parses a document of `Dict::to_json`. Anything else is a `DictFormat` error: another
+    /// version, a future one included, an unknown field, an entry that isn't a `0x` prefixed
+    /// 32-byte hex string, segments that don't tile the entries in order or a lookup value that
+    /// isn't a suffix of the entry it maps to.
+    pub fn from_json(json: &str) -> Result<Self, CompressorError> {
+        let value: Value = serde_json::from_str(json).map_err(invalid_format)?;
+        let document = value
+            .as_object()
+            .ok_or_else(|| invalid_format("not an object"))?;
+        if let Some(field) = document
+            .keys()
+            .find(|&field| !["version", "entries", "segments", "lookup"].contains(&field.as_str()))
+        {
+            return Err(invalid_format(format!("unknown field `{field}`")));
+        }
+        let version = document
+            .get("version")
+            .ok_or_else(|| invalid_format("missing `version`"))?;
+        if version.as_u64() != Some(DICT_FORMAT_VERSION) {
+            return Err(invalid_format(format!("unsupported version {version}")));
+        }
+        let entries = document
+            .get("entries")
+            .ok_or_else(|| invalid_format("missing `entries`"))?
+            .as_array()
+            .ok_or_else(|| invalid_format("`entries` is not an array"))?
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| parse_entry(index, entry))
+            .collect::<Result<_, _>>()?;
+        let mut dict = Self::from_entries(entries);
+        if let Some(segments) = document.get("segments") {
+            dict.segments = parse_segments(segments, dict.len())?;
+        }
+        if let Some(lookup) = document.get("lookup") {
+            dict.lookup = parse_lookup(lookup, &dict.entries)?;
+        }
+        Ok(dict)
+    }
+
+    /// `Dict::from_json` of the file at `path`
+    #[cfg(feature = "std")]
+    pub fn load_json(path: impl AsRef<Path>) -> Result<Self, CompressorError> {
+        Self::from_json(
+            &std::fs::read_to_string(path).map_err(|e| CompressorError::Io(e.to_string()))?,
+        )
+    }
+
+    /// Writes `Dict::to_json` to the file at `path`
+    #[cfg(feature = "std")]
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<(), CompressorError> {
+        std::fs::write(path, self.to_json() + "\n").map_err(|e| CompressorError::Io(e.to_string()))
+    }
+}
+
+/// Counts the occurrences of every abi word in `samples`. Words are read at offsets aligned to
+/// 32 bytes, counted from the end of the 4-byte function selector when the length of a sample
+/// suggests it has one (`4 + 32 * n` bytes) and from its start otherwise.
+pub fn word_frequencies<'a>(
+    samples: impl IntoIterator<Item = &'a [u8]>,
+) -> HashMap<Bytes32, usize> {
+    let mut frequencies = HashMap::new();
+    for sample in samples {
+        let start = if sample.len() % WORD_SIZE == SELECTOR_SIZE {
+            SELECTOR_SIZE
+        } else {
+            0
+        };
+        for word in sample[start..].chunks_exact(WORD_SIZE) {
+            *frequencies.entry(word.try_into().unwrap()).or_default() += 1;
+        }
+    }
+    frequencies
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{decompress_with, Calldata, Method},
+        options::CompressOptions,
+    };
+
+    #[test]
+    fn test_chain() {
+        let hot: Bytes32 = [0x11; 32];
+        let shared: Bytes32 = [0x22; 32];
+        let primary = Dict::new([0xaa; 32], [0xbb; 32], &[hot]);
+        let mut protocol = vec![[0x00; 32]; 5000];
+        for (i, word) in protocol.iter_mut().enumerate() {
+            word[28..].copy_from_slice(&(i as u32 | 0xee00_0000).to_be_bytes());
+        }
+        // also in the primary, which must win
+        protocol.push(hot);
+        protocol.push(shared);
+        let fallback = Dict::new([0xcc; 32], [0xdd; 32], &protocol);
+
+        let dict = Dict::chain(&primary, &fallback);
+        assert_eq!(dict.segments, vec![0..3, 3..3 + 5004]);
+        assert_eq!(dict.index_of(&hot), Some(2));
+        assert_eq!(dict.index_of(&shared), Some(3 + 2 + 5001));
+        assert_eq!(dict.segment_of(2), Some(0));
+        assert_eq!(dict.segment_of(3 + 2 + 5001), Some(1));
+        assert_eq!(dict.segment_of(3 + 5004), None);
+        assert_eq!(dict.get(3), Some(&[0xcc; 32]));
+
+        let calldata = Bytes::from([[0x01].as_slice(), &hot, &shared].concat());
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        let result = cb.compress().unwrap();
+        // hot entry through a 2-byte reference, the shared one beyond 4095 through a 3-byte one
+        let index = 3 + 2 + 5001_u32;
+        let far = (index | 0xc0_0000).to_be_bytes();
+        assert_eq!(
+            result.compressed_data.as_raw(),
+            &Bytes::from([[0x40, 0x01, 0x80, 0x02].as_slice(), &far[1..]].concat())
+        );
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+            Ok(calldata)
+        );
+
+        let extended = dict.extended(&[[0x33; 32]]);
+        assert_eq!(extended.segments, vec![0..3, 3..3 + 5005]);
+        assert_eq!(extended.index_of(&hot), Some(2));
+    }
+
+    #[test]
+    fn test_dict_entry_helpers() {
+        let token = Address::from([0xa0; 20]);
+        let amount = U256::from(10).pow(U256::from(18));
+        let entry = dict_entry_from_address(&token);
+        assert_eq!(entry[..12], [0x00; 12]);
+        assert_eq!(entry[12..], token[..]);
+        assert_eq!(
+            dict_entry_from_u256(&amount),
+            B256::from(amount).0,
+            "big-endian"
+        );
+        assert_eq!(dict_entry_from_u256(&U256::from(0x2a))[31], 0x2a);
+        assert_eq!(
+            dict_entry_from_hex("0xA0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0"),
+            Ok(entry)
+        );
+        assert_eq!(
+            dict_entry_from_hex("a9059cbb").unwrap()[28..],
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+        assert_eq!(
+            dict_entry_from_hex(&"11".repeat(33)),
+            Err(CompressorError::InvalidDictEntry(33))
+        );
+        assert_eq!(
+            dict_entry_from_hex("0x"),
+            Err(CompressorError::InvalidDictEntry(0))
+        );
+        assert_eq!(
+            dict_entry_from_hex("0xa9059cbg"),
+            Err(CompressorError::InvalidHex { position: 9 })
+        );
+
+        // `transfer(token, amount)`: the token is read from the dictionary, a right-padded
+        // address would never match
+        let calldata = Bytes::from(
+            [
+                [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                &entry,
+                &dict_entry_from_u256(&amount),
+            ]
+            .concat(),
+        );
+        let mut right_padded = [0x00; 32];
+        right_padded[..20].copy_from_slice(token.as_slice());
+        for (dict, referenced) in [(entry, true), (right_padded, false)] {
+            let dict = Dict::new([0xaa; 32], [0xbb; 32], &[dict]);
+            let mut cb =
+                Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+            cb.set_dict(&dict);
+            let result = cb.compress().unwrap();
+            // a reference covering the bytes of the token
+            let token_reference = result.segments().any(|segment| {
+                segment.method.is_storage() && segment.original.ends_with(token.as_slice())
+            });
+            assert_eq!(token_reference, referenced, "{}", result.table());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_dict_json() {
+        let dict = Dict::new(
+            [0xaa; 32],
+            [0xbb; 32],
+            &[[0x11; 32], DictEntry::new([0xca, 0xfe]).unwrap().word()],
+        );
+        let json = dict.to_json();
+        assert!(
+            json.starts_with(r#"{"version":1,"entries":["0xaaaa"#),
+            "{json}"
+        );
+        assert!(json.ends_with(r#"000000cafe"]}"#), "{json}");
+        assert_eq!(Dict::from_json(&json), Ok(dict.clone()));
+        assert_eq!(
+            Dict::from_json(r#"{"version":1,"entries":[]}"#),
+            Ok(Dict::from_entries(vec![]))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dict.json");
+        dict.save_json(&path).unwrap();
+        assert_eq!(Dict::load_json(&path), Ok(dict.clone()));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Dict::load_json(&path),
+            Err(CompressorError::Io(_))
+        ));
+
+        // the segments of a chain and the lookup of shorter entries are kept
+        let chained = Dict::chain(
+            &Dict::new([0xaa; 32], [0xbb; 32], &[[0x11; 32]]),
+            &Dict::new([0xaa; 32], [0xcc; 32], &[[0x22; 32], [0x33; 32]]),
+        );
+        let short = Dict::with_entries(
+            [0xaa; 32],
+            [0xbb; 32],
+            &[
+                DictEntry::new([0x44; 20]).unwrap(),
+                DictEntry::new([0xa9, 0x05, 0x9c, 0xbb]).unwrap(),
+            ],
+        );
+        for dict in [
+            chained.clone(),
+            chained.extended(&[[0xaa; 32]]),
+            short,
+            Dict::chain(&dict, &Dict::default()),
+            Dict::default(),
+        ] {
+            assert_eq!(
+                Dict::from_json(&dict.to_json()),
+                Ok(dict.clone()),
+                "{dict:?}"
+            );
+        }
+        assert_eq!(chained.index_of(&[0xaa; 32]), Some(0));
+        assert!(
+            chained
+                .to_json()
+                .contains(r#""segments":[[0,3],[3,7]],"lookup":{"#),
+            "{}",
+            chained.to_json()
+        );
+
+        let entry = format!("\"0x{}\"", "11".repeat(32));
+        for (json, reason) in [
+            ("[]".to_string(), "not an object"),
+            (r#"{"entries":[]}"#.to_string(), "missing `version`"),
+            (
+                r#"{"version":2,"entries":[]}"#.to_string(),
+                "unsupported version 2",
+            ),
+            (
+                r#"{"version":"1","entries":[]}"#.to_string(),
+                "unsupported version \"1\"",
+            ),
+            (r#"{"version":1}"#.to_string(), "missing `entries`"),
+            (
+                r#"{"version":1,"entries":{}}"#.to_string(),
+                "`entries` is not an array",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry}],"lengths":[]}}"#),
+                "unknown field `lengths`",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry}],"segments":{{}}}}"#),
+                "`segments` is not an array",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry},{entry}],"segments":[[0,1],[0,2]]}}"#),
+                "segment 1 doesn't follow the previous one",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry}],"segments":[[0,1,2]]}}"#),
+                "segment 0 doesn't follow the previous one",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry},{entry}],"segments":[[0,1]]}}"#),
+                "the segments end at 1, not at 2",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry}],"lookup":[]}}"#),
+                "`lookup` is not an object",
+            ),
+            (
+                format!(
+                    r#"{{"version":1,"entries":[{entry}],"lookup":{{"0x{}":0}}}}"#,
+                    "11".repeat(5)
+                ),
+                "lookup 0x1111111111 isn't a suffix of entry 0",
+            ),
+            (
+                format!(
+                    r#"{{"version":1,"entries":[{entry}],"lookup":{{"0x{}":0}}}}"#,
+                    "22".repeat(4)
+                ),
+                "lookup 0x22222222 isn't a suffix of entry 0",
+            ),
+            (
+                format!(
+                    r#"{{"version":1,"entries":[{entry}],"lookup":{{"0x{}":1}}}}"#,
+                    "11".repeat(4)
+                ),
+                "lookup 0x11111111 isn't a suffix of entry 1",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":[{entry},1]}}"#),
+                "entry 1 is not a string",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":["{}"]}}"#, "11".repeat(32)),
+                "entry 0 is not 0x prefixed",
+            ),
+            (
+                r#"{"version":1,"entries":["0xcafe"]}"#.to_string(),
+                "entry 0 is 2 bytes, not 32",
+            ),
+            (
+                format!(r#"{{"version":1,"entries":["0x{}"]}}"#, "11".repeat(33)),
+                "entry 0 is 33 bytes, not 32",
+            ),
+            (
+                r#"{"version":1,"entries":["0xzz"]}"#.to_string(),
+                "entry 0: Invalid hex at position `2`",
+            ),
+        ] {
+            assert_eq!(
+                Dict::from_json(&json),
+                Err(CompressorError::DictFormat(reason.to_string())),
+                "{json}"
+            );
+        }
+        assert!(matches!(
+            Dict::from_json("{"),
+            Err(CompressorError::DictFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_shared_tails() {
+        let tail = [0xca, 0xfe, 0xba, 0xbe];
+        let word =
+            |fill: u8| -> Bytes32 { [[fill; 28].as_slice(), &tail].concat().try_into().unwrap() };
+        let (near, far) = (word(0x11), word(0x22));
+        let mut entries = vec![near];
+        entries.extend((0..5000_u32).map(|i| {
+            let mut filler = [0x00; 32];
+            filler[28..].copy_from_slice(&(i | 0xee00_0000).to_be_bytes());
+            filler
+        }));
+        entries.push(far);
+        // the wallet shares the tail too, the entries take precedence over it
+        let dict = Dict::new(word(0x33), [0xbb; 32], &entries);
+        let far_index = 2 + 5001;
+        assert_eq!(dict.index_of(&tail), Some(2));
+        assert_eq!(dict.index_of(&far), Some(far_index));
+        assert_eq!(dict.index_of(&word(0x33)), Some(0));
+        // a later entry doesn't take the tail over
+        let extended = dict.extended(&[word(0x44)]);
+        assert_eq!(extended.index_of(&tail), Some(2));
+        assert_eq!(extended.index_of(&word(0x44)), Some(far_index + 1));
+
+        // the tail of the far entry is read from the near one, through a 2-byte reference
+        let calldata = Bytes::from([[0x01].as_slice(), &far, &tail].concat());
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        let result = cb.compress().unwrap();
+        let reference = (far_index as u32 | 0xc0_0000).to_be_bytes();
+        assert_eq!(
+            result.compressed_data.as_raw(),
+            &Bytes::from([[0x40, 0x01].as_slice(), &reference[1..], &[0xa0, 0x02]].concat())
+        );
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+            Ok(calldata)
+        );
+    }
+
+    #[test]
+    fn test_retarget_wallet() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let shared = Dict::new(alice.into_word().0, [0xcc; 32], &[[0x11; 32]]);
+
+        let view = shared.retarget_wallet(bob);
+        assert_eq!(
+            shared.retarget_wallet(alice).index_of(alice.as_slice()),
+            Some(0)
+        );
+        assert_eq!(view.index_of(bob.as_slice()), Some(0));
+        assert_eq!(view.index_of(alice.as_slice()), None);
+        assert_eq!(view.index_of(&[0x11; 32]), Some(2));
+        assert_eq!(view.get(0), Some(&bob.into_word().0));
+        assert_eq!(
+            view.to_dict(),
+            Dict::new(bob.into_word().0, [0xcc; 32], &[[0x11; 32]])
+        );
+        // the shared dictionary is left as is
+        assert_eq!(shared.index_of(alice.as_slice()), Some(0));
+
+        // the same payload, a transfer to the signer, refers to whichever wallet is active
+        let shared = Arc::new(shared);
+        for signer in [alice, bob] {
+            let calldata = Bytes::from(
+                [
+                    [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                    signer.into_word().as_slice(),
+                ]
+                .concat(),
+            );
+            let view = shared.retarget_wallet(signer);
+            let mut cb =
+                Calldata::new(calldata.clone(), view.wallet_addr(), view.contract_addr()).unwrap();
+            cb.set_shared_dict(Arc::clone(&shared));
+            cb.retarget_wallet(signer);
+            let result = cb.compress().unwrap();
+            // read in place, the shared dictionary isn't copied
+            assert!(Arc::ptr_eq(&cb.dict, &shared));
+            assert_eq!(cb.dict_view(), view);
+            assert_eq!(
+                cb.zip(&result.description).unwrap(),
+                result.compressed_data.as_raw()[..]
+            );
+            // the address ends with a 10BBXXXX XXXXXXXX reference to index 0
+            let reference = &result.compressed_data[result.compressed_data.len() - 2..];
+            assert_eq!(result.description.last().unwrap().method, Method::DictShort);
+            assert_eq!(u16::from_be_bytes([reference[0], reference[1]]) & 0x0fff, 0);
+            assert_eq!(
+                decompress_with(
+                    &result.compressed_data,
+                    &view.to_dict(),
+                    &CompressOptions::default()
+                ),
+                Ok(calldata)
+            );
+        }
+    }
+
+    #[test]
+    fn test_variable_length_entries() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let token = [0x5a; 20];
+        let entries = [
+            DictEntry::new(selector).unwrap(),
+            DictEntry::new(token).unwrap(),
+            DictEntry::from([0x77; 32]),
+        ];
+        let dict = Dict::with_entries([0xaa; 32], [0xbb; 32], &entries);
+        assert_eq!(dict.len(), 5);
+        assert_eq!(dict.get(2), Some(&entries[0].word()));
+        assert_eq!(dict.index_of(&selector), Some(2));
+        assert_eq!(dict.index_of(&token), Some(3));
+        assert_eq!(dict.index_of(&[0x77; 20]), Some(4));
+        // the padding of the short entries isn't part of them
+        assert_eq!(dict.index_of(&entries[0].word()), None);
+        assert_eq!(dict.index_of(&entries[1].word()[1..]), None);
+        assert_eq!(
+            Dict::with_entries([0xaa; 32], [0xbb; 32], &[DictEntry::from([0x77; 32])]),
+            Dict::new([0xaa; 32], [0xbb; 32], &[[0x77; 32]])
+        );
+
+        // an abi word holding only the selector is no longer a single reference
+        let word = entries[0].word();
+        let calldata = Bytes::from([selector.as_slice(), &word].concat());
+        let mut cb =
+            Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.init_dict_entries(&entries);
+        let result = cb.compress().unwrap();
+        assert!(result
+            .description
+            .iter()
+            .all(|d| d.method != Method::DictShort || d.amount_bytes == 4));
+        assert_eq!(
+            decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+            Ok(calldata)
+        );
+
+        assert_eq!(
+            DictEntry::new([]),
+            Err(CompressorError::InvalidDictEntry(0))
+        );
+        assert_eq!(
+            DictEntry::new([0x01; 33]),
+            Err(CompressorError::InvalidDictEntry(33))
+        );
+    }
+
+    #[test]
+    fn test_read_storage() {
+        let entry: Bytes32 = core::array::from_fn(|i| i as u8);
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[entry]);
+
+        assert_eq!(dict.read_storage(&[0x80, 0x02], false), Ok(entry.to_vec()));
+        assert_eq!(
+            dict.read_storage(&[0x90, 0x02], false),
+            Ok(entry[12..].to_vec())
+        );
+        assert_eq!(dict.read_storage(&[0xa0, 0x01], false), Ok(vec![0xbb; 4]));
+        assert_eq!(
+            dict.read_storage(&[0xb0, 0x02], false),
+            Ok(entry[1..].to_vec())
+        );
+        assert_eq!(
+            dict.read_storage(&[0xd0, 0x00, 0x02, 0x42], true),
+            Ok(entry[12..].to_vec())
+        );
+
+        // the index past the last entry, and the largest of each form
+        for (bytes, three_byte) in [
+            (&[0x80, 0x03][..], false),
+            (&[0x8f, 0xff], false),
+            (&[0xc0, 0x00, 0x03], true),
+            (&[0xcf, 0xff, 0xff], true),
+        ] {
+            assert_eq!(
+                dict.read_storage(bytes, three_byte),
+                Err(CompressorError::LookupNotFound)
+            );
+        }
+        assert_eq!(
+            dict.read_storage(&[0xc0, 0x00, 0x00], false),
+            Err(CompressorError::UnexpectedTag(0xc0))
+        );
+        assert_eq!(
+            dict.read_storage(&[0x40, 0x00], true),
+            Err(CompressorError::UnexpectedTag(0x40))
+        );
+        assert_eq!(
+            dict.read_storage(&[0xc0, 0x00], true),
+            Err(CompressorError::InvalidRange)
+        );
+    }
+}
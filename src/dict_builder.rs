@@ -0,0 +1,227 @@
+use alloc::{collections::BinaryHeap, vec, vec::Vec};
+use core::{cmp::Reverse, ops::Range};
+
+use alloy::primitives::Bytes;
+
+use crate::{compressor::Bytes32, HashMap, HashSet};
+
+// bytes of a storage reference, the cheap 2-byte one
+const REFERENCE_SIZE: usize = 2;
+
+// the window lengths the builder counts, of the suffixes of an entry a reference reads
+const WORD: usize = 32;
+const ADDRESS: usize = 20;
+const SELECTOR: usize = 4;
+
+// bytes a reference saves over sending `window` without the dictionary: its zero bytes are left
+// to the zero runs, the others would be copied
+fn saving(window: &[u8]) -> usize {
+    let non_zero = window.iter().filter(|&&b| b != 0x00).count();
+    non_zero.saturating_sub(REFERENCE_SIZE)
+}
+
+// `window` left-padded to the word it is deployed as
+fn padded(window: &[u8]) -> Bytes32 {
+    let mut word = Bytes32::default();
+    word[WORD - window.len()..].copy_from_slice(window);
+    word
+}
+
+/// Derives dictionary entries from a corpus of calldatas, see `DictBuilder::from_corpus`
+#[derive(Debug, Clone, Default)]
+pub struct DictBuilder {
+    // (sample, offset) of every occurrence of every 32-, 20- and 4-byte window
+    occurrences: HashMap<Vec<u8>, Vec<(usize, usize)>>,
+    lengths: Vec<usize>, // of the samples
+}
+
+impl DictBuilder {
+    /// Counts the 32-, 20- and 4-byte windows at every offset of `samples`, whether they are
+    /// aligned to abi words or not, such as addresses packed by `abi.encodePacked`
+    pub fn from_corpus(samples: &[Bytes]) -> Self {
+        let mut builder = Self::default();
+        for sample in samples {
+            builder.add_sample(sample);
+        }
+        builder
+    }
+
+    pub fn add_sample(&mut self, sample: &[u8]) {
+        let index = self.lengths.len();
+        self.lengths.push(sample.len());
+        for len in [WORD, ADDRESS, SELECTOR] {
+            for (offset, window) in sample.windows(len).enumerate() {
+                // all-zero windows are the zero runs'
+                if window.iter().any(|&b| b != 0x00) {
+                    self.occurrences
+                        .entry(window.to_vec())
+                        .or_default()
+                        .push((index, offset));
+                }
+            }
+        }
+    }
+
+    // bytes `word` saves as an entry, the occurrences of each of its suffixes not overlapping
+    // the `covered` bytes times what a reference to the suffix saves, with the byte ranges of
+    // the occurrences counted. Longer suffixes are counted first, a suffix occurring within an
+    // occurrence of a longer one isn't counted again.
+    fn gain(&self, word: &Bytes32, covered: &[Vec<bool>]) -> (usize, Vec<(usize, Range<usize>)>) {
+        let mut gain = 0;
+        let mut taken = Vec::new();
+        let mut taken_bytes = HashSet::new();
+        for len in [WORD, ADDRESS, SELECTOR] {
+            let suffix = &word[WORD - len..];
+            let saving = saving(suffix);
+            let Some(occurrences) = self.occurrences.get(suffix).filter(|_| saving > 0) else {
+                continue;
+            };
+            for &(sample, offset) in occurrences {
+                let range = offset..offset + len;
+                let free = range
+                    .clone()
+                    .all(|i| !covered[sample][i] && !taken_bytes.contains(&(sample, i)));
+                if free {
+                    gain += saving;
+                    taken_bytes.extend(range.clone().map(|i| (sample, i)));
+                    taken.push((sample, range));
+                }
+            }
+        }
+        (gain, taken)
+    }
+
+    /// Up to `max_entries` words, the most valuable first so that they get the indices of
+    /// 2-byte references. Every counted window is a candidate, left-padded to a word, scored by
+    /// its occurrences times the bytes a reference saves on each. Zero bytes are left to the
+    /// zero runs: they don't count as saved, which ranks zero-dominated windows low.
+    ///
+    /// Entries are picked greedily: once one is picked, the bytes of its occurrences are taken
+    /// and the occurrences of the other candidates overlapping them no longer count. A value
+    /// read across its zero padding or the windows straddling it and its neighbours only keep
+    /// what they save beyond it.
+    pub fn build(&self, max_entries: usize) -> Vec<Bytes32> {
+        let mut covered: Vec<Vec<bool>> =
+            self.lengths.iter().map(|&len| vec![false; len]).collect();
+        let candidates: HashSet<Bytes32> = self.occurrences.keys().map(|w| padded(w)).collect();
+        // scores only drop as bytes get taken, a candidate whose updated score still tops the
+        // stale ones of the others is the best
+        let mut heap: BinaryHeap<(usize, Reverse<Bytes32>)> = candidates
+            .into_iter()
+            .map(|word| (self.gain(&word, &covered).0, Reverse(word)))
+            .filter(|&(gain, _)| gain > 0)
+            .collect();
+        let mut entries = Vec::new();
+        while entries.len() < max_entries {
+            let Some((_, Reverse(word))) = heap.pop() else {
+                break;
+            };
+            let (gain, taken) = self.gain(&word, &covered);
+            if gain == 0 {
+                continue;
+            }
+            if heap.peek().is_some_and(|&(next, _)| next > gain) {
+                heap.push((gain, Reverse(word)));
+                continue;
+            }
+            for (sample, range) in taken {
+                covered[sample][range].fill(true);
+            }
+            entries.push(word);
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::compress;
+
+    // `bytes` without its leading and trailing zeros
+    fn trim_zeros(bytes: &[u8]) -> &[u8] {
+        let start = bytes.iter().position(|&b| b != 0x00).unwrap_or(bytes.len());
+        let end = bytes
+            .iter()
+            .rposition(|&b| b != 0x00)
+            .map_or(start, |end| end + 1);
+        &bytes[start..end]
+    }
+
+    const SELECTOR_BYTES: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+    const TOKEN: [u8; 20] = [
+        0xa0, 0xb8, 0x69, 0x91, 0xc6, 0x21, 0x8b, 0x36, 0xc1, 0xd1, 0x9d, 0x4a, 0x2e, 0x9e, 0xb0,
+        0xce, 0x36, 0x06, 0xeb, 0x48,
+    ];
+
+    // `transfer(token, recipient, amount)` to a new recipient every time, every other one with
+    // the token packed after the selector rather than abi encoded
+    fn samples() -> Vec<Bytes> {
+        (1..=24_u8)
+            .map(|i| {
+                let recipient: Vec<u8> = (0..20).map(|b| b ^ i.wrapping_mul(37) | 1).collect();
+                let mut amount = Bytes32::default();
+                amount[30] = i;
+                let token = if i % 2 == 0 {
+                    TOKEN.to_vec()
+                } else {
+                    padded(&TOKEN).to_vec()
+                };
+                Bytes::from(
+                    [
+                        SELECTOR_BYTES.as_slice(),
+                        &token,
+                        &padded(&recipient),
+                        &amount,
+                    ]
+                    .concat(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dict_builder() {
+        let samples = samples();
+        let builder = DictBuilder::from_corpus(&samples);
+        let entries = builder.build(4);
+        // the token first, then the selector; recipients are only seen once
+        assert_eq!(entries[..2], [padded(&TOKEN), padded(&SELECTOR_BYTES)]);
+        assert_eq!(entries.len(), 4);
+        // no shifted copy of the token, nor a window of the amounts
+        for entry in &entries[2..] {
+            assert!(
+                !TOKEN
+                    .windows(trim_zeros(entry).len())
+                    .any(|w| w == trim_zeros(entry)),
+                "{entry:?}"
+            );
+            assert!(saving(entry) > 2, "{entry:?}");
+        }
+        assert_eq!(builder.build(1), [padded(&TOKEN)]);
+
+        // both encodings of the token are read from the dictionary
+        for sample in &samples[..2] {
+            let plain = compress(sample.clone(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+            let result = compress(sample.clone(), [0xaa; 32], [0xbb; 32], &entries).unwrap();
+            assert!(result.stats().storage_references >= 1);
+            assert!(result.compressed_data.len() < plain.compressed_data.len());
+        }
+    }
+
+    #[test]
+    fn test_dict_builder_zeros() {
+        // zeros and windows of a single byte between zeros are left to the zero runs
+        let samples: Vec<Bytes> = (0..8_u8)
+            .map(|_| Bytes::from([[0x00; 40].as_slice(), &[0x01], &[0x00; 40]].concat()))
+            .collect();
+        assert_eq!(
+            DictBuilder::from_corpus(&samples).build(16),
+            Vec::<Bytes32>::new()
+        );
+        assert_eq!(
+            DictBuilder::from_corpus(&[]).build(16),
+            Vec::<Bytes32>::new()
+        );
+    }
+}
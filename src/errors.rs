@@ -1,5 +1,10 @@
+use alloc::{boxed::Box, string::String};
+
+use alloy::primitives::Address;
 use thiserror::Error;
 
+use crate::{plan::PlanWarning, verify::Mismatch};
+
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum CompressorError {
     #[error("Dict not init")]
@@ -10,4 +15,41 @@ pub enum CompressorError {
     LookupNotFound,
     #[error("Unsupported method: `{0}`")]
     UnsupportedMethod(u8),
+    #[error("Zero run too long: `{0}` bytes, at most 64 fit an instruction")]
+    RunTooLong(usize),
+    #[error("Unexpected instruction tag: `{0:#04x}`")]
+    UnexpectedTag(u8),
+    #[error("Invalid plan at description `{0}`")]
+    InvalidPlan(usize),
+    #[error("Non-canonical plan: {0}")]
+    NonCanonicalPlan(PlanWarning),
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
+    #[error("Provider error: {0}")]
+    Provider(String),
+    #[error("Invalid SSTORE2 pointer: `{0}`")]
+    InvalidPointer(Address),
+    #[error("Invalid dict length: `{0}` is not a multiple of 32")]
+    InvalidDictLength(usize),
+    #[error("Invalid dict entry: `{0}` bytes is not between 1 and 32")]
+    InvalidDictEntry(usize),
+    #[error("Dict too large: index `{0}` doesn't fit its storage reference")]
+    DictTooLarge(usize),
+    #[error("Roundtrip {0}")]
+    RoundtripMismatch(Box<Mismatch>),
+    #[error("No compression: `{compressed}` bytes for `{uncompressed}` bytes of calldata")]
+    NoCompression {
+        uncompressed: usize,
+        compressed: usize,
+    },
+    #[error("Write error: {0}")]
+    Io(String),
+    #[error("Invalid hex at position `{position}`")]
+    InvalidHex { position: usize },
+    #[error("Odd hex length: `{0}` digits")]
+    OddHexLength(usize),
+    #[error("Invalid test vector: {0}")]
+    InvalidTestVector(String),
+    #[error("Invalid dict format: {0}")]
+    DictFormat(String),
 }
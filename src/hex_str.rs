@@ -0,0 +1,231 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use alloy::primitives::Bytes;
+
+use crate::{
+    compressor::{compress, decompress, Bytes32, CompressResult},
+    errors::CompressorError,
+};
+
+// value of the hex digit `c`
+fn digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes a hex string, with or without the `0x` prefix, in either case. `InvalidHex` points at
+/// the offending character of `hex`, valid digits of an odd number are `OddHexLength`.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>, CompressorError> {
+    let prefix = if hex.starts_with("0x") || hex.starts_with("0X") {
+        2
+    } else {
+        0
+    };
+    let digits = &hex.as_bytes()[prefix..];
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    for (i, pair) in digits.chunks(2).enumerate() {
+        let position = prefix + 2 * i;
+        let high = digit(pair[0]).ok_or(CompressorError::InvalidHex { position })?;
+        let low = *pair
+            .get(1)
+            .ok_or(CompressorError::OddHexLength(digits.len()))?;
+        let low = digit(low).ok_or(CompressorError::InvalidHex {
+            position: position + 1,
+        })?;
+        bytes.push(high << 4 | low);
+    }
+    Ok(bytes)
+}
+
+// a dictionary value, wallet or contract address given as hex, left-padded to a 32-byte word
+fn decode_word(hex: &str) -> Result<Bytes32, CompressorError> {
+    let bytes = decode_hex(hex)?;
+    if bytes.len() > 32 {
+        return Err(CompressorError::InvalidDictEntry(bytes.len()));
+    }
+    let mut word = Bytes32::default();
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+fn decode_dict(dict: &[&str]) -> Result<Vec<Bytes32>, CompressorError> {
+    dict.iter().map(|entry| decode_word(entry)).collect()
+}
+
+/// The figures of a compression a preview of its savings needs, see `compress_hex_summary`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexCompression {
+    pub compressed: String, // `0x` prefixed and lowercase, as `compress_hex` returns it
+    pub ratio: f64,         // `CompressResult::compression_ratio`
+    pub saved_bytes: i64,   // `CompressResult::saved_bytes`
+}
+
+// `compress` of the hex inputs of `compress_hex`
+fn compress_decoded(
+    calldata: &str,
+    wallet: &str,
+    contract: &str,
+    dict: &[&str],
+) -> Result<CompressResult, CompressorError> {
+    compress(
+        Bytes::from(decode_hex(calldata)?),
+        decode_word(wallet)?,
+        decode_word(contract)?,
+        &decode_dict(dict)?,
+    )
+}
+
+/// `compress` taking and returning hex strings, as copied from an explorer. The addresses and
+/// dictionary values may be shorter than 32 bytes, they are left-padded like abi words. The
+/// output is `0x` prefixed and lowercase.
+pub fn compress_hex(
+    calldata: &str,
+    wallet: &str,
+    contract: &str,
+    dict: &[&str],
+) -> Result<String, CompressorError> {
+    let result = compress_decoded(calldata, wallet, contract, dict)?;
+    Ok(result.compressed_data.as_raw().to_string())
+}
+
+/// `compress_hex` with the ratio and the bytes saved, the result of the `compress_hex` of the
+/// `wasm` feature
+pub fn compress_hex_summary(
+    calldata: &str,
+    wallet: &str,
+    contract: &str,
+    dict: &[&str],
+) -> Result<HexCompression, CompressorError> {
+    let result = compress_decoded(calldata, wallet, contract, dict)?;
+    Ok(HexCompression {
+        compressed: result.compressed_data.as_raw().to_string(),
+        ratio: result.compression_ratio(),
+        saved_bytes: result.saved_bytes(),
+    })
+}
+
+/// `decompress` of the output of `compress_hex`, with the same inputs
+pub fn decompress_hex(
+    compressed: &str,
+    wallet: &str,
+    contract: &str,
+    dict: &[&str],
+) -> Result<String, CompressorError> {
+    let calldata = decompress(
+        &Bytes::from(decode_hex(compressed)?),
+        decode_word(wallet)?,
+        decode_word(contract)?,
+        &decode_dict(dict)?,
+    )?;
+    Ok(calldata.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+    const CONTRACT: &str = "0x1111111254EEB25477B68fb85Ed929f73A960582";
+
+    #[test]
+    fn test_compress_hex() {
+        let recipient = "0x000000000022D473030F116dDEE9F6B43aC78BA3";
+        let calldata = "0xa9059cbb\
+                        000000000000000000000000000000000022d473030f116ddee9f6b43ac78ba3\
+                        000000000000000000000000000000000000000000000000000000000000002a"
+            .to_string();
+        let compressed = compress_hex(&calldata, WALLET, CONTRACT, &[recipient]).unwrap();
+        // the selector is copied and the recipient word is a reference to entry 2
+        assert!(compressed.starts_with("0x43a9059cbb8002"), "{compressed}");
+        assert_eq!(
+            decompress_hex(&compressed, WALLET, CONTRACT, &[recipient]),
+            Ok(calldata.clone())
+        );
+
+        // the prefix is optional and digits of any case are accepted
+        let upper = calldata.trim_start_matches("0x").to_uppercase();
+        assert_eq!(
+            compress_hex(&upper, WALLET, CONTRACT, &[recipient]),
+            Ok(compressed.clone())
+        );
+        assert_eq!(
+            compress_hex(&upper, &WALLET.to_lowercase(), CONTRACT, &[recipient]),
+            Ok(compressed)
+        );
+    }
+
+    #[test]
+    fn test_compress_hex_summary() {
+        let calldata = "0xa9059cbb\
+                        000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045\
+                        000000000000000000000000000000000000000000000000000000000000002a";
+        let summary = compress_hex_summary(calldata, WALLET, CONTRACT, &[]).unwrap();
+        assert_eq!(
+            Ok(summary.compressed.clone()),
+            compress_hex(calldata, WALLET, CONTRACT, &[])
+        );
+        // the selector is copied, the wallet is read from its slot
+        assert!(
+            summary.compressed.starts_with("0x43a9059cbb"),
+            "{summary:?}"
+        );
+        let compressed = (summary.compressed.len() - 2) / 2;
+        assert_eq!(summary.saved_bytes, 68 - compressed as i64);
+        assert_eq!(summary.ratio, compressed as f64 / 68.0);
+
+        assert_eq!(
+            compress_hex_summary("0xa9059cb", WALLET, CONTRACT, &[]),
+            Err(CompressorError::OddHexLength(7))
+        );
+    }
+
+    #[test]
+    fn test_invalid_hex() {
+        assert_eq!(decode_hex(""), Ok(Vec::new()));
+        assert_eq!(decode_hex("0x"), Ok(Vec::new()));
+        assert_eq!(decode_hex("0XaB"), Ok(vec![0xab]));
+
+        // odd length, the last digit is unpaired
+        assert_eq!(
+            decode_hex("0xa9059cb"),
+            Err(CompressorError::OddHexLength(7))
+        );
+        assert_eq!(
+            compress_hex("abc", WALLET, CONTRACT, &[]),
+            Err(CompressorError::OddHexLength(3))
+        );
+        // a bad character wins over the odd length
+        assert_eq!(
+            decode_hex("0xabg"),
+            Err(CompressorError::InvalidHex { position: 4 })
+        );
+        // embedded whitespace
+        assert_eq!(
+            decode_hex("0xa905 9cbb"),
+            Err(CompressorError::InvalidHex { position: 6 })
+        );
+        assert_eq!(
+            compress_hex("0xa9059cbb", WALLET, " 0x11", &[]),
+            Err(CompressorError::InvalidHex { position: 0 })
+        );
+        assert_eq!(
+            compress_hex("0xa9059cbb\n", WALLET, CONTRACT, &[]),
+            Err(CompressorError::InvalidHex { position: 10 })
+        );
+        assert_eq!(
+            decode_hex("0xg0"),
+            Err(CompressorError::InvalidHex { position: 2 })
+        );
+        assert_eq!(
+            compress_hex("0x00", WALLET, CONTRACT, &[&"ab".repeat(33)]),
+            Err(CompressorError::InvalidDictEntry(33))
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+
+use crate::{compressor::Method, errors::CompressorError};
+
+/// tail lengths of a dictionary entry selected by the `BB` bits of a storage reference
+pub const TABLE_LENGTHS: [usize; 4] = [32, 20, 4, 31];
+/// largest index of a 2-byte storage reference, 12 bits
+pub const MAX_SHORT_INDEX: u16 = 0x0fff;
+/// largest index of a 3-byte storage reference, 20 bits
+pub const MAX_LONG_INDEX: u32 = 0x000f_ffff;
+
+/// One instruction of a compressed payload, as the decompressor reads it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    // `00XXXXXX`, `len` zero bytes, 1 to 64
+    ZeroRun { len: u8 },
+    // `01PXXXXX` followed by `bytes`, 1 to 32 of them. `P` is set when `pad` isn't zero, the
+    // decompressor then left-pads `bytes` to a 32-byte word: decoding yields `32 - bytes.len()`
+    Copy { pad: u8, bytes: Vec<u8> },
+    // `10BBXXXX XXXXXXXX`, the tail `TABLE_LENGTHS[table]` of entry `index`
+    DictShort { table: u8, index: u16 },
+    // `11BBXXXX XXXXXXXX XXXXXXXX`, the tail `TABLE_LENGTHS[table]` of entry `index`
+    DictLong { table: u8, index: u32 },
+}
+
+impl Instruction {
+    /// Appends the instruction to `out`. Fields out of their range are truncated to their bits,
+    /// lengths of 0 included, `zip` rejects the lengths and dictionary indices that don't fit
+    /// before encoding.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::ZeroRun { len } => out.push(len.wrapping_sub(1) & 0x3f),
+            Self::Copy { pad, bytes } => {
+                let padded = if *pad == 0 { 0 } else { 0x20 };
+                out.push(0x40 | padded | (bytes.len().wrapping_sub(1) as u8 & 0x1f));
+                out.extend(bytes);
+            }
+            Self::DictShort { table, index } => {
+                let value = 0x8000 | (u16::from(table & 0b11) << 12) | (index & MAX_SHORT_INDEX);
+                out.extend(value.to_be_bytes());
+            }
+            Self::DictLong { table, index } => {
+                let value =
+                    0x00c0_0000 | (u32::from(table & 0b11) << 20) | (index & MAX_LONG_INDEX);
+                out.extend(&value.to_be_bytes()[1..]);
+            }
+        }
+    }
+
+    /// Reads the instruction at the start of `stream`, returning it with its size in bytes
+    pub fn decode(stream: &[u8]) -> Result<(Self, usize), CompressorError> {
+        let tag = *stream.first().ok_or(CompressorError::InvalidRange)?;
+        let operand = |size: usize| stream.get(..size).ok_or(CompressorError::InvalidRange);
+        match tag >> 6 {
+            0b00 => Ok((
+                Self::ZeroRun {
+                    len: (tag & 0x3f) + 1,
+                },
+                1,
+            )),
+            0b01 => {
+                let amount = (tag & 0x1f) as usize + 1;
+                let bytes = operand(1 + amount)?[1..].to_vec();
+                let pad = if tag & 0x20 == 0 {
+                    0
+                } else {
+                    32 - amount as u8
+                };
+                Ok((Self::Copy { pad, bytes }, 1 + amount))
+            }
+            0b10 => {
+                let value = u16::from_be_bytes(operand(2)?.try_into().unwrap());
+                Ok((
+                    Self::DictShort {
+                        table: ((value >> 12) & 0b11) as u8,
+                        index: value & MAX_SHORT_INDEX,
+                    },
+                    2,
+                ))
+            }
+            _ => {
+                let bytes = operand(3)?;
+                let value = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+                Ok((
+                    Self::DictLong {
+                        table: ((value >> 20) & 0b11) as u8,
+                        index: value & MAX_LONG_INDEX,
+                    },
+                    3,
+                ))
+            }
+        }
+    }
+
+    /// Number of bytes the instruction expands to
+    pub fn decompressed_len(&self) -> usize {
+        match self {
+            Self::ZeroRun { len } => *len as usize,
+            Self::Copy { pad, bytes } => *pad as usize + bytes.len(),
+            Self::DictShort { table, .. } | Self::DictLong { table, .. } => {
+                TABLE_LENGTHS[(*table & 0b11) as usize]
+            }
+        }
+    }
+
+    // compression method of the instruction, as in `CompressDataDescription::method`
+    pub fn method(&self) -> Method {
+        match self {
+            Self::ZeroRun { .. } => Method::Zeros,
+            Self::Copy { .. } => Method::Copy,
+            Self::DictShort { .. } => Method::DictShort,
+            Self::DictLong { .. } => Method::DictLong,
+        }
+    }
+
+    // dictionary entry a storage reference reads, `None` for the other instructions
+    pub fn dict_index(&self) -> Option<usize> {
+        match self {
+            Self::DictShort { index, .. } => Some(*index as usize),
+            Self::DictLong { index, .. } => Some(*index as usize),
+            Self::ZeroRun { .. } | Self::Copy { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(instruction: &Instruction, encoded: &[u8]) {
+        let mut out = Vec::new();
+        instruction.encode(&mut out);
+        assert_eq!(out, encoded, "{instruction:?}");
+        assert_eq!(
+            Instruction::decode(encoded),
+            Ok((instruction.clone(), encoded.len()))
+        );
+    }
+
+    #[test]
+    fn test_instruction_boundaries() {
+        roundtrip(&Instruction::ZeroRun { len: 1 }, &[0x00]);
+        roundtrip(&Instruction::ZeroRun { len: 64 }, &[0x3f]);
+
+        roundtrip(
+            &Instruction::Copy {
+                pad: 0,
+                bytes: vec![0x00],
+            },
+            &[0x40, 0x00],
+        );
+        roundtrip(
+            &Instruction::Copy {
+                pad: 31,
+                bytes: vec![0x2a],
+            },
+            &[0x60, 0x2a],
+        );
+        let word: Vec<u8> = (1..=32).collect();
+        roundtrip(
+            &Instruction::Copy {
+                pad: 0,
+                bytes: word.clone(),
+            },
+            &[[0x5f].as_slice(), &word].concat(),
+        );
+
+        roundtrip(
+            &Instruction::DictShort { table: 0, index: 0 },
+            &[0x80, 0x00],
+        );
+        roundtrip(
+            &Instruction::DictShort {
+                table: 3,
+                index: MAX_SHORT_INDEX,
+            },
+            &[0xbf, 0xff],
+        );
+        roundtrip(
+            &Instruction::DictLong { table: 0, index: 0 },
+            &[0xc0, 0x00, 0x00],
+        );
+        roundtrip(
+            &Instruction::DictLong {
+                table: 2,
+                index: MAX_LONG_INDEX,
+            },
+            &[0xef, 0xff, 0xff],
+        );
+
+        // lengths out of range are truncated like the other fields, rather than underflowing
+        for (instruction, tag) in [
+            (Instruction::ZeroRun { len: 0 }, 0x3f),
+            (
+                Instruction::Copy {
+                    pad: 0,
+                    bytes: vec![],
+                },
+                0x5f,
+            ),
+        ] {
+            let mut out = Vec::new();
+            instruction.encode(&mut out);
+            assert_eq!(out, [tag], "{instruction:?}");
+        }
+
+        assert_eq!(
+            Instruction::DictLong { table: 1, index: 7 }.decompressed_len(),
+            20
+        );
+        assert_eq!(
+            Instruction::DictLong { table: 1, index: 7 }.dict_index(),
+            Some(7)
+        );
+        assert_eq!(Instruction::ZeroRun { len: 1 }.dict_index(), None);
+        assert_eq!(
+            Instruction::Copy {
+                pad: 31,
+                bytes: vec![0x2a],
+            }
+            .decompressed_len(),
+            32
+        );
+    }
+
+    #[test]
+    fn test_truncated_instructions() {
+        for stream in [&[][..], &[0x41, 0x01], &[0x80], &[0xc0, 0x00]] {
+            assert_eq!(
+                Instruction::decode(stream),
+                Err(CompressorError::InvalidRange),
+                "{stream:?}"
+            );
+        }
+    }
+}
@@ -1,6 +1,69 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "no_std")))]
+compile_error!("either the `std` or the `no_std` feature must be enabled");
+
+pub mod arbitrum;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "provider")]
+pub mod chain;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod compat;
 pub mod compressor;
+pub mod corpus;
+pub mod cost;
+pub mod deployment;
+pub mod detector;
+pub mod dict;
+pub mod dict_builder;
 pub mod errors;
+pub mod hex_str;
+pub mod instruction;
+pub mod op_stack;
+pub mod options;
+pub mod plan;
+pub mod planner;
+#[cfg(feature = "std")]
+pub mod session;
 pub mod utils;
+pub mod vector;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zksync;
+
+// the maps of `std`, or of `hashbrown` in `no_std` builds
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{HashMap, HashSet};
 
+pub use arbitrum::*;
+#[cfg(feature = "std")]
+pub use batch::*;
+#[cfg(feature = "codegen")]
+pub use codegen::*;
+pub use compat::*;
 pub use compressor::*;
+pub use corpus::*;
+pub use cost::*;
+pub use deployment::*;
+pub use detector::*;
+pub use dict::*;
+pub use dict_builder::*;
 pub use errors::*;
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::{HashMap, HashSet};
+pub use hex_str::*;
+pub use instruction::*;
+pub use op_stack::*;
+pub use options::*;
+pub use plan::*;
+pub use planner::*;
+#[cfg(feature = "std")]
+pub use session::*;
+pub use vector::*;
+pub use verify::*;
+pub use zksync::*;
@@ -0,0 +1,209 @@
+//! Compresses or decompresses one calldata from the shell.
+//!
+//! ```sh
+//! echo 0xa9059cbb... | calldata-compressor compress --wallet 0xaa.. --contract 0xbb.. \
+//!     --dict-file dict.json
+//! ```
+//!
+//! The calldata is read as hex from `--input`, or from stdin without it. The result is printed
+//! to stdout as `0x` prefixed hex, the compression ratio to stderr so that the result can be
+//! piped. Exits with 1 on a `CompressorError` and 2 on invalid arguments.
+use std::{fs, io::Read, process::ExitCode};
+
+use calldata_compressor::{compress_hex_summary, decode_hex, decompress_hex};
+
+const USAGE: &str = "usage: calldata-compressor <compress|decompress> --wallet <HEX> \
+                     --contract <HEX> [--dict-file <FILE>] [--input <FILE>]
+
+  --wallet <HEX>       the wallet address, dictionary entry 0
+  --contract <HEX>     the contract address, dictionary entry 1
+  --dict-file <FILE>   a JSON array of the other entries, 32-byte hex strings
+  --input <FILE>       the hex calldata, or compressed calldata, read from stdin without it";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Compress,
+    Decompress,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Args {
+    command: Command,
+    wallet: String,
+    contract: String,
+    dict_file: Option<String>,
+    input: Option<String>,
+}
+
+// the arguments after the program name
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = match args.next().as_deref() {
+        Some("compress") => Command::Compress,
+        Some("decompress") => Command::Decompress,
+        Some(other) => return Err(format!("unknown command `{other}`")),
+        None => return Err("missing command".to_string()),
+    };
+    let (mut wallet, mut contract, mut dict_file, mut input) = (None, None, None, None);
+    while let Some(flag) = args.next() {
+        let slot = match flag.as_str() {
+            "--wallet" => &mut wallet,
+            "--contract" => &mut contract,
+            "--dict-file" => &mut dict_file,
+            "--input" => &mut input,
+            _ => return Err(format!("unknown argument `{flag}`")),
+        };
+        let value = args
+            .next()
+            .ok_or_else(|| format!("`{flag}` needs a value"))?;
+        if slot.replace(value).is_some() {
+            return Err(format!("`{flag}` given twice"));
+        }
+    }
+    Ok(Args {
+        command,
+        wallet: wallet.ok_or("missing `--wallet`")?,
+        contract: contract.ok_or("missing `--contract`")?,
+        dict_file,
+        input,
+    })
+}
+
+// the result as hex and the compression ratio, the compressed size over the calldata size
+fn run(args: &Args, input: &str) -> Result<(String, f64), Box<dyn std::error::Error>> {
+    let dict: Vec<String> = match &args.dict_file {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    let dict: Vec<&str> = dict.iter().map(String::as_str).collect();
+    let input = input.trim();
+    match args.command {
+        Command::Compress => {
+            let summary = compress_hex_summary(input, &args.wallet, &args.contract, &dict)?;
+            Ok((summary.compressed, summary.ratio))
+        }
+        Command::Decompress => {
+            let calldata = decompress_hex(input, &args.wallet, &args.contract, &dict)?;
+            // both are valid hex by now
+            let ratio = decode_hex(input)?.len() as f64 / decode_hex(&calldata)?.len() as f64;
+            Ok((calldata, ratio))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let input = match &args.input {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    match input
+        .map_err(Into::into)
+        .and_then(|input| run(&args, &input))
+    {
+        Ok((output, ratio)) => {
+            println!("{output}");
+            eprintln!("ratio: {ratio:.4}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            args(&["compress", "--contract", "0xbb", "--wallet", "0xaa"]),
+            Ok(Args {
+                command: Command::Compress,
+                wallet: "0xaa".to_string(),
+                contract: "0xbb".to_string(),
+                dict_file: None,
+                input: None,
+            })
+        );
+        let parsed = args(&[
+            "decompress",
+            "--wallet",
+            "0xaa",
+            "--contract",
+            "0xbb",
+            "--dict-file",
+            "dict.json",
+            "--input",
+            "calldata.hex",
+        ])
+        .unwrap();
+        assert_eq!(parsed.command, Command::Decompress);
+        assert_eq!(parsed.dict_file.as_deref(), Some("dict.json"));
+        assert_eq!(parsed.input.as_deref(), Some("calldata.hex"));
+
+        assert_eq!(args(&[]), Err("missing command".to_string()));
+        assert_eq!(args(&["zip"]), Err("unknown command `zip`".to_string()));
+        assert_eq!(
+            args(&["compress", "--wallet", "0xaa"]),
+            Err("missing `--contract`".to_string())
+        );
+        assert_eq!(
+            args(&["compress", "--wallet"]),
+            Err("`--wallet` needs a value".to_string())
+        );
+        assert_eq!(
+            args(&["compress", "--wallet", "0xaa", "--wallet", "0xaa"]),
+            Err("`--wallet` given twice".to_string())
+        );
+        assert_eq!(
+            args(&["compress", "--verbose"]),
+            Err("unknown argument `--verbose`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        // removed when dropped, even if an assertion fails
+        let dict_file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(&dict_file, r#"["0xa9059cbb"]"#).unwrap();
+        let mut args = Args {
+            command: Command::Compress,
+            wallet: "0xaa".to_string(),
+            contract: "0xbb".to_string(),
+            dict_file: Some(dict_file.path().to_str().unwrap().to_string()),
+            input: None,
+        };
+        let calldata = format!("0xa9059cbb{}\n", "00".repeat(64));
+        let (compressed, ratio) = run(&args, &calldata).unwrap();
+        assert!(ratio < 1.0);
+
+        args.command = Command::Decompress;
+        let (decompressed, inverse) = run(&args, &compressed).unwrap();
+        assert_eq!(decompressed, calldata.trim());
+        assert_eq!(inverse, ratio);
+
+        // a `CompressorError` and an unreadable dictionary are errors
+        assert_eq!(
+            run(&args, "0xzz").unwrap_err().to_string(),
+            "Invalid hex at position `2`"
+        );
+        args.dict_file = Some("missing.json".to_string());
+        assert!(run(&args, &compressed).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use alloc::vec;
+
+use crate::cost::CostModel;
+
+/// intercept of the linear regression of the L1 size of a transaction on its FastLZ size,
+/// scaled by 1e6
+pub const L1_COST_INTERCEPT: i64 = -42_585_600;
+/// slope of the linear regression of the L1 size of a transaction on its FastLZ size, scaled
+/// by 1e6
+pub const L1_COST_FASTLZ_COEF: i64 = 836_500;
+/// smallest estimated L1 size of a transaction, in bytes
+pub const MIN_TRANSACTION_SIZE: i64 = 100;
+/// bytes of a signature, added by `GasPriceOracle.getL1Fee` to the size of an unsigned
+/// transaction
+pub const SIGNATURE_OVERHEAD: usize = 68;
+
+// scale of the size estimate and of the scalars, the fee is divided by their product
+const SIZE_SCALE: i64 = 1_000_000;
+const FEE_SCALE: u128 = 1_000_000_000_000;
+
+// entries of the FastLZ hash table
+const HASH_TABLE_SIZE: usize = 8192;
+// farthest back a FastLZ level 1 match may point
+const MAX_DISTANCE: usize = 0x1fff;
+// longest match a single FastLZ level 1 instruction encodes, minus 2
+const MAX_MATCH: usize = 262;
+
+// bytes of the literal instructions for a run of `len` unmatched bytes, 32 at most each
+fn literals_len(len: usize) -> usize {
+    33 * (len / 32) + if len % 32 == 0 { 0 } else { len % 32 + 1 }
+}
+
+// bytes of the match instructions for a match of `len` bytes beyond the first 2
+fn match_len(len: usize) -> usize {
+    let len = len - 1;
+    3 * (len / MAX_MATCH) + if len % MAX_MATCH >= 6 { 3 } else { 2 }
+}
+
+/// Size of `data` compressed with FastLZ level 1, the way the `GasPriceOracle` of OP-stack chains
+/// computes it (`flzCompressLen` of solady) without producing the compressed bytes
+pub fn flz_compress_len(data: &[u8]) -> usize {
+    let u24 =
+        |i: usize| u32::from(data[i]) | u32::from(data[i + 1]) << 8 | u32::from(data[i + 2]) << 16;
+    let hash = |v: u32| (2_654_435_769_u32.wrapping_mul(v) >> 19) as usize & (HASH_TABLE_SIZE - 1);
+    let mut table = vec![0; HASH_TABLE_SIZE];
+
+    let mut len = 0;
+    // start of the bytes not yet emitted
+    let mut anchor = 0;
+    let limit = data.len().saturating_sub(13);
+    let mut ip = 2;
+    while ip < limit {
+        let mut reference;
+        loop {
+            let seq = u24(ip);
+            let h = hash(seq);
+            reference = table[h];
+            table[h] = ip;
+            let distance = ip - reference;
+            if ip >= limit {
+                break;
+            }
+            ip += 1;
+            if distance <= MAX_DISTANCE && seq == u24(reference) {
+                break;
+            }
+        }
+        if ip >= limit {
+            break;
+        }
+        ip -= 1;
+        if ip > anchor {
+            len += literals_len(ip - anchor);
+        }
+
+        // the matching bytes after the first 3, plus 1 when the match ends before the bound
+        let bound = limit + 9 - (ip + 3);
+        let mut matched = 0;
+        while matched < bound {
+            let same = data[reference + 3 + matched] == data[ip + 3 + matched];
+            matched += 1;
+            if !same {
+                break;
+            }
+        }
+        len += match_len(matched);
+
+        ip += matched;
+        for _ in 0..2 {
+            table[hash(u24(ip))] = ip;
+            ip += 1;
+        }
+        anchor = ip;
+    }
+    len + literals_len(data.len() - anchor)
+}
+
+/// L1 data fee of the OP-stack chains since Fjord, in wei: the Ecotone scalars price an L1 size
+/// estimated from the FastLZ size of the transaction. Fees are in wei and scalars are the raw
+/// values of the `GasPriceOracle`, e.g. `base_fee_scalar: 2269` for `0.002269`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpStackCostModel {
+    pub l1_base_fee: u64,
+    pub blob_base_fee: u64,
+    pub base_fee_scalar: u32,
+    pub blob_scalar: u32,
+}
+
+impl OpStackCostModel {
+    /// Estimated L1 size of a transaction of `fastlz_size` compressed bytes, scaled by 1e6
+    pub fn estimated_size(fastlz_size: usize) -> u64 {
+        let size = L1_COST_INTERCEPT + L1_COST_FASTLZ_COEF * fastlz_size as i64;
+        size.max(MIN_TRANSACTION_SIZE * SIZE_SCALE) as u64
+    }
+
+    /// L1 data fee of a transaction sending `calldata`, its signature included. The rest of
+    /// the transaction is left out, it's the same whether the calldata is compressed or not.
+    pub fn l1_fee(&self, calldata: &[u8]) -> u64 {
+        let size = Self::estimated_size(flz_compress_len(calldata) + SIGNATURE_OVERHEAD);
+        let fee_scaled = 16 * u128::from(self.base_fee_scalar) * u128::from(self.l1_base_fee)
+            + u128::from(self.blob_scalar) * u128::from(self.blob_base_fee);
+        let fee = u128::from(size) * fee_scaled / FEE_SCALE;
+        fee.try_into().unwrap_or(u64::MAX)
+    }
+}
+
+impl CostModel for OpStackCostModel {
+    // FastLZ compresses whole transactions, the fee of a part is only a rough figure for
+    // `CompressObjective::Gas`
+    fn data_cost(&self, bytes: &[u8]) -> u64 {
+        self.l1_fee(bytes)
+    }
+
+    // the decompression is L2 execution, paid in L2 gas apart from the L1 fee
+    fn transaction_cost(&self, calldata: &[u8], _decompression: Option<u64>) -> u64 {
+        self.l1_fee(calldata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::compressor::{compress, Bytes32};
+
+    // Base mainnet figures, a 10 gwei L1 base fee and a 1 wei blob base fee
+    const MODEL: OpStackCostModel = OpStackCostModel {
+        l1_base_fee: 10_000_000_000,
+        blob_base_fee: 1,
+        base_fee_scalar: 2269,
+        blob_scalar: 1_055_762,
+    };
+
+    #[test]
+    fn test_flz_compress_len() {
+        // too short for a match, a single literal instruction
+        assert_eq!(flz_compress_len(&[]), 0);
+        assert_eq!(flz_compress_len(&[0x42; 13]), 14);
+        // literal runs are split every 32 bytes
+        let random: Vec<u8> = (0..64_u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        assert_eq!(flz_compress_len(&random), 66);
+
+        // 2 literals, then a match of the rest but the last 5 bytes, split across 4 instructions
+        assert_eq!(flz_compress_len(&[0x00; 1000]), 3 + 4 * 3 + 6);
+        let word = [[0x00; 12].as_slice(), &random[..20]].concat();
+        let words = flz_compress_len(&word.repeat(10));
+        assert!(words < 2 * word.len(), "{words}");
+    }
+
+    #[test]
+    fn test_op_stack_cost_model() {
+        // small transactions are charged the minimum size
+        assert_eq!(OpStackCostModel::estimated_size(0), 100_000_000);
+        assert_eq!(
+            OpStackCostModel::estimated_size(1000),
+            836_500_000 - 42_585_600
+        );
+        let fee = MODEL.l1_fee(&[0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(
+            fee,
+            100 * (16 * 2269 * 10_000_000_000 + 1_055_762) / 1_000_000
+        );
+        assert_eq!(OpStackCostModel::default().l1_fee(&[0x42; 1000]), 0);
+
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("test-data/big_calldata.json").unwrap())
+                .unwrap();
+        let data = Bytes::from_str(fixture["uncompress"].as_str().unwrap()).unwrap();
+        let result = compress(data.clone(), Bytes32::default(), Bytes32::default(), &[]).unwrap();
+        let report = result.evaluate(&MODEL);
+        assert_eq!(report.before, MODEL.l1_fee(&data));
+        assert_eq!(report.after, MODEL.l1_fee(&result.compressed_data));
+        assert_eq!(
+            (
+                flz_compress_len(&data),
+                flz_compress_len(&result.compressed_data)
+            ),
+            (10207, 6592)
+        );
+        // FastLZ finds less to squeeze out of the compressed calldata, which still pays
+        assert_eq!(
+            (report.before, report.after),
+            (3_104_882_146_805, 2_007_068_243_212)
+        );
+        // the decompressor runs on L2, its gas isn't part of the L1 fee
+        assert!(result.is_worth_it(&MODEL, u64::MAX / 2));
+    }
+}
@@ -0,0 +1,508 @@
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::WORD_SIZE,
+    cost::CostModel,
+    detector::Detector,
+    dict::LOOKUP_LENGTHS,
+    planner::{GreedyPlanner, Planner},
+    HashSet,
+};
+
+// thresholds of `CompressOptions::auto`, measured with release builds on 1 KB to 1 MB payloads
+// of random bytes, abi words of small integers and mostly zeros:
+// - the optimal planner takes about 1.7 ms per KB, the greedy one about 0.01 ms
+// - both produce the same output on random bytes
+// - on zero-heavy payloads the greedy plan is 3 to 7 times bigger, and the optimal planner
+//   takes half the time on a 32-byte lookback for 0.03% more output
+// - planning 64 KB at a time adds 0.02% to the output and 10% to the time
+// - the 4-byte lookups take 18% of the dictionary analysis of random bytes
+const TINY_LEN: usize = 1024; // the optimal planner takes a few milliseconds at most
+const LARGE_LEN: usize = 16 * 1024; // the optimal planner takes over 25 ms
+const MAX_DP_LEN: usize = 256 * 1024; // over 250 ms, even on a 32-byte lookback
+const ZERO_HEAVY_FRACTION: f64 = 0.5;
+const INCOMPRESSIBLE_ZERO_FRACTION: f64 = 1.0 / 32.0;
+const ZERO_HEAVY_LOOKBACK: usize = 32;
+const DP_CHUNK_LEN: usize = 64 * 1024;
+const FAST_DICT_SCAN_DEPTH: usize = 3; // the lookups of whole, 31-byte and address suffixes
+
+/// Shape of a calldata, inspected by `CompressOptions::auto`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CalldataStats {
+    pub len: usize,
+    pub zero_bytes: usize,
+    pub words: usize,          // abi words, after the selector if there is one
+    pub distinct_words: usize, // words occurring once or more
+}
+
+impl CalldataStats {
+    pub fn new(data: &[u8]) -> Self {
+        let words: Vec<&[u8]> = data[data.len() % WORD_SIZE..]
+            .chunks_exact(WORD_SIZE)
+            .collect();
+        Self {
+            len: data.len(),
+            zero_bytes: data.iter().filter(|&&b| b == 0x00).count(),
+            words: words.len(),
+            distinct_words: words.iter().copied().collect::<HashSet<_>>().len(),
+        }
+    }
+
+    pub fn zero_fraction(&self) -> f64 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        self.zero_bytes as f64 / self.len as f64
+    }
+}
+
+/// Class of input `CompressOptions::auto` picked the options for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompressProfile {
+    Tiny,           // up to 1 KB: the optimal planner
+    ZeroHeavy,      // at least half zeros, up to 256 KB: the optimal planner, 64 KB at a time
+    Incompressible, // few zeros and no repeated word: the greedy planner, the DP saves little
+    Large,          // over 16 KB: the greedy planner
+    Standard,       // anything else: the optimal planner
+}
+
+impl CompressProfile {
+    pub fn select(stats: &CalldataStats) -> Self {
+        let zero_fraction = stats.zero_fraction();
+        if stats.len <= TINY_LEN {
+            Self::Tiny
+        } else if zero_fraction >= ZERO_HEAVY_FRACTION && stats.len <= MAX_DP_LEN {
+            Self::ZeroHeavy
+        } else if zero_fraction < INCOMPRESSIBLE_ZERO_FRACTION
+            && stats.distinct_words == stats.words
+        {
+            Self::Incompressible
+        } else if stats.len > LARGE_LEN {
+            Self::Large
+        } else {
+            Self::Standard
+        }
+    }
+
+    // the planner of `CompressOptions::planner`, `None` being the optimal one
+    fn planner(self) -> Option<Arc<dyn Planner>> {
+        match self {
+            Self::Tiny | Self::ZeroHeavy | Self::Standard => None,
+            Self::Incompressible | Self::Large => Some(Arc::new(GreedyPlanner)),
+        }
+    }
+
+    fn max_lookback(self) -> usize {
+        match self {
+            Self::ZeroHeavy => ZERO_HEAVY_LOOKBACK,
+            _ => DEFAULT_MAX_LOOKBACK,
+        }
+    }
+
+    fn chunk_len(self) -> Option<usize> {
+        match self {
+            Self::ZeroHeavy => Some(DP_CHUNK_LEN),
+            _ => None,
+        }
+    }
+
+    // the greedy planner is quick enough for the 4-byte lookups to be a large part of the work
+    fn dict_scan_depth(self) -> usize {
+        match self {
+            Self::Incompressible | Self::Large => FAST_DICT_SCAN_DEPTH,
+            _ => LOOKUP_LENGTHS.len(),
+        }
+    }
+}
+
+/// Planner of `Calldata::compress_with_strategy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CompressionStrategy {
+    // `OptimalDpPlanner`, usually the smallest output but not always, see its docs
+    #[default]
+    Optimal,
+    // `GreedyPlanner`, a single pass for latency-sensitive callers whose output may be bigger
+    Fast,
+}
+
+impl CompressionStrategy {
+    // the planner of `CompressOptions::planner`, `None` being the optimal one
+    fn planner(self) -> Option<Arc<dyn Planner>> {
+        match self {
+            Self::Optimal => None,
+            Self::Fast => Some(Arc::new(GreedyPlanner)),
+        }
+    }
+}
+
+/// What the planner minimizes when choosing between instruction sequences
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompressObjective {
+    // size of the compressed instruction stream in bytes
+    #[default]
+    Bytes,
+    // cost of the instruction stream under `CompressOptions::cost_model`, by default its
+    // calldata gas: 4 per zero byte and 16 per non-zero byte. The planner then prefers
+    // instructions emitting zero bytes, e.g. single-byte zero runs.
+    Gas,
+}
+
+/// how far back from a byte the optimal planner starts the parts ending at it, see
+/// `CompressOptions::max_lookback`
+pub const DEFAULT_MAX_LOOKBACK: usize = 63;
+
+/// Options of `Calldata::compress_with` and `decompress_with`
+#[derive(Clone)]
+pub struct CompressOptions {
+    // append a checksum of the original calldata to the compressed output, see `checksum`
+    pub checksum: bool,
+    // emit the instruction stream back to front for decompressors reading from the end,
+    // see `decompress_reverse`
+    pub reverse: bool,
+    pub objective: CompressObjective,
+    // pricing of `CompressObjective::Gas`, `MainnetCalldata` if unset
+    pub cost_model: Option<Arc<dyn CostModel>>,
+    // fail with `CompressorError::NoCompression` unless the output is strictly smaller than the
+    // input, instead of returning inflated calldata
+    pub forbid_inflation: bool,
+    // detectors run after the built-in ones, see `Detector`
+    pub extra_detectors: Vec<Arc<dyn Detector>>,
+    // planner of `Calldata::compress_with`, `OptimalDpPlanner` if unset
+    pub planner: Option<Arc<dyn Planner>>,
+    // secondary objective after size: replace the storage references that zero runs and copies
+    // encode in as many bytes, every reference costing the decompressor an `SLOAD`, and point
+    // the others at an index the payload already read when one holds the same bytes
+    pub gas_golf: bool,
+    // the profile `CompressOptions::auto` picked, reported in `CompressResult::profile`
+    pub profile: Option<CompressProfile>,
+    // methods the planners may use, a disabled method is left out of the analysis rather than
+    // dropped from the plan: `0x00` zero runs, `0x01` copies of a word with its leading zeros
+    // stripped, `0x10` and `0x11` references. References to small indices fall back to `0x11`
+    // without `0x10`.
+    pub enable_zero: bool,
+    pub enable_copy_padding: bool,
+    pub enable_dict_short: bool,
+    pub enable_dict_long: bool,
+    // bytes before the end of a part the optimal planner may start it, the default covers every
+    // instruction. Smaller values trade plan size for speed.
+    pub max_lookback: usize,
+    // decompress the output against the dictionary it was compressed with before returning it,
+    // failing with `CompressorError::RoundtripMismatch` if it doesn't give the input back
+    pub verify_roundtrip: bool,
+    // plan the data this many bytes at a time, each chunk analysed and planned on its own: the
+    // memory of the optimal planner then grows with the chunk rather than with the data, for
+    // the few instructions the chunk borders cut
+    pub chunk_len: Option<usize>,
+    // how many of the `LOOKUP_LENGTHS` suffixes, longest first, the analysis looks up in the
+    // dictionary at every byte. The default looks them all up, 3 skips the 4-byte suffixes.
+    pub dict_scan_depth: usize,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self {
+            checksum: false,
+            reverse: false,
+            objective: CompressObjective::default(),
+            cost_model: None,
+            forbid_inflation: false,
+            extra_detectors: Vec::new(),
+            planner: None,
+            gas_golf: false,
+            profile: None,
+            enable_zero: true,
+            enable_copy_padding: true,
+            enable_dict_short: true,
+            enable_dict_long: true,
+            max_lookback: DEFAULT_MAX_LOOKBACK,
+            verify_roundtrip: false,
+            chunk_len: None,
+            dict_scan_depth: LOOKUP_LENGTHS.len(),
+        }
+    }
+}
+
+impl CompressOptions {
+    /// Default options with the planner, lookback, chunking and dictionary lookups picked from
+    /// the shape of `data`, see `CompressProfile`. The choice only depends on `data`, so
+    /// compressing it again with the same options gives the same output.
+    pub fn auto(data: &[u8]) -> Self {
+        let profile = CompressProfile::select(&CalldataStats::new(data));
+        Self {
+            planner: profile.planner(),
+            max_lookback: profile.max_lookback(),
+            chunk_len: profile.chunk_len(),
+            dict_scan_depth: profile.dict_scan_depth(),
+            profile: Some(profile),
+            ..Self::default()
+        }
+    }
+
+    /// Default options compressing with the planner of `strategy`
+    pub fn strategy(strategy: CompressionStrategy) -> Self {
+        Self {
+            planner: strategy.planner(),
+            ..Self::default()
+        }
+    }
+
+    // whether a method is disabled, the analysis must then be filtered before planning
+    pub(crate) fn restricts_methods(&self) -> bool {
+        !(self.enable_zero
+            && self.enable_copy_padding
+            && self.enable_dict_short
+            && self.enable_dict_long)
+    }
+
+    pub fn extra_detectors(mut self, detectors: Vec<Box<dyn Detector>>) -> Self {
+        self.extra_detectors
+            .extend(detectors.into_iter().map(Arc::from));
+        self
+    }
+}
+
+impl fmt::Debug for CompressOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressOptions")
+            .field("checksum", &self.checksum)
+            .field("reverse", &self.reverse)
+            .field("objective", &self.objective)
+            .field("cost_model", &self.cost_model)
+            .field("forbid_inflation", &self.forbid_inflation)
+            .field("extra_detectors", &self.extra_detectors)
+            .field("planner", &self.planner)
+            .field("gas_golf", &self.gas_golf)
+            .field("profile", &self.profile)
+            .field("enable_zero", &self.enable_zero)
+            .field("enable_copy_padding", &self.enable_copy_padding)
+            .field("enable_dict_short", &self.enable_dict_short)
+            .field("enable_dict_long", &self.enable_dict_long)
+            .field("max_lookback", &self.max_lookback)
+            .field("verify_roundtrip", &self.verify_roundtrip)
+            .field("chunk_len", &self.chunk_len)
+            .field("dict_scan_depth", &self.dict_scan_depth)
+            .finish()
+    }
+}
+
+// detectors, planners and cost models compare by identity, options holding clones of the same
+// ones are equal
+impl PartialEq for CompressOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.checksum == other.checksum
+            && self.reverse == other.reverse
+            && self.objective == other.objective
+            && self.forbid_inflation == other.forbid_inflation
+            && self.gas_golf == other.gas_golf
+            && self.profile == other.profile
+            && self.enable_zero == other.enable_zero
+            && self.enable_copy_padding == other.enable_copy_padding
+            && self.enable_dict_short == other.enable_dict_short
+            && self.enable_dict_long == other.enable_dict_long
+            && self.max_lookback == other.max_lookback
+            && self.verify_roundtrip == other.verify_roundtrip
+            && self.chunk_len == other.chunk_len
+            && self.dict_scan_depth == other.dict_scan_depth
+            && self.extra_detectors.len() == other.extra_detectors.len()
+            && self
+                .extra_detectors
+                .iter()
+                .zip(&other.extra_detectors)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+            && match (&self.planner, &other.planner) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+            && match (&self.cost_model, &other.cost_model) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+}
+
+impl Eq for CompressOptions {}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{decompress_with, Calldata, CompressResult},
+        dict::Dict,
+    };
+
+    fn compress_auto(data: &[u8]) -> CompressResult {
+        let mut cb = Calldata::new(Bytes::from(data.to_vec()), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[]);
+        cb.compress_with(&CompressOptions::auto(data)).unwrap()
+    }
+
+    #[test]
+    fn test_auto_profile() {
+        let tiny = [[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0x00; 31], &[0x01]].concat();
+        assert_eq!(
+            CompressOptions::auto(&tiny).profile,
+            Some(CompressProfile::Tiny)
+        );
+        let result = compress_auto(&tiny);
+        assert_eq!(result.profile, Some(CompressProfile::Tiny));
+        assert_eq!(
+            decompress_with(
+                &result.compressed_data,
+                &Dict::new([0xaa; 32], [0xbb; 32], &[]),
+                &CompressOptions::default()
+            ),
+            Ok(Bytes::from(tiny))
+        );
+
+        let mut zeros = vec![0x00; 1 << 20];
+        for i in (31..zeros.len()).step_by(97) {
+            zeros[i] = 0x01;
+        }
+        let stats = CalldataStats::new(&zeros);
+        assert_eq!(stats.words, 1 << 15);
+        assert!(stats.zero_fraction() > 0.98);
+        // past `MAX_DP_LEN` the optimal planner is too slow, zeros or not
+        assert_eq!(CompressProfile::select(&stats), CompressProfile::Large);
+        zeros.truncate(MAX_DP_LEN);
+        assert_eq!(
+            CompressProfile::select(&CalldataStats::new(&zeros)),
+            CompressProfile::ZeroHeavy
+        );
+        assert!(CompressOptions::auto(&zeros).planner.is_none());
+
+        let mut seed = 0x2545_f491_u32;
+        let random: Vec<u8> = (0..4096)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed >> 8) as u8 | 1
+            })
+            .collect();
+        let result = compress_auto(&random);
+        assert_eq!(result.profile, Some(CompressProfile::Incompressible));
+        // the selection is deterministic
+        assert_eq!(
+            compress_auto(&random).compressed_data,
+            result.compressed_data
+        );
+
+        let mut large = random.repeat(6);
+        large.extend(vec![0x00; 4096]);
+        assert_eq!(
+            CompressProfile::select(&CalldataStats::new(&large)),
+            CompressProfile::Large
+        );
+    }
+
+    #[test]
+    fn test_auto_options() {
+        let mut seed = 0x2545_f491_u32;
+        let random: Vec<u8> = (0..4096)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed >> 8) as u8 | 1
+            })
+            .collect();
+        let mut zeros = vec![0x00; 4 * DP_CHUNK_LEN];
+        zeros[31] = 0x01;
+        for (data, profile, greedy, max_lookback, chunk_len, dict_scan_depth) in [
+            (vec![0x01; 100], CompressProfile::Tiny, false, 63, None, 4),
+            (
+                zeros,
+                CompressProfile::ZeroHeavy,
+                false,
+                32,
+                Some(64 * 1024),
+                4,
+            ),
+            (
+                random.clone(),
+                CompressProfile::Incompressible,
+                true,
+                63,
+                None,
+                3,
+            ),
+            (random.repeat(5), CompressProfile::Large, true, 63, None, 3),
+            (
+                random.repeat(2),
+                CompressProfile::Standard,
+                false,
+                63,
+                None,
+                4,
+            ),
+        ] {
+            let opts = CompressOptions::auto(&data);
+            assert_eq!(opts.profile, Some(profile));
+            assert_eq!(opts.planner.is_some(), greedy, "{profile:?}");
+            assert_eq!(
+                (opts.max_lookback, opts.chunk_len, opts.dict_scan_depth),
+                (max_lookback, chunk_len, dict_scan_depth),
+                "{profile:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunk_len() {
+        let data = [[0x00; 40].as_slice(), &[0x11; 30], &[0x42; 32]]
+            .concat()
+            .repeat(50);
+        let dict = [[0x42; 32]];
+        let mut cb = Calldata::new(Bytes::from(data.clone()), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&dict);
+        let whole = cb.compress().unwrap();
+        for chunk_len in [1, 100, 1000] {
+            let opts = CompressOptions {
+                chunk_len: Some(chunk_len),
+                ..CompressOptions::default()
+            };
+            let result = cb.compress_with(&opts).unwrap();
+            // no instruction straddles a chunk border
+            assert!(result.description.iter().all(|d| {
+                d.start_byte / chunk_len == (d.start_byte + d.amount_bytes - 1) / chunk_len
+            }));
+            assert!(result.compressed_data.len() >= whole.compressed_data.len());
+            assert_eq!(
+                decompress_with(
+                    &result.compressed_data,
+                    &Dict::new([0xaa; 32], [0xbb; 32], &dict),
+                    &opts
+                ),
+                Ok(Bytes::from(data.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_dict_scan_depth() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let mut entry = [0x00; 32];
+        entry[28..].copy_from_slice(&selector);
+        let data = [selector.as_slice(), &[0x42; 32]].concat();
+        let mut cb = Calldata::new(Bytes::from(data), [0xaa; 32], [0xbb; 32]).unwrap();
+        cb.init_dict(&[entry]);
+        let reads_selector = |opts: &CompressOptions| {
+            cb.clone().compress_with(opts).unwrap().description[0]
+                .method
+                .is_storage()
+        };
+        assert!(reads_selector(&CompressOptions::default()));
+        assert!(!reads_selector(&CompressOptions {
+            dict_scan_depth: FAST_DICT_SCAN_DEPTH,
+            ..CompressOptions::default()
+        }));
+    }
+}
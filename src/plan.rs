@@ -0,0 +1,887 @@
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+use core::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compressor::{
+        instruction_size, restrict_methods, storage_index, Calldata, CompressDataDescription,
+        CompressDataPower, CompressResult, Method,
+    },
+    corpus::MethodTotals,
+    dict::{Dict, LOOKUP_LENGTHS},
+    errors::CompressorError,
+    instruction::MAX_SHORT_INDEX,
+    options::CompressOptions,
+    HashSet,
+};
+
+/// longest zero run a single `0x00` instruction encodes
+pub const MAX_ZERO_RUN: usize = 64;
+/// most bytes a single `0x01` copy expands to, a padded copy included
+pub const MAX_COPY: usize = 32;
+
+/// Checks that `descriptions` tile the `data_len` bytes of the data in order, from the first
+/// to the last with no gap or overlap, and that each of them fits one instruction of its
+/// method: a zero run of at most `MAX_ZERO_RUN` bytes, a copy of at most `MAX_COPY` bytes or a
+/// storage reference to one of the `LOOKUP_LENGTHS` suffixes. `Method` only has the encodable
+/// methods, unknown ones are rejected when a plan is parsed, see `Method::from_name`.
+/// `InvalidPlan` carries the index of the first offending description, or the number of
+/// descriptions when they stop short of the end of the data.
+pub fn validate_descriptions(
+    data_len: usize,
+    descriptions: &[CompressDataDescription],
+) -> Result<(), CompressorError> {
+    let mut next_byte = 0;
+    for (i, description) in descriptions.iter().enumerate() {
+        let fits = match description.method {
+            Method::Zeros => description.amount_bytes <= MAX_ZERO_RUN,
+            Method::Copy => description.amount_bytes <= MAX_COPY,
+            Method::DictShort | Method::DictLong => {
+                LOOKUP_LENGTHS.contains(&description.amount_bytes)
+            }
+        };
+        if description.start_byte != next_byte || description.amount_bytes == 0 || !fits {
+            return Err(CompressorError::InvalidPlan(i));
+        }
+        next_byte += description.amount_bytes;
+    }
+    if next_byte != data_len {
+        return Err(CompressorError::InvalidPlan(descriptions.len()));
+    }
+    Ok(())
+}
+
+/// How `validate_plan` treats plans that are valid but needlessly large
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Canonicality {
+    #[default]
+    Ignore, // only check that the plan tiles the data
+    Warn,   // report avoidable inefficiencies as warnings
+    Reject, // fail on the first avoidable inefficiency
+}
+
+/// An avoidable inefficiency of a plan, carrying the index of the offending description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanWarning {
+    // a zero run directly following another one that both fit a single `0x00` instruction
+    MergeableZeroRuns(usize),
+    // a `0x01` copy whose bytes are all zero, a zero run is always smaller
+    ZeroCopy(usize),
+    // a 3-byte `0x11` storage reference to a value also stored at an index within the 2-byte range
+    FarDictReference(usize),
+}
+
+impl core::fmt::Display for PlanWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MergeableZeroRuns(i) => write!(f, "description {i} extends a mergeable zero run"),
+            Self::ZeroCopy(i) => write!(f, "description {i} copies only zero bytes"),
+            Self::FarDictReference(i) => {
+                write!(
+                    f,
+                    "description {i} uses a 3-byte reference to a 2-byte reachable entry"
+                )
+            }
+        }
+    }
+}
+
+/// Whether a plan made against the current dictionary of a `Calldata` still holds for another one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevalidationReport {
+    // every storage reference resolves to the same index
+    Valid,
+    // every referenced value is still in the dictionary but some moved, the plan is updated to
+    // the storage method their new index fits
+    Remapped(Vec<CompressDataDescription>),
+    // indices of the descriptions whose value is no longer in the dictionary, or whose length it
+    // can't be referenced with
+    Broken(Vec<usize>),
+}
+
+/// Shape of the instruction stream of a compressed payload, see `CompressResult::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressStats {
+    pub instructions: usize,
+    pub storage_references: usize, // instructions reading the dictionary with an `SLOAD`
+    pub cold_references: usize,    // references to an index not read before in the payload
+    pub warm_references: usize,    // references repeating an index already read
+}
+
+/// One description of a plan with the bytes it covers and the instruction encoding them, see
+/// `CompressResult::segments`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub range: Range<usize>, // bytes of the original data covered
+    pub method: Method,
+    pub original: &'a [u8],
+    pub compressed: Vec<u8>, // the instruction, in the regular order of the stream
+    pub saved: i64,          // `original` bytes minus `compressed` bytes
+}
+
+/// The descriptions of a plan rendered as a table, one line per instruction with its first
+/// byte, its length, its method and the hex of the bytes it covers, e.g.
+/// `    36   32 copy      0x00..2a`. Bytes are written straight to the formatter, so nothing is
+/// allocated until the table is formatted, see `CompressResult::table`.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionTable<'a> {
+    data: &'a [u8],
+    descriptions: &'a [CompressDataDescription],
+}
+
+impl<'a> InstructionTable<'a> {
+    /// Bytes of descriptions running past `data` are left out of their hex
+    pub fn new(data: &'a [u8], descriptions: &'a [CompressDataDescription]) -> Self {
+        Self { data, descriptions }
+    }
+}
+
+impl core::fmt::Display for InstructionTable<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for d in self.descriptions {
+            write!(
+                f,
+                "{:>6} {:>4} {:<9} 0x",
+                d.start_byte,
+                d.amount_bytes,
+                d.method.name()
+            )?;
+            let end = core::cmp::min(d.start_byte + d.amount_bytes, self.data.len());
+            for byte in &self.data[core::cmp::min(d.start_byte, end)..end] {
+                write!(f, "{byte:02x}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the planner kept the instruction of a `SegmentDecision` over the other candidates found
+/// at its first byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum DecisionReason {
+    Only,        // no other method applies at the offset
+    LargerRange, // it saves more bytes than every other candidate
+    TieBreak,    // another candidate saves as many bytes, the planner keeps the first it finds
+    // another candidate saves more on its own, but it runs past the data or takes bytes that the
+    // following instructions of the plan save more with
+    Plan,
+}
+
+/// An instruction of the plan `compress` picks, with the candidates the analysis found at its
+/// first byte, see `Calldata::explain`. Candidates that don't apply have a zero power.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SegmentDecision {
+    pub description: CompressDataDescription,
+    pub power: CompressDataPower, // of the instruction as `zip` encodes it
+    pub zero: CompressDataPower,  // of `check_zeros_case`
+    pub copy: CompressDataPower,  // of `check_copy_case_with_zeros`
+    pub storage: Vec<CompressDataPower>, // of `check_storage_case`, longest suffix first
+    pub reason: DecisionReason,
+}
+
+impl SegmentDecision {
+    // the candidates other than the one the chosen instruction comes from
+    fn alternatives(&self) -> impl Iterator<Item = &CompressDataPower> {
+        let method = self.description.method;
+        let chosen = &self.power;
+        let zero = (method != Method::Zeros).then_some(&self.zero);
+        let copy = (method != Method::Copy).then_some(&self.copy);
+        let storage = self.storage.iter().filter(move |power| {
+            !method.is_storage() || power.decompressed_size != chosen.decompressed_size
+        });
+        zero.into_iter()
+            .chain(copy)
+            .chain(storage)
+            .filter(|power| power.decompressed_size != 0)
+    }
+
+    fn decide(mut self) -> Self {
+        let range = self.power.range();
+        self.reason = match self.alternatives().map(CompressDataPower::range).max() {
+            None => DecisionReason::Only,
+            Some(best) if range > best => DecisionReason::LargerRange,
+            Some(best) if range == best => DecisionReason::TieBreak,
+            Some(_) => DecisionReason::Plan,
+        };
+        self
+    }
+}
+
+impl CompressResult {
+    /// The descriptions of the plan paired with their original bytes and their instruction.
+    /// Ranges tile the original data in order; the instructions, concatenated, make up the
+    /// instruction stream, which is `compressed_data` unless it's reversed or has a checksum.
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'_>> {
+        let stream = self.instruction_stream();
+        let mut offset = 0;
+        self.description.iter().map(move |description| {
+            let end = core::cmp::min(
+                description.start_byte + description.amount_bytes,
+                self.uncompressed_data.len(),
+            );
+            let range = core::cmp::min(description.start_byte, end)..end;
+            let size = instruction_size(&stream, offset);
+            let compressed = stream[offset..offset + size].to_vec();
+            offset += size;
+            Segment {
+                saved: range.len() as i64 - compressed.len() as i64,
+                method: description.method,
+                original: &self.uncompressed_data[range.clone()],
+                range,
+                compressed,
+            }
+        })
+    }
+
+    /// The plan as an `InstructionTable` of the calldata, for logs and bug reports
+    pub fn table(&self) -> InstructionTable<'_> {
+        InstructionTable::new(&self.uncompressed_data, &self.description)
+    }
+
+    /// Per method totals of the instructions as `zip` encoded them, a padded copy counting the
+    /// 32 bytes it expands to against the bytes it actually sends. `PlanSummary` and
+    /// `CorpusStats` are made of them.
+    pub fn method_stats(&self) -> BTreeMap<Method, MethodTotals> {
+        let mut methods: BTreeMap<Method, MethodTotals> = BTreeMap::new();
+        for segment in self.segments() {
+            let stats = methods.entry(segment.method).or_default();
+            stats.instructions += 1;
+            stats.decompressed_bytes += segment.range.len();
+            stats.compressed_bytes += segment.compressed.len();
+        }
+        methods
+    }
+
+    pub fn stats(&self) -> CompressStats {
+        let mut stats = CompressStats::default();
+        let mut read = HashSet::new();
+        for segment in self.segments() {
+            stats.instructions += 1;
+            if let Some(index) = storage_index(&segment.compressed, 0) {
+                stats.storage_references += 1;
+                if read.insert(index) {
+                    stats.cold_references += 1;
+                } else {
+                    stats.warm_references += 1;
+                }
+            }
+        }
+        stats
+    }
+}
+
+impl Calldata {
+    /// Analyses the data against the current dictionary and explains the plan `compress_with`
+    /// picks with `opts`: every instruction with the candidates found at its first byte and why
+    /// it won over them. A storage candidate missing from a decision is a value the dictionary
+    /// doesn't hold or a method `opts` disables. Fails where `compress_with` does.
+    pub fn explain(
+        &mut self,
+        opts: &CompressOptions,
+    ) -> Result<Vec<SegmentDecision>, CompressorError> {
+        self.analyse_data();
+        self.analyse_dict_for(opts)?;
+        let result = self.compress_analysed(opts)?;
+        // the candidates the planner was given
+        let analysis = if opts.restricts_methods() {
+            Cow::Owned(restrict_methods(&self.bytes_info, &self.data, opts))
+        } else {
+            Cow::Borrowed(self.bytes_info.as_slice())
+        };
+        Ok(result
+            .segments()
+            .map(|segment| {
+                let info = &analysis[segment.range.start];
+                SegmentDecision {
+                    description: CompressDataDescription::new(
+                        segment.range.start,
+                        segment.range.len(),
+                        segment.method,
+                    ),
+                    power: CompressDataPower::new(segment.range.len(), segment.compressed.len()),
+                    zero: info.zero_compress.clone(),
+                    copy: info.copy_compress.clone(),
+                    storage: info.storage_compress.clone(),
+                    reason: DecisionReason::Only,
+                }
+                .decide()
+            })
+            .collect())
+    }
+
+    /// Checks that the storage references of `plan`, made against the current dictionary, resolve
+    /// to the same bytes in `dict`, so that a cached plan can be reused after the dictionary
+    /// changed. Zero runs and copies don't depend on the dictionary and are left as is.
+    pub fn revalidate_plan(
+        &self,
+        plan: &[CompressDataDescription],
+        dict: &Dict,
+    ) -> RevalidationReport {
+        let mut remapped = plan.to_vec();
+        let mut moved = false;
+        let mut broken = Vec::new();
+        for (i, description) in plan.iter().enumerate() {
+            if !description.method.is_storage() {
+                continue;
+            }
+            let value = self
+                .get_bytes(description.start_byte, description.amount_bytes)
+                .unwrap_or_default();
+            let new_index = (value.len() == description.amount_bytes)
+                .then(|| dict.index_of(value))
+                .flatten();
+            let Some(new_index) = new_index else {
+                broken.push(i);
+                continue;
+            };
+            if self.dict_view().index_of(value) != Some(new_index) {
+                moved = true;
+            }
+            remapped[i].method = if new_index > MAX_SHORT_INDEX as usize {
+                Method::DictLong
+            } else {
+                Method::DictShort
+            };
+        }
+
+        if !broken.is_empty() {
+            RevalidationReport::Broken(broken)
+        } else if moved || remapped != plan {
+            RevalidationReport::Remapped(remapped)
+        } else {
+            RevalidationReport::Valid
+        }
+    }
+
+    /// Checks that `plan` tiles the data from the first to the last byte, see
+    /// `validate_descriptions`, then looks for avoidable inefficiencies according to
+    /// `canonicality`.
+    pub fn validate_plan(
+        &self,
+        plan: &[CompressDataDescription],
+        canonicality: Canonicality,
+    ) -> Result<Vec<PlanWarning>, CompressorError> {
+        validate_descriptions(self.data.len(), plan)?;
+
+        if canonicality == Canonicality::Ignore {
+            return Ok(Vec::new());
+        }
+
+        let mut warnings = Vec::new();
+        for (i, description) in plan.iter().enumerate() {
+            let bytes = self.get_bytes(description.start_byte, description.amount_bytes)?;
+            let warning = match description.method {
+                Method::Zeros
+                    if i > 0
+                        && plan[i - 1].method == Method::Zeros
+                        && plan[i - 1].amount_bytes + description.amount_bytes <= 64 =>
+                {
+                    Some(PlanWarning::MergeableZeroRuns(i))
+                }
+                Method::Copy if bytes.iter().all(|&b| b == 0x00) => Some(PlanWarning::ZeroCopy(i)),
+                Method::DictLong
+                    if (0..=MAX_SHORT_INDEX as usize)
+                        .map_while(|index| self.dict_view().get(index).copied())
+                        .any(|entry| entry.ends_with(bytes)) =>
+                {
+                    Some(PlanWarning::FarDictReference(i))
+                }
+                _ => None,
+            };
+            if let Some(warning) = warning {
+                if canonicality == Canonicality::Reject {
+                    return Err(CompressorError::NonCanonicalPlan(warning));
+                }
+                warnings.push(warning);
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Encodes a caller supplied plan after validating it, see `validate_plan`.
+    /// Returns the compressed bytes along with the warnings raised in `Canonicality::Warn` mode.
+    pub fn apply_plan(
+        &self,
+        plan: &[CompressDataDescription],
+        canonicality: Canonicality,
+    ) -> Result<(Vec<u8>, Vec<PlanWarning>), CompressorError> {
+        let warnings = self.validate_plan(plan, canonicality)?;
+        Ok((self.zip(plan)?, warnings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{compress, decompress_with, tests::SMALL_CALLDATA, ByteInfo, Bytes32},
+        planner::Planner,
+        utils::assert_snapshot,
+    };
+
+    fn calldata(data: Vec<u8>, dict: &[Bytes32]) -> Calldata {
+        let mut calldata = Calldata::new(Bytes::from(data), [0xaa; 32], [0xbb; 32]).unwrap();
+        calldata.init_dict(dict);
+        calldata
+    }
+
+    fn desc(start_byte: usize, amount_bytes: usize, method: Method) -> CompressDataDescription {
+        CompressDataDescription::new(start_byte, amount_bytes, method)
+    }
+
+    #[test]
+    fn test_validate_plan_tiling() {
+        let cb = calldata(vec![0x00, 0x00, 0x01], &[]);
+        assert_eq!(
+            cb.validate_plan(
+                &[desc(0, 2, Method::Zeros), desc(2, 1, Method::Copy)],
+                Canonicality::Reject
+            ),
+            Ok(vec![])
+        );
+        // gap
+        assert_eq!(
+            cb.validate_plan(
+                &[desc(0, 1, Method::Zeros), desc(2, 1, Method::Copy)],
+                Canonicality::Ignore
+            ),
+            Err(CompressorError::InvalidPlan(1))
+        );
+        // short
+        assert_eq!(
+            cb.validate_plan(&[desc(0, 2, Method::Zeros)], Canonicality::Ignore),
+            Err(CompressorError::InvalidPlan(1))
+        );
+    }
+
+    #[test]
+    fn test_mergeable_zero_runs() {
+        let cb = calldata(vec![0x00; 10], &[]);
+        let plan = [desc(0, 4, Method::Zeros), desc(4, 6, Method::Zeros)];
+        assert_eq!(
+            cb.apply_plan(&plan, Canonicality::Warn),
+            Ok((vec![0x03, 0x05], vec![PlanWarning::MergeableZeroRuns(1)]))
+        );
+        assert_eq!(
+            cb.apply_plan(&plan, Canonicality::Reject),
+            Err(CompressorError::NonCanonicalPlan(
+                PlanWarning::MergeableZeroRuns(1)
+            ))
+        );
+        // 64 + 6 zeros can't be a single run
+        let cb = calldata(vec![0x00; 70], &[]);
+        assert_eq!(
+            cb.validate_plan(
+                &[desc(0, 64, Method::Zeros), desc(64, 6, Method::Zeros)],
+                Canonicality::Reject
+            ),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_zero_copy() {
+        let cb = calldata(vec![0x01, 0x00, 0x00], &[]);
+        let plan = [desc(0, 1, Method::Copy), desc(1, 2, Method::Copy)];
+        assert_eq!(
+            cb.validate_plan(&plan, Canonicality::Warn),
+            Ok(vec![PlanWarning::ZeroCopy(1)])
+        );
+        assert_eq!(
+            cb.validate_plan(&plan, Canonicality::Reject),
+            Err(CompressorError::NonCanonicalPlan(PlanWarning::ZeroCopy(1)))
+        );
+    }
+
+    #[test]
+    fn test_revalidate_plan() {
+        let token: Bytes32 = [0x11; 32];
+        let router: Bytes32 = [0x22; 32];
+        let mut cb = calldata(
+            [[0x01].as_slice(), &token, &router].concat(),
+            &[token, router],
+        );
+        let plan = cb.compress().unwrap().description;
+        assert_eq!(
+            plan,
+            vec![
+                desc(0, 1, Method::Copy),
+                desc(1, 32, Method::DictShort),
+                desc(33, 32, Method::DictShort)
+            ]
+        );
+        let dict = |entries: &[Bytes32]| Dict::new([0xaa; 32], [0xbb; 32], entries);
+
+        // new entries appended after the referenced ones
+        let grown = dict(&[token, router, [0x33; 32]]);
+        assert_eq!(cb.revalidate_plan(&plan, &grown), RevalidationReport::Valid);
+
+        // the router moves to an index beyond the 2-byte range
+        let mut entries = vec![[0x00; 32]; 5000];
+        entries[0] = token;
+        entries.push(router);
+        let moved = dict(&entries);
+        let RevalidationReport::Remapped(remapped) = cb.revalidate_plan(&plan, &moved) else {
+            panic!("router not remapped");
+        };
+        assert_eq!(
+            remapped,
+            vec![
+                desc(0, 1, Method::Copy),
+                desc(1, 32, Method::DictShort),
+                desc(33, 32, Method::DictLong)
+            ]
+        );
+        cb.set_dict(&moved);
+        let (compressed, _) = cb.apply_plan(&remapped, Canonicality::Reject).unwrap();
+        assert_eq!(
+            decompress_with(&compressed, &moved, &CompressOptions::default()),
+            Ok(cb.data().clone())
+        );
+
+        // swapped entries decode fine through the new indices, the plan itself is unchanged
+        cb.set_dict(&dict(&[token, router]));
+        assert_eq!(
+            cb.revalidate_plan(&plan, &dict(&[router, token])),
+            RevalidationReport::Remapped(plan.clone())
+        );
+
+        assert_eq!(
+            cb.revalidate_plan(&plan, &dict(&[router])),
+            RevalidationReport::Broken(vec![1])
+        );
+    }
+
+    #[test]
+    fn test_far_dict_reference() {
+        let entry: Bytes32 = [0x42; 32];
+        let cb = calldata(entry.to_vec(), &[entry]);
+        assert_eq!(
+            cb.validate_plan(&[desc(0, 32, Method::DictShort)], Canonicality::Reject),
+            Ok(vec![])
+        );
+        assert_eq!(
+            cb.validate_plan(&[desc(0, 32, Method::DictLong)], Canonicality::Warn),
+            Ok(vec![PlanWarning::FarDictReference(0)])
+        );
+        assert_eq!(
+            cb.apply_plan(&[desc(0, 32, Method::DictLong)], Canonicality::Reject),
+            Err(CompressorError::NonCanonicalPlan(
+                PlanWarning::FarDictReference(0)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_compress_stats() {
+        let word: Bytes32 = [0x42; 32];
+        let mut cb = calldata([[0x12].as_slice(), &word, &word].concat(), &[word]);
+        let result = cb.compress().unwrap();
+        assert_eq!(
+            result.stats(),
+            CompressStats {
+                instructions: 3,
+                storage_references: 2,
+                cold_references: 1,
+                warm_references: 1,
+            }
+        );
+        // the checksum trailer and the reversed order don't change the stream
+        let opts = CompressOptions {
+            checksum: true,
+            reverse: true,
+            ..CompressOptions::default()
+        };
+        assert_eq!(cb.compress_with(&opts).unwrap().stats(), result.stats());
+    }
+
+    #[test]
+    fn test_segments() {
+        for path in ["test-data/calldata.json", "test-data/big_calldata.json"] {
+            let fixture: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            let data = Bytes::from_str(fixture["uncompress"].as_str().unwrap()).unwrap();
+            let result = compress(data.clone(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+
+            let segments: Vec<_> = result.segments().collect();
+            assert_eq!(segments.len(), result.description.len());
+            let mut end = 0;
+            for segment in &segments {
+                assert_eq!(segment.range.start, end, "{path}");
+                end = segment.range.end;
+                assert_eq!(segment.original, &data[segment.range.clone()]);
+            }
+            assert_eq!(end, data.len());
+            assert_eq!(
+                segments
+                    .iter()
+                    .map(|s| s.compressed.as_slice())
+                    .collect::<Vec<_>>()
+                    .concat(),
+                result.compressed_data.as_raw().to_vec()
+            );
+            assert_eq!(
+                segments.iter().map(|s| s.saved).sum::<i64>(),
+                result.saved_bytes()
+            );
+
+            // the trailer and the reversed order are left out of the segments
+            let opts = CompressOptions {
+                checksum: true,
+                reverse: true,
+                ..CompressOptions::default()
+            };
+            let mut cb = calldata(data.to_vec(), &[]);
+            assert_eq!(
+                cb.compress_with(&opts)
+                    .unwrap()
+                    .segments()
+                    .collect::<Vec<_>>(),
+                segments
+            );
+        }
+    }
+
+    #[test]
+    fn test_instruction_table() {
+        let data = Bytes::from_str(SMALL_CALLDATA).unwrap();
+        let result = compress(data, [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let table = result.table().to_string();
+        assert_eq!(table.lines().count(), result.description.len());
+        assert_snapshot("small_calldata_table", &table);
+
+        let data = [0xa9, 0x05, 0x9c, 0xbb, 0x00, 0x00];
+        let descriptions = [desc(0, 4, Method::Copy), desc(4, 4, Method::Zeros)];
+        assert_eq!(
+            InstructionTable::new(&data, &descriptions).to_string(),
+            "     0    4 copy      0xa9059cbb\n     4    4 zero-run  0x0000\n"
+        );
+        assert_eq!(InstructionTable::new(&data, &[]).to_string(), "");
+        assert_eq!(descriptions[0].to_string(), "copy 0..4");
+    }
+
+    #[test]
+    fn test_method_stats() {
+        // the second word is copied padded, 4 bytes sent for the 32 it expands to
+        let data = [
+            [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+            &[0x00; 28],
+            &[0x01, 0x02, 0x03, 0x04],
+            &[0x00; 12],
+            &[0x42; 20],
+        ]
+        .concat();
+        let result = compress(Bytes::from(data), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let copy = MethodTotals {
+            instructions: 3,
+            decompressed_bytes: 4 + 32 + 20,
+            compressed_bytes: 5 + 5 + 21,
+        };
+        let zeros = MethodTotals {
+            instructions: 1,
+            decompressed_bytes: 12,
+            compressed_bytes: 1,
+        };
+        let stats = result.method_stats();
+        assert_eq!(
+            stats,
+            BTreeMap::from([(Method::Zeros, zeros), (Method::Copy, copy)])
+        );
+        assert_eq!(
+            stats.values().map(|s| s.compressed_bytes).sum::<usize>(),
+            result.compressed_data.as_raw().len()
+        );
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            serde_json::json!({
+                "zero-run": { "instructions": 1, "decompressed_bytes": 12, "compressed_bytes": 1 },
+                "copy": { "instructions": 3, "decompressed_bytes": 56, "compressed_bytes": 31 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let tail = [0x11; 20];
+        let mut entry = Bytes32::default();
+        entry[12..].copy_from_slice(&tail);
+        let data = [
+            [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+            &[0x42; 32],
+            &[0x00; 31],
+            &[0x07],
+            &[0x00; 12],
+            &tail,
+            &[0x07],
+        ]
+        .concat();
+        let mut cb = calldata(data.clone(), &[[0x42; 32], entry]);
+        let explained = cb.explain(&CompressOptions::default()).unwrap();
+        let summary: Vec<_> = explained
+            .iter()
+            .map(|d| {
+                let desc = &d.description;
+                (desc.start_byte, desc.amount_bytes, desc.method, d.reason)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, 4, Method::Copy, DecisionReason::Only),
+                (4, 32, Method::DictShort, DecisionReason::LargerRange),
+                // a single zero, the other 30 pad the copy ending with 0x0700 instead
+                (36, 1, Method::Zeros, DecisionReason::Plan),
+                (37, 32, Method::Copy, DecisionReason::TieBreak),
+                (69, 11, Method::Zeros, DecisionReason::Plan),
+                (80, 20, Method::DictShort, DecisionReason::LargerRange),
+                (100, 1, Method::Copy, DecisionReason::Only),
+            ]
+        );
+        assert_eq!(explained[2].zero, CompressDataPower::new(31, 1));
+        // the address and the last 4 bytes of it
+        assert_eq!(
+            explained[5].storage,
+            [CompressDataPower::new(20, 2), CompressDataPower::new(4, 2)]
+        );
+        let descriptions: Vec<_> = explained.iter().map(|d| d.description.clone()).collect();
+        assert_eq!(descriptions, cb.compress().unwrap().description);
+
+        // the plan of the options, without the candidates of the methods they disable
+        let opts = CompressOptions {
+            enable_dict_short: false,
+            ..CompressOptions::default()
+        };
+        let restricted = cb.explain(&opts).unwrap();
+        let descriptions: Vec<_> = restricted.iter().map(|d| d.description.clone()).collect();
+        assert_eq!(descriptions, cb.compress_with(&opts).unwrap().description);
+        // the short references that fit a long one only
+        assert!(restricted
+            .iter()
+            .flat_map(|d| &d.storage)
+            .all(|power| power.compressed_size == 3));
+        assert_eq!(restricted[1].description.method, Method::DictLong);
+        let opts = CompressOptions {
+            planner: Some(Arc::new(GappyPlanner)),
+            ..CompressOptions::default()
+        };
+        assert_eq!(cb.explain(&opts), Err(CompressorError::InvalidPlan(1)));
+
+        // without the entry, nothing but copies for the address
+        let mut cb = calldata(data, &[[0x42; 32]]);
+        let explained = cb.explain(&CompressOptions::default()).unwrap();
+        let address = explained
+            .iter()
+            .find(|d| d.description.start_byte == 80)
+            .unwrap();
+        assert!(address.storage.is_empty());
+        assert_eq!(address.description.method, Method::Copy);
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_value(&explained[2]).unwrap(),
+            serde_json::json!({
+                "description": { "start_byte": 36, "amount_bytes": 1, "method": "zero-run" },
+                "power": { "decompressed_size": 1, "compressed_size": 1 },
+                "zero": { "decompressed_size": 31, "compressed_size": 1 },
+                "copy": { "decompressed_size": 32, "compressed_size": 1 },
+                "storage": [],
+                "reason": "plan",
+            })
+        );
+    }
+
+    // a plan leaving the second byte out
+    #[derive(Debug)]
+    struct GappyPlanner;
+
+    impl Planner for GappyPlanner {
+        fn plan(
+            &self,
+            _analysis: &[ByteInfo],
+            data: &[u8],
+            _opts: &CompressOptions,
+        ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+            Ok(vec![
+                desc(0, 1, Method::Copy),
+                desc(2, data.len() - 2, Method::Copy),
+            ])
+        }
+    }
+
+    #[test]
+    fn test_validate_descriptions() {
+        let mut cb = calldata(vec![0x11, 0x22, 0x33, 0x44], &[]);
+        let gappy = GappyPlanner
+            .plan(&[], cb.data(), &CompressOptions::default())
+            .unwrap();
+        // `zip` encodes it into a payload missing the byte
+        let zipped = cb.zip(&gappy).unwrap();
+        let dict = Dict::new(cb.wallet_addr, cb.contract_addr, &[]);
+        assert_eq!(
+            decompress_with(&zipped, &dict, &CompressOptions::default())
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            validate_descriptions(4, &gappy),
+            Err(CompressorError::InvalidPlan(1))
+        );
+        let opts = CompressOptions {
+            planner: Some(Arc::new(GappyPlanner)),
+            ..CompressOptions::default()
+        };
+        assert_eq!(
+            cb.compress_with(&opts).map(|result| result.compressed_data),
+            Err(CompressorError::InvalidPlan(1))
+        );
+
+        assert_eq!(validate_descriptions(0, &[]), Ok(()));
+        assert_eq!(
+            validate_descriptions(4, &[desc(0, 3, Method::Copy)]),
+            Err(CompressorError::InvalidPlan(1))
+        );
+        // overlap
+        assert_eq!(
+            validate_descriptions(4, &[desc(0, 3, Method::Copy), desc(2, 2, Method::Copy)]),
+            Err(CompressorError::InvalidPlan(1))
+        );
+        // longer than a single instruction
+        assert_eq!(
+            validate_descriptions(65, &[desc(0, 65, Method::Zeros)]),
+            Err(CompressorError::InvalidPlan(0))
+        );
+        assert_eq!(
+            validate_descriptions(33, &[desc(0, 33, Method::Copy)]),
+            Err(CompressorError::InvalidPlan(0))
+        );
+        assert_eq!(
+            validate_descriptions(64, &[desc(0, 64, Method::Zeros)]),
+            Ok(())
+        );
+        // no suffix of 5 bytes is referenced
+        assert_eq!(
+            validate_descriptions(5, &[desc(0, 5, Method::DictShort)]),
+            Err(CompressorError::InvalidPlan(0))
+        );
+        assert_eq!(
+            validate_descriptions(20, &[desc(0, 20, Method::DictLong)]),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use crate::{
+    compressor::{
+        optimal_plan, ByteInfo, Calldata, CompressDataDescription, CompressDataPower, Method,
+    },
+    errors::CompressorError,
+    options::CompressOptions,
+};
+
+/// Turns the analysis of every byte of the calldata into the instructions encoding it.
+/// A plan must tile the data from its first to its last byte, see `Calldata::validate_plan`.
+pub trait Planner: fmt::Debug + Send + Sync {
+    fn plan(
+        &self,
+        analysis: &[ByteInfo],
+        data: &[u8],
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError>;
+}
+
+/// The default planner: a DP over the parts ending at every byte, starting up to
+/// `CompressOptions::max_lookback` bytes before it, minimizing `CompressOptions::objective`.
+/// The parts are priced as 1inch/calldata-compressor prices them, whose output it reproduces
+/// byte for byte: a part ending with a zero run counts the whole part as decompressed by that
+/// run, on top of the instructions before it. The DP then overrates these parts, so the plan
+/// isn't the smallest one and `GreedyPlanner` sometimes beats it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptimalDpPlanner;
+
+impl Planner for OptimalDpPlanner {
+    fn plan(
+        &self,
+        analysis: &[ByteInfo],
+        data: &[u8],
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        Ok(optimal_plan(analysis, data, opts).descriptions)
+    }
+}
+
+/// A single left to right pass taking the candidate saving the most bytes at every position,
+/// the bytes no candidate saves anything on are gathered into copies of up to 32 bytes.
+/// Linear in the size of the data. Its outputs are usually larger than those of
+/// `OptimalDpPlanner`, not always, see there.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyPlanner;
+
+impl Planner for GreedyPlanner {
+    fn plan(
+        &self,
+        analysis: &[ByteInfo],
+        data: &[u8],
+        _opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        let mut plan = Vec::new();
+        let mut literal_start = None;
+        let mut i = 0;
+        while i < analysis.len() {
+            let info = &analysis[i];
+            // (power, method) of the candidate saving the most bytes
+            let mut best: Option<(CompressDataPower, Method)> = None;
+            let mut consider = |power: &CompressDataPower, method: Method| {
+                if power.decompressed_size > 0
+                    && best
+                        .as_ref()
+                        .is_none_or(|(best, _)| power.range() > best.range())
+                {
+                    best = Some((power.clone(), method));
+                }
+            };
+            if data[i] == 0x00 {
+                consider(&info.zero_compress, Method::Zeros);
+                // a copy only keeps its leading zeros when it covers a whole word
+                if info.copy_compress.decompressed_size == 32 {
+                    consider(&info.copy_compress, Method::Copy);
+                }
+            }
+            for power in &info.storage_compress {
+                consider(power, Method::storage(power.compressed_size));
+            }
+
+            match best {
+                Some((power, method)) if power.range() > 0 || data[i] == 0x00 => {
+                    flush_literal(&mut plan, &mut literal_start, i);
+                    plan.push(CompressDataDescription::new(
+                        i,
+                        power.decompressed_size,
+                        method,
+                    ));
+                    i += power.decompressed_size;
+                }
+                _ => {
+                    let start = *literal_start.get_or_insert(i);
+                    if i + 1 - start == 32 {
+                        flush_literal(&mut plan, &mut literal_start, i + 1);
+                    }
+                    i += 1;
+                }
+            }
+        }
+        flush_literal(&mut plan, &mut literal_start, analysis.len());
+        Ok(plan)
+    }
+}
+
+// ends the pending copy of the literal bytes preceding `end`
+fn flush_literal(
+    plan: &mut Vec<CompressDataDescription>,
+    literal_start: &mut Option<usize>,
+    end: usize,
+) {
+    if let Some(start) = literal_start.take() {
+        plan.push(CompressDataDescription::new(
+            start,
+            end - start,
+            Method::Copy,
+        ));
+    }
+}
+
+// the smallest encoding of `bytes` with the zero runs and copies `opts` enables, as
+// (size, descriptions)
+fn inline_plan(
+    bytes: &[u8],
+    start_byte: usize,
+    opts: &CompressOptions,
+) -> (usize, Vec<CompressDataDescription>) {
+    // best[k]: size and last description of the best encoding of the first `k` bytes
+    let mut best: Vec<Option<(usize, CompressDataDescription)>> = vec![None; bytes.len() + 1];
+    let mut best_size = vec![usize::MAX; bytes.len() + 1];
+    best_size[0] = 0;
+    for from in 0..bytes.len() {
+        if best_size[from] == usize::MAX {
+            continue;
+        }
+        let mut relax = |to: usize, size: usize, method: Method| {
+            if best_size[from] + size < best_size[to] {
+                best_size[to] = best_size[from] + size;
+                best[to] = Some((
+                    from,
+                    CompressDataDescription::new(start_byte + from, to - from, method),
+                ));
+            }
+        };
+        if bytes[from] == 0x00 && opts.enable_zero {
+            let zeros = bytes[from..].iter().take_while(|&&b| b == 0x00).count();
+            relax(from + core::cmp::min(zeros, 64), 1, Method::Zeros);
+        } else {
+            // a copy starting with a non-zero byte doesn't use the padding flag, nor does one of
+            // less than a word
+            let longest = if bytes[from] == 0x00 { 31 } else { 32 };
+            for to in from + 1..=core::cmp::min(from + longest, bytes.len()) {
+                relax(to, 1 + to - from, Method::Copy);
+            }
+        }
+    }
+
+    let mut descriptions = Vec::new();
+    let mut end = bytes.len();
+    while let Some((from, description)) = best[end].clone() {
+        descriptions.push(description);
+        end = from;
+    }
+    descriptions.reverse();
+    let mut size = best_size[bytes.len()];
+    // a whole word with leading zeros is a single padded copy
+    let leading_zeros = bytes.iter().take_while(|&&b| b == 0x00).count();
+    if opts.enable_copy_padding
+        && bytes.len() == 32
+        && leading_zeros > 0
+        && leading_zeros < 32
+        && 33 - leading_zeros < size
+    {
+        size = 33 - leading_zeros;
+        descriptions = vec![CompressDataDescription::new(start_byte, 32, Method::Copy)];
+    }
+    (size, descriptions)
+}
+
+// the gas-golf pass of `CompressOptions::gas_golf`: every storage reference that zero runs and
+// copies encode in as many bytes or fewer is replaced by them, saving its `SLOAD` without
+// growing the output. `encode_plan` then prefers warm indices for the references left.
+pub(crate) fn golf_plan(
+    data: &[u8],
+    plan: Vec<CompressDataDescription>,
+    opts: &CompressOptions,
+) -> Vec<CompressDataDescription> {
+    let mut golfed = Vec::with_capacity(plan.len());
+    for description in plan {
+        let reference_size = match description.method {
+            Method::DictShort => 2,
+            Method::DictLong => 3,
+            Method::Zeros | Method::Copy => {
+                golfed.push(description);
+                continue;
+            }
+        };
+        let bytes =
+            &data[description.start_byte..description.start_byte + description.amount_bytes];
+        let (size, inline) = inline_plan(bytes, description.start_byte, opts);
+        if size <= reference_size {
+            golfed.extend(inline);
+        } else {
+            golfed.push(description);
+        }
+    }
+
+    // the replacements may leave zero runs or copies that fit a single instruction side by side
+    let mut merged: Vec<CompressDataDescription> = Vec::with_capacity(golfed.len());
+    for description in golfed {
+        if let Some(last) = merged.last_mut() {
+            let mergeable = match (last.method, description.method) {
+                (Method::Zeros, Method::Zeros) => {
+                    last.amount_bytes + description.amount_bytes <= 64
+                }
+                (Method::Copy, Method::Copy) => {
+                    data[last.start_byte] != 0x00
+                        && last.amount_bytes + description.amount_bytes <= 32
+                }
+                _ => false,
+            };
+            if mergeable {
+                last.amount_bytes += description.amount_bytes;
+                continue;
+            }
+        }
+        merged.push(description);
+    }
+    merged
+}
+
+impl Calldata {
+    /// Plans the compression of the data with `planner`, `analyse` must have been run for the
+    /// current data and dictionary
+    pub fn plan_with(
+        &self,
+        planner: &dyn Planner,
+        opts: &CompressOptions,
+    ) -> Result<Vec<CompressDataDescription>, CompressorError> {
+        planner.plan(&self.bytes_info, &self.data, opts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::{decompress_with, Bytes32},
+        dict::Dict,
+        plan::Canonicality,
+    };
+
+    fn inputs() -> Vec<Bytes> {
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("test-data/calldata.json").unwrap())
+                .unwrap();
+        let big = fixture["uncompress"]
+            .as_str()
+            .unwrap()
+            .parse::<Bytes>()
+            .unwrap();
+        let pseudo_random: Vec<u8> = (0..70_u32).map(|i| (i * 37 + 5) as u8).collect();
+        vec![
+            Bytes::from(vec![0x00]),
+            Bytes::from(vec![0x00; 200]),
+            Bytes::from(vec![0x5a; 100]),
+            Bytes::from(pseudo_random),
+            Bytes::from([[0x01].as_slice(), &[0x00; 31], &[0x02], &[0x07; 33]].concat()),
+            big.slice(..800),
+        ]
+    }
+
+    #[test]
+    fn test_planners_produce_valid_plans() {
+        let mut word = [0x00; 32];
+        word[28..].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[word, [0x5a; 32]]);
+        let planners: [Arc<dyn Planner>; 2] = [Arc::new(OptimalDpPlanner), Arc::new(GreedyPlanner)];
+        let mut all_sizes = Vec::new();
+        for data in inputs() {
+            let mut cb =
+                Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+            cb.set_dict(&dict);
+            cb.analyse();
+            let mut sizes = Vec::new();
+            for planner in &planners {
+                let plan = cb
+                    .plan_with(planner.as_ref(), &CompressOptions::default())
+                    .unwrap();
+                assert_eq!(
+                    cb.validate_plan(&plan, Canonicality::Ignore),
+                    Ok(Vec::new()),
+                    "{planner:?} on {data}"
+                );
+
+                let opts = CompressOptions {
+                    planner: Some(planner.clone()),
+                    ..CompressOptions::default()
+                };
+                let result = cb.compress_analysed(&opts).unwrap();
+                assert_eq!(result.description, plan);
+                assert_eq!(
+                    decompress_with(&result.compressed_data, &dict, &opts).as_ref(),
+                    Ok(&data),
+                    "{planner:?} on {data}"
+                );
+                sizes.push(result.compressed_data.len());
+            }
+            all_sizes.push(sizes);
+        }
+        // neither planner always wins, these only pin the sizes they reach on the inputs
+        assert_eq!(
+            all_sizes,
+            [[1, 1], [4, 4], [8, 8], [73, 73], [39, 39], [274, 404]]
+        );
+    }
+
+    #[test]
+    fn test_gas_golf() {
+        let mut entry = [0x00; 32];
+        entry[28] = 0x07;
+        let mut entries = vec![[0x99; 32]; 4100];
+        entries.push(entry);
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &entries);
+        let data = Bytes::from(
+            [
+                [0x12, 0x34].as_slice(),
+                &[0x00; 17],
+                &entry[28..],
+                &[0x02, 0x00, 0x02],
+            ]
+            .concat(),
+        );
+        let mut cb = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+
+        // `07 00 00 00` is a 3-byte reference to index 4102, or a copy and a zero run
+        let plain = cb.compress().unwrap();
+        assert_eq!(
+            plain.description[2],
+            CompressDataDescription::new(19, 4, Method::DictLong)
+        );
+        let stats = plain.stats();
+        assert_eq!(stats.storage_references, 1);
+        assert_eq!((stats.cold_references, stats.warm_references), (1, 0));
+
+        let opts = CompressOptions {
+            gas_golf: true,
+            ..CompressOptions::default()
+        };
+        let golfed = cb.compress_with(&opts).unwrap();
+        assert_eq!(golfed.compressed_data.len(), plain.compressed_data.len());
+        // same size without reading the dictionary
+        let stats = golfed.stats();
+        assert_eq!(stats.storage_references, 0);
+        assert!(golfed.description.iter().all(|d| !d.method.is_storage()));
+        assert_eq!(
+            cb.validate_plan(&golfed.description, Canonicality::Reject),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            decompress_with(&golfed.compressed_data, &dict, &opts).as_ref(),
+            Ok(&data)
+        );
+    }
+
+    #[test]
+    fn test_gas_golf_warm_index() {
+        // the address is entry 2 on its own and the tail of entry 3
+        let address = [0x5a; 20];
+        let mut padded = [0x00; 32];
+        padded[12..].copy_from_slice(&address);
+        let mut tagged = [0x11; 32];
+        tagged[12..].copy_from_slice(&address);
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[padded, tagged]);
+        let data = Bytes::from([tagged.as_slice(), &address].concat());
+        let mut cb = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+
+        let plain = cb.compress().unwrap();
+        assert_eq!(
+            plain.description,
+            vec![
+                CompressDataDescription::new(0, 32, Method::DictShort),
+                CompressDataDescription::new(32, 20, Method::DictShort),
+            ]
+        );
+        let stats = plain.stats();
+        assert_eq!((stats.cold_references, stats.warm_references), (2, 0));
+
+        // the address is read again from entry 3 instead of entry 2
+        let opts = CompressOptions {
+            gas_golf: true,
+            ..CompressOptions::default()
+        };
+        let golfed = cb.compress_with(&opts).unwrap();
+        assert_eq!(golfed.description, plain.description);
+        assert_eq!(golfed.compressed_data.len(), plain.compressed_data.len());
+        let stats = golfed.stats();
+        assert_eq!((stats.cold_references, stats.warm_references), (1, 1));
+        assert_eq!(
+            decompress_with(&golfed.compressed_data, &dict, &opts).as_ref(),
+            Ok(&data)
+        );
+    }
+
+    #[test]
+    fn test_greedy_plan() {
+        let data = Bytes::from([[0x00; 40].as_slice(), &[0x07; 33]].concat());
+        let mut cb = Calldata::new(data, Bytes32::default(), Bytes32::default()).unwrap();
+        cb.init_dict(&[]);
+        cb.analyse();
+        assert_eq!(
+            cb.plan_with(&GreedyPlanner, &CompressOptions::default()),
+            Ok(vec![
+                CompressDataDescription::new(0, 40, Method::Zeros),
+                CompressDataDescription::new(40, 32, Method::Copy),
+                CompressDataDescription::new(72, 1, Method::Copy),
+            ])
+        );
+    }
+}
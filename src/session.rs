@@ -0,0 +1,183 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use alloy::primitives::Bytes;
+
+use crate::{
+    compressor::{Calldata, CompressResult},
+    dict::Dict,
+    errors::CompressorError,
+    options::CompressOptions,
+};
+
+// results of payloads compressed before, the oldest is evicted first once full
+#[derive(Debug, Default)]
+struct ResultCache {
+    capacity: usize,
+    results: HashMap<Bytes, CompressResult>,
+    order: VecDeque<Bytes>, // insertion order of `results`
+}
+
+impl ResultCache {
+    fn insert(&mut self, data: Bytes, result: CompressResult) {
+        if self.capacity == 0 || self.results.contains_key(&data) {
+            return;
+        }
+        if self.results.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+        self.order.push_back(data.clone());
+        self.results.insert(data, result);
+    }
+}
+
+/// A compressor owning its dictionary and options, built once and shared, e.g. in an `Arc`,
+/// across the threads of a service. `compress` takes `&self`; the optional result cache is
+/// synchronized internally.
+#[derive(Debug)]
+pub struct Compressor {
+    dict: Arc<Dict>,
+    opts: CompressOptions,
+    cache: Option<Mutex<ResultCache>>,
+}
+
+impl Compressor {
+    pub fn new(dict: Dict, opts: CompressOptions) -> Self {
+        Self {
+            dict: Arc::new(dict),
+            opts,
+            cache: None,
+        }
+    }
+
+    /// Keeps the results of the last `capacity` distinct payloads, returned again without
+    /// compressing when the same payload comes back
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Mutex::new(ResultCache {
+            capacity,
+            ..ResultCache::default()
+        }));
+        self
+    }
+
+    pub fn dict(&self) -> &Dict {
+        &self.dict
+    }
+
+    pub fn options(&self) -> &CompressOptions {
+        &self.opts
+    }
+
+    // number of payloads whose result is cached
+    pub fn cached(&self) -> usize {
+        self.cache
+            .as_ref()
+            .map_or(0, |cache| cache.lock().unwrap().results.len())
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<CompressResult, CompressorError> {
+        let data = Bytes::copy_from_slice(data);
+        if let Some(cache) = &self.cache {
+            if let Some(result) = cache.lock().unwrap().results.get(&data) {
+                return Ok(result.clone());
+            }
+        }
+
+        // compressed without holding the lock, concurrent misses of the same payload both
+        // compress it and the first to finish is cached
+        let mut calldata = Calldata::new(
+            data.clone(),
+            self.dict.wallet_addr(),
+            self.dict.contract_addr(),
+        )?;
+        let result = calldata.compress_with_dict(&self.dict, &self.opts)?;
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().insert(data, result.clone());
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::compressor::{decompress_with, Bytes32};
+
+    fn payloads() -> Vec<Vec<u8>> {
+        (0..16_u8)
+            .map(|i| {
+                let mut amount = Bytes32::default();
+                amount[31] = i;
+                [
+                    [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+                    &[0x11; 32],
+                    &amount,
+                    &[i | 1; 7],
+                ]
+                .concat()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compressor_concurrent() {
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &[[0x11; 32]]);
+        let opts = CompressOptions {
+            checksum: true,
+            ..CompressOptions::default()
+        };
+        let expected: Vec<CompressResult> = payloads()
+            .into_iter()
+            .map(|data| {
+                let mut cb =
+                    Calldata::new(Bytes::from(data), dict.wallet_addr(), dict.contract_addr())
+                        .unwrap();
+                cb.set_dict(&dict);
+                cb.compress_with(&opts).unwrap()
+            })
+            .collect();
+
+        for compressor in [
+            Compressor::new(dict.clone(), opts.clone()),
+            Compressor::new(dict.clone(), opts.clone()).with_cache(4),
+        ] {
+            let compressor = Arc::new(compressor);
+            std::thread::scope(|scope| {
+                for thread in 0..8 {
+                    let compressor = Arc::clone(&compressor);
+                    let expected = &expected;
+                    scope.spawn(move || {
+                        for round in 0..40 {
+                            let i = (thread * 7 + round) % expected.len();
+                            let result = compressor.compress(&payloads()[i]).unwrap();
+                            assert_eq!(result.compressed_data, expected[i].compressed_data);
+                            assert_eq!(result.description, expected[i].description);
+                        }
+                    });
+                }
+            });
+            assert!(compressor.cached() <= 4);
+        }
+
+        let compressor = Compressor::new(dict.clone(), opts.clone()).with_cache(2);
+        let result = compressor.compress(&payloads()[0]).unwrap();
+        assert_eq!(compressor.cached(), 1);
+        assert_eq!(
+            decompress_with(
+                &result.compressed_data,
+                compressor.dict(),
+                compressor.options()
+            ),
+            Ok(Bytes::from(payloads()[0].clone()))
+        );
+        compressor.compress(&payloads()[1]).unwrap();
+        compressor.compress(&payloads()[2]).unwrap();
+        assert_eq!(compressor.cached(), 2);
+    }
+}
@@ -1,5 +1,5 @@
 #[allow(unused_imports)]
-use std::str::FromStr;
+use core::str::FromStr;
 
 #[macro_export]
 macro_rules! assert_json_eq {
@@ -11,6 +11,40 @@ macro_rules! assert_json_eq {
     };
 }
 
+/// Compares `actual` with the snapshot `test-data/snapshots/<name>.snap`.
+///
+/// Snapshots are reviewed like code: when a change to the planner or to a report format is
+/// intended, rerun the failing tests with `UPDATE_SNAPSHOTS=1` to rewrite the snapshots and
+/// check the resulting `git diff` before committing them.
+#[cfg(test)]
+pub(crate) fn assert_snapshot(name: &str, actual: &str) {
+    let path = format!("test-data/snapshots/{name}.snap");
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all("test-data/snapshots").unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing snapshot {path}, run with UPDATE_SNAPSHOTS=1"));
+    if let Some((line, (e, a))) = expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .find(|(_, (e, a))| e != a)
+    {
+        panic!(
+            "snapshot {path} differs at line {}:\n- {e}\n+ {a}\nrerun with UPDATE_SNAPSHOTS=1 \
+             if the change is intended",
+            line + 1
+        );
+    }
+    assert_eq!(
+        expected.lines().count(),
+        actual.lines().count(),
+        "snapshot {path} length differs, rerun with UPDATE_SNAPSHOTS=1 if the change is intended"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,321 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use alloy::primitives::{Bytes, B256};
+use serde_json::{json, Value};
+
+use crate::{
+    compressor::{Bytes32, Calldata, CompressResult, CompressedCalldata, Method},
+    corpus::MethodTotals,
+    dict::Dict,
+    errors::CompressorError,
+    options::{CompressObjective, CompressOptions},
+};
+
+/// version of the test-vector format written by `TestVector::save`,
+/// the format is specified in `test-data/vectors/README.md`
+pub const TEST_VECTOR_VERSION: u64 = 1;
+
+/// Shape of a compression plan, enough to notice a different plan with the same output size
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlanSummary {
+    pub instructions: usize,
+    pub methods: BTreeMap<Method, MethodTotals>, // method -> totals
+}
+
+impl PlanSummary {
+    pub fn new(result: &CompressResult) -> Self {
+        Self {
+            instructions: result.description.len(),
+            methods: result.method_stats(),
+        }
+    }
+}
+
+/// A language-agnostic compression test case: the input, the dictionary and options it is
+/// compressed with, and the expected output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub name: String,
+    pub calldata: Bytes,
+    pub wallet_addr: Bytes32,
+    pub contract_addr: Bytes32,
+    pub dict: Vec<Bytes32>, // entries following the reserved wallet and contract slots
+    pub options: CompressOptions,
+    pub compressed: CompressedCalldata,
+    pub plan: PlanSummary,
+}
+
+fn invalid(reason: impl core::fmt::Display) -> CompressorError {
+    CompressorError::InvalidTestVector(reason.to_string())
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, CompressorError> {
+    value
+        .get(name)
+        .ok_or_else(|| invalid(format!("missing `{name}`")))
+}
+
+fn str_field<'a>(value: &'a Value, name: &str) -> Result<&'a str, CompressorError> {
+    field(value, name)?
+        .as_str()
+        .ok_or_else(|| invalid(format!("`{name}` is not a string")))
+}
+
+fn usize_field(value: &Value, name: &str) -> Result<usize, CompressorError> {
+    field(value, name)?
+        .as_u64()
+        .and_then(|n| usize::try_from(n).ok())
+        .ok_or_else(|| invalid(format!("`{name}` is not an unsigned integer")))
+}
+
+fn bool_field(value: &Value, name: &str) -> Result<bool, CompressorError> {
+    field(value, name)?
+        .as_bool()
+        .ok_or_else(|| invalid(format!("`{name}` is not a boolean")))
+}
+
+fn parse_bytes(hex: &str) -> Result<Bytes, CompressorError> {
+    Bytes::from_str(hex).map_err(invalid)
+}
+
+fn parse_bytes32(hex: &str) -> Result<Bytes32, CompressorError> {
+    B256::from_str(hex).map(|word| word.0).map_err(invalid)
+}
+
+fn objective_name(objective: CompressObjective) -> &'static str {
+    match objective {
+        CompressObjective::Bytes => "bytes",
+        CompressObjective::Gas => "gas",
+    }
+}
+
+impl TestVector {
+    /// Records `result`, obtained by compressing with `dict` and `options`, as a vector
+    pub fn from_result(
+        name: &str,
+        dict: &Dict,
+        options: &CompressOptions,
+        result: &CompressResult,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            calldata: result.uncompressed_data.clone(),
+            wallet_addr: dict.wallet_addr(),
+            contract_addr: dict.contract_addr(),
+            dict: dict.entries.iter().skip(2).copied().collect(),
+            options: options.clone(),
+            compressed: result.compressed_data.clone(),
+            plan: PlanSummary::new(result),
+        }
+    }
+
+    pub fn dict(&self) -> Dict {
+        Dict::new(self.wallet_addr, self.contract_addr, &self.dict)
+    }
+
+    /// Compresses the input of the vector, the outcome matches the vector if
+    /// `TestVector::from_result` of it equals `self`
+    pub fn run(&self) -> Result<CompressResult, CompressorError> {
+        let dict = self.dict();
+        let mut calldata =
+            Calldata::new(self.calldata.clone(), self.wallet_addr, self.contract_addr)?;
+        calldata.set_dict(&dict);
+        calldata.compress_with(&self.options)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let methods: serde_json::Map<String, Value> = self
+            .plan
+            .methods
+            .iter()
+            .map(|(&method, totals)| {
+                (
+                    method.name().to_string(),
+                    json!({
+                        "instructions": totals.instructions,
+                        "decompressed_bytes": totals.decompressed_bytes,
+                        "compressed_bytes": totals.compressed_bytes,
+                    }),
+                )
+            })
+            .collect();
+        let mut options = json!({
+            "checksum": self.options.checksum,
+            "reverse": self.options.reverse,
+            "objective": objective_name(self.options.objective),
+        });
+        if self.options.gas_golf {
+            options["gas_golf"] = json!(true);
+        }
+        json!({
+            "version": TEST_VECTOR_VERSION,
+            "name": self.name,
+            "calldata": self.calldata.to_string(),
+            "wallet": B256::from(self.wallet_addr).to_string(),
+            "contract": B256::from(self.contract_addr).to_string(),
+            "dict": self.dict.iter().map(|entry| B256::from(*entry).to_string()).collect::<Vec<_>>(),
+            "options": options,
+            "expected": {
+                "compressed": self.compressed.as_raw().to_string(),
+                "plan": {
+                    "instructions": self.plan.instructions,
+                    "methods": methods,
+                },
+            },
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, CompressorError> {
+        let version = field(value, "version")?.as_u64();
+        if version != Some(TEST_VECTOR_VERSION) {
+            return Err(invalid(format!("unsupported version {version:?}")));
+        }
+
+        let options = field(value, "options")?;
+        let objective = match str_field(options, "objective")? {
+            "bytes" => CompressObjective::Bytes,
+            "gas" => CompressObjective::Gas,
+            other => return Err(invalid(format!("unknown objective `{other}`"))),
+        };
+
+        let expected = field(value, "expected")?;
+        let plan = field(expected, "plan")?;
+        let mut methods = BTreeMap::new();
+        if let Some(names) = field(plan, "methods")?.as_object() {
+            for (name, totals) in names {
+                let method = Method::from_name(name)
+                    .ok_or_else(|| invalid(format!("unknown method `{name}`")))?;
+                methods.insert(
+                    method,
+                    MethodTotals {
+                        instructions: usize_field(totals, "instructions")?,
+                        decompressed_bytes: usize_field(totals, "decompressed_bytes")?,
+                        compressed_bytes: usize_field(totals, "compressed_bytes")?,
+                    },
+                );
+            }
+        }
+
+        Ok(Self {
+            name: str_field(value, "name")?.to_string(),
+            calldata: parse_bytes(str_field(value, "calldata")?)?,
+            wallet_addr: parse_bytes32(str_field(value, "wallet")?)?,
+            contract_addr: parse_bytes32(str_field(value, "contract")?)?,
+            dict: field(value, "dict")?
+                .as_array()
+                .ok_or_else(|| invalid("`dict` is not an array"))?
+                .iter()
+                .map(|entry| parse_bytes32(entry.as_str().unwrap_or_default()))
+                .collect::<Result<_, _>>()?,
+            options: CompressOptions {
+                checksum: bool_field(options, "checksum")?,
+                reverse: bool_field(options, "reverse")?,
+                objective,
+                gas_golf: match options.get("gas_golf") {
+                    Some(_) => bool_field(options, "gas_golf")?,
+                    None => false,
+                },
+                // options that don't change the output aren't part of the format
+                ..CompressOptions::default()
+            },
+            compressed: CompressedCalldata::from_raw(parse_bytes(str_field(
+                expected,
+                "compressed",
+            )?)?),
+            plan: PlanSummary {
+                instructions: usize_field(plan, "instructions")?,
+                methods,
+            },
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CompressorError> {
+        let contents = std::fs::read_to_string(path).map_err(invalid)?;
+        Self::from_json(&serde_json::from_str(&contents).map_err(invalid)?)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CompressorError> {
+        let contents = serde_json::to_string_pretty(&self.to_json()).map_err(invalid)?;
+        std::fs::write(path, contents + "\n").map_err(invalid)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    // runs every vector of `dir`, rewriting the expected outputs with `UPDATE_SNAPSHOTS=1`
+    fn run_vectors(dir: &str) -> usize {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in &paths {
+            let vector = TestVector::load(path).unwrap();
+            let result = vector.run().unwrap();
+            let actual =
+                TestVector::from_result(&vector.name, &vector.dict(), &vector.options, &result);
+            if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+                actual.save(path).unwrap();
+                continue;
+            }
+            assert_eq!(
+                actual.compressed,
+                vector.compressed,
+                "{}: compressed output differs",
+                path.display()
+            );
+            assert_eq!(actual.plan, vector.plan, "{}: plan differs", path.display());
+            assert_eq!(
+                crate::compressor::decompress_with(
+                    &result.compressed_data,
+                    &vector.dict(),
+                    &vector.options
+                ),
+                Ok(vector.calldata.clone())
+            );
+        }
+        paths.len()
+    }
+
+    #[test]
+    fn test_vectors() {
+        assert!(run_vectors("test-data/vectors") > 0);
+    }
+
+    #[test]
+    fn test_large_vectors() {
+        assert!(run_vectors("test-data/vectors/large") > 0);
+    }
+
+    #[test]
+    fn test_vector_json() {
+        let vector = TestVector::load("test-data/vectors/dict_checksum.json").unwrap();
+        assert!(vector.options.checksum);
+        assert_eq!(TestVector::from_json(&vector.to_json()), Ok(vector.clone()));
+        assert!(vector.to_json()["options"].get("gas_golf").is_none());
+
+        let mut golfed = vector.clone();
+        golfed.options.gas_golf = true;
+        assert_eq!(TestVector::from_json(&golfed.to_json()), Ok(golfed));
+
+        let mut json = vector.to_json();
+        json["version"] = json!(TEST_VECTOR_VERSION + 1);
+        assert!(matches!(
+            TestVector::from_json(&json),
+            Err(CompressorError::InvalidTestVector(_))
+        ));
+    }
+}
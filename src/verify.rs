@@ -0,0 +1,467 @@
+use alloc::{boxed::Box, format, vec::Vec};
+use core::fmt;
+
+use alloy::primitives::Bytes;
+
+use crate::{
+    compressor::{
+        decompress_with, instruction_size, storage_index, CompressDataDescription, CompressResult,
+        Method, CHECKSUM_SIZE,
+    },
+    dict::Dict,
+    errors::CompressorError,
+    options::CompressOptions,
+};
+
+/// size of the expected/actual windows captured around a mismatch
+pub const MISMATCH_WINDOW: usize = 32;
+
+/// First difference between the original calldata and what a decompressor produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub offset: usize, // first differing byte, or the shorter length if one is a prefix
+    pub window_start: usize, // offset of the first byte of `expected` and `actual`
+    pub expected: Bytes, // up to 32 bytes of the original data around `offset`
+    pub actual: Bytes, // the same window of the decompressed data
+    pub description: Option<CompressDataDescription>, // instruction that produced `offset`
+}
+
+impl Mismatch {
+    /// Locates the first difference between `expected` and `actual`, attributing it to the
+    /// description of `plan` covering it. Returns `None` if both are identical.
+    pub fn find(expected: &[u8], actual: &[u8], plan: &[CompressDataDescription]) -> Option<Self> {
+        let offset = match expected.iter().zip(actual).position(|(e, a)| e != a) {
+            Some(offset) => offset,
+            None if expected.len() == actual.len() => return None,
+            None => core::cmp::min(expected.len(), actual.len()),
+        };
+        let window_start = offset.saturating_sub(MISMATCH_WINDOW / 2);
+        let window = |data: &[u8]| {
+            let start = core::cmp::min(window_start, data.len());
+            let end = core::cmp::min(window_start + MISMATCH_WINDOW, data.len());
+            Bytes::copy_from_slice(&data[start..end])
+        };
+        Some(Self {
+            offset,
+            window_start,
+            expected: window(expected),
+            actual: window(actual),
+            description: plan
+                .iter()
+                .find(|d| d.start_byte <= offset && offset < d.start_byte + d.amount_bytes)
+                .cloned(),
+        })
+    }
+}
+
+impl fmt::Display for Mismatch {
+    // side-by-side hex rows of 16 bytes, rows containing a difference are marked with `<`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mismatch at byte {}", self.offset)?;
+        if let Some(d) = &self.description {
+            write!(
+                f,
+                " produced by {} {}..{}",
+                d.method,
+                d.start_byte,
+                d.start_byte + d.amount_bytes
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<8}  {:<47}  actual", "offset", "expected")?;
+
+        let row = |data: &[u8], start: usize| {
+            data.get(start..core::cmp::min(start + 16, data.len()))
+                .unwrap_or_default()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let len = core::cmp::max(self.expected.len(), self.actual.len());
+        for start in (0..len).step_by(16) {
+            let expected = row(&self.expected, start);
+            let actual = row(&self.actual, start);
+            let marker = if expected == actual { "" } else { " <" };
+            writeln!(
+                f,
+                "{:08x}  {expected:<47}  {actual:<47}{marker}",
+                self.window_start + start
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// First instruction whose expansion by a decompressor differs from the original data, see
+/// `CompressResult::locate_divergence`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub instruction: usize, // index of the instruction in the plan
+    pub description: CompressDataDescription, // the instruction as planned
+    pub stream_offset: usize, // of the instruction in the instruction stream
+    pub dict_index: Option<usize>, // index encoded by a storage reference
+    pub expected: Bytes,    // original bytes the instruction stands for
+    pub actual: Bytes,      // produced instead, the value read for a reference
+}
+
+/// Instruction taking another number of bytes in the instruction stream than its method
+/// accounts for, or a `power` whose compressed size isn't that of the stream, see
+/// `CompressResult::verify_power`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerMismatch {
+    pub instruction: Option<usize>, // index in the plan, `None` for the total of `power`
+    pub description: Option<CompressDataDescription>, // the instruction as planned
+    pub stream_offset: usize,       // of the instruction in the instruction stream
+    pub expected: usize,            // bytes accounted for
+    pub actual: usize,              // bytes found in the instruction stream
+}
+
+impl PowerMismatch {
+    /// bytes found beyond the ones accounted for, negative when fewer were emitted
+    pub fn drift(&self) -> i64 {
+        self.actual as i64 - self.expected as i64
+    }
+}
+
+impl fmt::Display for PowerMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.instruction, &self.description) {
+            (Some(instruction), Some(d)) => write!(
+                f,
+                "instruction {instruction} ({d}) at stream offset {}",
+                self.stream_offset
+            )?,
+            _ => write!(f, "instruction stream")?,
+        }
+        write!(
+            f,
+            ": {} bytes accounted, {} emitted ({:+})",
+            self.expected,
+            self.actual,
+            self.drift()
+        )
+    }
+}
+
+// bytes the instruction of `description` accounts for, a word copy emitted with its padding bit
+// only sending the bytes after the leading zeros of `original`
+fn accounted_size(
+    description: &CompressDataDescription,
+    original: &[u8],
+    tag: Option<u8>,
+) -> usize {
+    match description.method {
+        Method::Zeros => 1,
+        Method::Copy if tag.is_some_and(|tag| tag >> 5 == 0b011) => {
+            1 + original.len() - original.iter().position(|&b| b != 0x00).unwrap_or(0)
+        }
+        Method::Copy => 1 + description.amount_bytes,
+        Method::DictShort => 2,
+        Method::DictLong => 3,
+    }
+}
+
+impl CompressResult {
+    /// Recomputes the size of every instruction from the bytes `compressed_data` actually holds
+    /// and checks it against the size its method accounts for, then checks the total of
+    /// `power` against the instruction stream. Returns the first instruction that disagrees, by
+    /// how many bytes, or the total if only it does. `compress` runs it in debug builds.
+    pub fn verify_power(&self) -> Result<(), PowerMismatch> {
+        let stream = self.instruction_stream();
+        let mut stream_offset = 0;
+        for (instruction, description) in self.description.iter().enumerate() {
+            let end = core::cmp::min(
+                description.start_byte + description.amount_bytes,
+                self.uncompressed_data.len(),
+            );
+            let original = self
+                .uncompressed_data
+                .get(description.start_byte..end)
+                .unwrap_or_default();
+            let tag = stream.get(stream_offset).copied();
+            let expected = accounted_size(description, original, tag);
+            let actual = instruction_size(&stream, stream_offset);
+            if expected != actual {
+                return Err(PowerMismatch {
+                    instruction: Some(instruction),
+                    description: Some(description.clone()),
+                    stream_offset,
+                    expected,
+                    actual,
+                });
+            }
+            stream_offset += actual;
+        }
+        if self.power.compressed_size != stream.len() {
+            return Err(PowerMismatch {
+                instruction: None,
+                description: None,
+                stream_offset: 0,
+                expected: self.power.compressed_size,
+                actual: stream.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Walks the plan instruction by instruction, comparing the expansion each one stands for
+    /// with the bytes `actual` holds at the same offsets, `actual` being what a decompressor,
+    /// such as the on-chain one through `eth_call`, produced from `compressed_data`.
+    /// Returns the first instruction that diverges, or `None` if `actual` is the original data.
+    pub fn locate_divergence(&self, actual: &[u8]) -> Option<Divergence> {
+        let stream = self.instruction_stream();
+        let mut stream_offset = 0;
+        for (instruction, description) in self.description.iter().enumerate() {
+            let range = description.start_byte..description.start_byte + description.amount_bytes;
+            let window = |data: &[u8]| {
+                let end = core::cmp::min(range.end, data.len());
+                Bytes::copy_from_slice(data.get(range.start..end).unwrap_or_default())
+            };
+            let expected = window(&self.uncompressed_data);
+            let observed = window(actual);
+            if expected != observed {
+                let dict_index = storage_index(&stream, stream_offset);
+                return Some(Divergence {
+                    instruction,
+                    description: description.clone(),
+                    stream_offset,
+                    dict_index,
+                    expected,
+                    actual: observed,
+                });
+            }
+            stream_offset += instruction_size(&stream, stream_offset);
+        }
+        // the instructions agree, but the decompressor produced trailing bytes
+        (actual.len() > self.uncompressed_data.len()).then(|| Divergence {
+            instruction: self.description.len(),
+            description: CompressDataDescription::new(
+                self.uncompressed_data.len(),
+                actual.len() - self.uncompressed_data.len(),
+                Method::Copy,
+            ),
+            stream_offset,
+            dict_index: None,
+            expected: Bytes::new(),
+            actual: Bytes::copy_from_slice(&actual[self.uncompressed_data.len()..]),
+        })
+    }
+
+    /// Decompresses `compressed_data` against `dict` and checks that it reproduces
+    /// `uncompressed_data`, failing with `CompressorError::RoundtripMismatch` otherwise.
+    pub fn verify(&self, dict: &Dict) -> Result<(), CompressorError> {
+        let opts = CompressOptions {
+            checksum: self.checksum,
+            reverse: self.reverse,
+            ..CompressOptions::default()
+        };
+        let decompressed = match decompress_with(&self.compressed_data, dict, &opts) {
+            Ok(decompressed) => decompressed,
+            // the trailer only tells that something differs, locate it instead
+            Err(CompressorError::ChecksumMismatch) => {
+                let stream = &self.compressed_data[..self.compressed_data.len() - CHECKSUM_SIZE];
+                let opts = CompressOptions {
+                    checksum: false,
+                    ..opts
+                };
+                decompress_with(stream, dict, &opts)?
+            }
+            Err(e) => return Err(e),
+        };
+        match Mismatch::find(&self.uncompressed_data, &decompressed, &self.description) {
+            Some(mismatch) => Err(CompressorError::RoundtripMismatch(Box::new(mismatch))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compressor::{Bytes32, Calldata, CompressedCalldata},
+        utils::assert_snapshot,
+    };
+
+    #[test]
+    fn test_verify() {
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let amount: Vec<u8> = (1..=32).collect();
+        let data = Bytes::from([selector.as_slice(), &[0x00; 32], &amount].concat());
+        let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[]);
+        let mut cb = Calldata::new(data, dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        let result = cb.compress().unwrap();
+        assert_eq!(result.verify(&dict), Ok(()));
+
+        // corrupt the 10th literal byte of the copy of `amount`
+        let copy = result
+            .description
+            .iter()
+            .find(|d| d.method == Method::Copy && d.start_byte == 36)
+            .unwrap()
+            .clone();
+        let mut compressed = result.compressed_data.to_vec();
+        let literal = compressed.len() - 32;
+        compressed[literal + 9] ^= 0xff;
+        let corrupted = CompressResult {
+            compressed_data: CompressedCalldata::from_raw(Bytes::from(compressed)),
+            ..result
+        };
+
+        let Err(CompressorError::RoundtripMismatch(mismatch)) = corrupted.verify(&dict) else {
+            panic!("corruption not detected");
+        };
+        assert_eq!(mismatch.offset, 36 + 9);
+        assert_eq!(mismatch.description, Some(copy));
+        assert_eq!(mismatch.window_start, 36 + 9 - 16);
+        assert_eq!(mismatch.expected.len(), 32);
+        assert_ne!(mismatch.expected, mismatch.actual);
+
+        let diff = mismatch.to_string();
+        assert!(diff.starts_with("mismatch at byte 45 produced by copy 36..68\n"));
+        let rows: Vec<&str> = diff.lines().skip(2).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("0000001d  "));
+        assert!(!rows[0].ends_with('<'));
+        assert!(rows[1].starts_with("0000002d  0a ") && rows[1].ends_with(" <"));
+        assert_snapshot("verify_mismatch", &diff);
+    }
+
+    #[test]
+    fn test_locate_divergence() {
+        let entries: Vec<Bytes32> = (0..8_u8).map(|i| [i + 0x10; 32]).collect();
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &entries);
+        let data = Bytes::from(
+            [
+                [0x01, 0x02].as_slice(),
+                &entries[1],
+                &[0x00; 40],
+                &entries[5],
+                &[0x03],
+            ]
+            .concat(),
+        );
+        let mut cb = Calldata::new(data.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        for opts in [
+            CompressOptions::default(),
+            CompressOptions {
+                checksum: true,
+                reverse: true,
+                ..CompressOptions::default()
+            },
+        ] {
+            let result = cb.compress_with(&opts).unwrap();
+            assert_eq!(result.locate_divergence(&data), None);
+
+            // the deployed dictionary holds something else at index 7 (entry 5 after the
+            // reserved slots)
+            let mut onchain = dict.clone();
+            onchain.entries[7] = [0x77; 32];
+            let stream = &result.compressed_data
+                [..result.compressed_data.len() - if opts.checksum { CHECKSUM_SIZE } else { 0 }];
+            let actual = decompress_with(
+                stream,
+                &onchain,
+                &CompressOptions {
+                    checksum: false,
+                    ..opts.clone()
+                },
+            )
+            .unwrap();
+
+            let divergence = result.locate_divergence(&actual).unwrap();
+            assert_eq!(divergence.description.method, Method::DictShort);
+            assert_eq!(divergence.description.start_byte, 2 + 32 + 40);
+            assert_eq!(divergence.dict_index, Some(7));
+            assert_eq!(divergence.expected, Bytes::from(entries[5].to_vec()));
+            assert_eq!(divergence.actual, Bytes::from(vec![0x77; 32]));
+            assert_eq!(
+                result.description[divergence.instruction],
+                divergence.description
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_power() {
+        let entries: Vec<Bytes32> = (0..4_u8).map(|i| [i + 0x10; 32]).collect();
+        let dict = Dict::new([0xaa; 32], [0xbb; 32], &entries);
+        let amount: Vec<u8> = (1..=20).collect();
+        let data = Bytes::from(
+            [
+                [0x01, 0x02].as_slice(),
+                &entries[1],
+                &[0x00; 12],
+                &amount,
+                &[0x00; 40],
+                &[0x03],
+            ]
+            .concat(),
+        );
+        let mut cb = Calldata::new(data, dict.wallet_addr(), dict.contract_addr()).unwrap();
+        cb.set_dict(&dict);
+        for opts in [
+            CompressOptions::default(),
+            CompressOptions {
+                checksum: true,
+                reverse: true,
+                enable_copy_padding: false,
+                ..CompressOptions::default()
+            },
+        ] {
+            assert_eq!(cb.compress_with(&opts).unwrap().verify_power(), Ok(()));
+        }
+
+        // a 3-byte reference accounted for as a 2-byte one
+        let result = cb.compress().unwrap();
+        let (instruction, reference) = result
+            .description
+            .iter()
+            .enumerate()
+            .find(|(_, d)| d.method == Method::DictShort)
+            .unwrap();
+        let mut drifted = result.clone();
+        drifted.description[instruction].method = Method::DictLong;
+        let mismatch = drifted.verify_power().unwrap_err();
+        assert_eq!(mismatch.instruction, Some(instruction));
+        assert_eq!(mismatch.stream_offset, 1 + 2);
+        assert_eq!((mismatch.expected, mismatch.actual), (3, 2));
+        assert_eq!(mismatch.drift(), -1);
+        assert_eq!(
+            mismatch.to_string(),
+            format!(
+                "instruction {instruction} (storage-3 {}..{}) at stream offset 3: 3 bytes \
+                 accounted, 2 emitted (-1)",
+                reference.start_byte,
+                reference.start_byte + 32
+            )
+        );
+
+        // the instructions agree, the total doesn't
+        let mut drifted = result;
+        drifted.power.compressed_size += 1;
+        let mismatch = drifted.verify_power().unwrap_err();
+        assert_eq!(mismatch.instruction, None);
+        assert_eq!(mismatch.drift(), -1);
+        assert_eq!(
+            mismatch.to_string(),
+            format!(
+                "instruction stream: {} bytes accounted, {} emitted (-1)",
+                mismatch.expected, mismatch.actual
+            )
+        );
+    }
+
+    #[test]
+    fn test_mismatch_find_length() {
+        assert_eq!(Mismatch::find(&[1, 2], &[1, 2], &[]), None);
+        let mismatch = Mismatch::find(&[1, 2, 3], &[1, 2], &[]).unwrap();
+        assert_eq!(mismatch.offset, 2);
+        assert_eq!(mismatch.expected, Bytes::from(vec![1, 2, 3]));
+        assert_eq!(mismatch.actual, Bytes::from(vec![1, 2]));
+        assert_eq!(mismatch.description, None);
+    }
+}
@@ -0,0 +1,30 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::hex_str::compress_hex_summary;
+
+/// `compress` for JavaScript, e.g. a browser extension previewing the savings of a call before
+/// it's signed. Takes hex strings as `crate::compress_hex` does and returns an object
+/// `{ compressed, ratio, saved_bytes }`, see `HexCompression`. Errors are thrown as their
+/// message.
+#[wasm_bindgen]
+pub fn compress_hex(
+    calldata_hex: &str,
+    wallet_hex: &str,
+    contract_hex: &str,
+    dict_hex: Vec<String>,
+) -> Result<JsValue, JsValue> {
+    let dict: Vec<&str> = dict_hex.iter().map(String::as_str).collect();
+    let summary = compress_hex_summary(calldata_hex, wallet_hex, contract_hex, &dict)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let object = Object::new();
+    Reflect::set(&object, &"compressed".into(), &summary.compressed.into())?;
+    Reflect::set(&object, &"ratio".into(), &summary.ratio.into())?;
+    // a number rather than the `BigInt` of an `i64`
+    Reflect::set(
+        &object,
+        &"saved_bytes".into(),
+        &(summary.saved_bytes as f64).into(),
+    )?;
+    Ok(object.into())
+}
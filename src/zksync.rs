@@ -0,0 +1,82 @@
+use crate::cost::CostModel;
+
+/// Pricing of zkSync Era, which charges the bytes published to L1 as pubdata rather than
+/// calldata gas: every byte of calldata sent as pubdata costs `gas_per_pubdata`, zero or not.
+/// The state diffs of the transaction go through the compression of the L1 batch and are left
+/// out, only the calldata differs between the original and the compressed transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZkSyncCostModel {
+    pub gas_per_pubdata: u64,
+}
+
+impl ZkSyncCostModel {
+    pub fn new(gas_per_pubdata: u64) -> Self {
+        Self { gas_per_pubdata }
+    }
+}
+
+impl CostModel for ZkSyncCostModel {
+    fn data_cost(&self, bytes: &[u8]) -> u64 {
+        (bytes.len() as u64).saturating_mul(self.gas_per_pubdata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Bytes;
+
+    use super::*;
+    use crate::{
+        compressor::compress,
+        cost::{CostReport, MainnetCalldata},
+    };
+
+    const SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+    #[test]
+    fn test_zksync_cost_model() {
+        let model = ZkSyncCostModel::new(800);
+        let word = [[0x00; 31].as_slice(), &[0x01]].concat();
+        let calldata = Bytes::from([SELECTOR.as_slice(), &word.repeat(10)].concat());
+        let result = compress(calldata.clone(), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        let report = result.evaluate(&model);
+        assert_eq!(
+            report,
+            CostReport::new(
+                calldata.len() as u64 * 800,
+                result.compressed_data.len() as u64 * 800
+            )
+        );
+        assert!(!report.is_net_loss());
+        assert!(result.is_worth_it(&model, 0));
+        // zero bytes cost as much as the others, the savings are the saved bytes
+        assert_eq!(report.saved, result.saved_bytes() * 800);
+        assert!(report.saved > result.evaluate(&MainnetCalldata).saved);
+
+        // a payload the format only inflates
+        let random: Vec<u8> = (0..64_u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8 | 0x01)
+            .collect();
+        let inflated = compress(Bytes::from(random), [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        assert!(inflated.saved_bytes() < 0);
+        assert!(inflated.evaluate(&model).is_net_loss());
+    }
+
+    #[test]
+    fn test_zksync_free_pubdata() {
+        let model = ZkSyncCostModel::default();
+        let calldata = Bytes::from([SELECTOR.as_slice(), &[0x00; 320]].concat());
+        let result = compress(calldata, [0xaa; 32], [0xbb; 32], &[]).unwrap();
+        // nothing to save, nothing lost either
+        let report = result.evaluate(&model);
+        assert_eq!(report, CostReport::new(0, 0));
+        assert!(!report.is_net_loss());
+        // but the decompressor still runs
+        assert!(!result.is_worth_it(&model, 0));
+        let saved = result.saved_bytes() as u64;
+        assert_eq!(
+            result.is_worth_it(&ZkSyncCostModel::new(1), 0),
+            saved > result.decompression_gas_estimate
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Allocation budget of compressing the large fixture.
+//!
+//! Most past slowness of the planner was allocation churn (per-candidate `Vec` clones), so the
+//! number of allocations and the peak of live heap bytes are tracked by a counting global
+//! allocator and checked against the budgets below. When a change legitimately needs more, run
+//! `ALLOC_BUDGET_REPORT=1 cargo test --test alloc_budget -- --nocapture` to print the measured
+//! numbers and raise the budget in the same PR, explaining why.
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{Bytes32, Calldata};
+use counting_alloc::{track, CountingAlloc, Usage};
+
+/// allocations, reallocations included, of compressing `test-data/calldata.json`
+const ALLOCATION_BUDGET: usize = 330_000;
+/// peak of live heap bytes while compressing it
+const PEAK_BYTES_BUDGET: usize = 12 * 1024 * 1024;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+fn test_compress_allocation_budget() {
+    let contents = std::fs::read_to_string("test-data/calldata.json").unwrap();
+    let fixture: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    let calldata: Bytes = fixture["uncompress"].as_str().unwrap().parse().unwrap();
+    let mut cb = Calldata::new(calldata, Bytes32::default(), Bytes32::default()).unwrap();
+    cb.init_dict(&[Bytes32::default()]);
+
+    let (
+        result,
+        Usage {
+            allocations,
+            peak_bytes,
+        },
+    ) = track(|| cb.compress().unwrap());
+    if std::env::var_os("ALLOC_BUDGET_REPORT").is_some() {
+        println!("allocations: {allocations}, peak bytes: {peak_bytes}");
+    }
+    assert!(!result.compressed_data.is_empty());
+    assert!(
+        allocations <= ALLOCATION_BUDGET,
+        "{allocations} allocations, over the budget of {ALLOCATION_BUDGET}"
+    );
+    assert!(
+        peak_bytes <= PEAK_BYTES_BUDGET,
+        "peak of {peak_bytes} bytes, over the budget of {PEAK_BYTES_BUDGET}"
+    );
+}
@@ -0,0 +1,41 @@
+//! The compressor API is reachable from the crate root, as downstream users import it.
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{
+    compress, compress_hex, decompress, Calldata, CompressResult, CompressorError,
+};
+
+#[test]
+fn test_crate_root() {
+    let wallet = [0xaa; 32];
+    let contract = [0xbb; 32];
+    let calldata = Bytes::from([[0xa9, 0x05, 0x9c, 0xbb].as_slice(), &[0xaa; 32]].concat());
+
+    let result: CompressResult = compress(calldata.clone(), wallet, contract, &[]).unwrap();
+    assert_eq!(
+        decompress(result.compressed_data.as_raw(), wallet, contract, &[]),
+        Ok(calldata.clone())
+    );
+
+    let mut cb = Calldata::new(calldata.clone(), wallet, contract).unwrap();
+    cb.init_dict(&[]);
+    assert_eq!(
+        cb.compress().unwrap().compressed_data,
+        result.compressed_data
+    );
+
+    // the hex wrapper in place of the old string-based `Calldata`
+    assert_eq!(
+        compress_hex(
+            &calldata.to_string(),
+            &Bytes::from(wallet).to_string(),
+            &Bytes::from(contract).to_string(),
+            &[]
+        ),
+        Ok(result.compressed_data.as_raw().to_string())
+    );
+    assert_eq!(
+        compress_hex("0xa9059cb", "0x", "0x", &[]),
+        Err(CompressorError::OddHexLength(7))
+    );
+}
@@ -0,0 +1,60 @@
+//! Compressing an ERC-20 `transfer(address,uint256)` call, the most common calldata there is.
+
+use alloy::primitives::{address, Bytes, U256};
+use calldata_compressor::{decompress_with, Bytes32, Calldata, CompressOptions, Dict, Method};
+
+#[test]
+fn test_erc20_transfer() {
+    let recipient = address!("d8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+    let amount = U256::from(10).pow(U256::from(18)); // 1 token with 18 decimals
+
+    // abi words are left padded to 32 bytes
+    let mut recipient_word = Bytes32::default();
+    recipient_word[12..].copy_from_slice(recipient.as_slice());
+    let calldata = Bytes::from(
+        [
+            [0xa9, 0x05, 0x9c, 0xbb].as_slice(),
+            &recipient_word,
+            &amount.to_be_bytes::<32>(),
+        ]
+        .concat(),
+    );
+
+    // a frequent recipient is worth a dictionary entry, stored as the padded abi word so the
+    // whole word is referenced at once (its last 20 bytes would match the bare address too)
+    let dict = Dict::new(Bytes32::default(), Bytes32::default(), &[recipient_word]);
+    let mut cb = Calldata::new(calldata.clone(), dict.wallet_addr(), dict.contract_addr()).unwrap();
+    cb.set_dict(&dict);
+    let result = cb.compress().unwrap();
+
+    // the selector is copied and the recipient word becomes a 2-byte reference to entry 2
+    assert_eq!(
+        &result.compressed_data[..7],
+        &[0x43, 0xa9, 0x05, 0x9c, 0xbb, 0x80, 0x02]
+    );
+    let recipient_ref = &result.description[1];
+    assert_eq!(
+        (
+            recipient_ref.start_byte,
+            recipient_ref.amount_bytes,
+            recipient_ref.method
+        ),
+        (4, 32, Method::DictShort)
+    );
+    // only the significant bytes of the amount are copied, its zeros are zero runs
+    for description in result.description.iter().skip(2) {
+        let bytes = &calldata[description.start_byte..][..description.amount_bytes];
+        match description.method {
+            Method::Zeros => assert!(bytes.iter().all(|&b| b == 0x00)),
+            Method::Copy => assert_ne!(bytes[0], 0x00),
+            method => panic!("unexpected method {method} in the amount"),
+        }
+    }
+    assert!(result.compressed_data.len() <= 17);
+    assert_eq!(calldata.len(), 68);
+
+    assert_eq!(
+        decompress_with(&result.compressed_data, &dict, &CompressOptions::default()),
+        Ok(calldata)
+    );
+}
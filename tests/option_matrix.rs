@@ -0,0 +1,165 @@
+//! Roundtrips a fixed set of inputs through a curated matrix of options and dictionaries, so that
+//! interactions between options that their own tests don't combine can't break decompression.
+
+use std::sync::Arc;
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{
+    decompress_with, AnalysisCtx, Bytes32, Calldata, Candidate, CompressOptions, CompressorError,
+    Detector, Dict, DictEntry, Method, MAX_SHORT_INDEX,
+};
+use smallvec::SmallVec;
+
+const SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const WALLET: Bytes32 = [0xaa; 32];
+const CONTRACT: Bytes32 = [0xbb; 32];
+
+// references every full abi word found in the dictionary, on top of the built-in analysis
+#[derive(Debug)]
+struct WordDetector;
+
+impl Detector for WordDetector {
+    fn candidates(
+        &self,
+        data: &[u8],
+        pos: usize,
+        ctx: &AnalysisCtx<'_>,
+    ) -> SmallVec<[Candidate; 4]> {
+        match data
+            .get(pos..pos + 32)
+            .and_then(|word| ctx.dict.index_of(word))
+        {
+            Some(index) if index <= MAX_SHORT_INDEX as usize => {
+                SmallVec::from_elem(Candidate::new(Method::DictShort, 32, 2), 1)
+            }
+            _ => SmallVec::new(),
+        }
+    }
+}
+
+fn word(byte: u8) -> Bytes32 {
+    let mut word = Bytes32::default();
+    word[12..].fill(byte);
+    word
+}
+
+fn inputs() -> Vec<(&'static str, Bytes)> {
+    let pseudo_random: Vec<u8> = (0..97_u32).map(|i| (i * 73 + 19) as u8 | 1).collect();
+    let fixture: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("test-data/calldata.json").unwrap()).unwrap();
+    vec![
+        ("single byte", Bytes::from(vec![0x42])),
+        ("zeros", Bytes::from(vec![0x00; 130])),
+        ("pseudo random", Bytes::from(pseudo_random)),
+        (
+            "transfer",
+            Bytes::from([SELECTOR.as_slice(), &word(0x11), &[0x00; 31], &[0x64]].concat()),
+        ),
+        (
+            "repeated words",
+            Bytes::from(
+                [
+                    SELECTOR.as_slice(),
+                    &word(0x22),
+                    &word(0x11),
+                    &word(0x22),
+                    &[0x05; 32],
+                ]
+                .concat(),
+            ),
+        ),
+        (
+            "fixture head",
+            fixture["uncompress"]
+                .as_str()
+                .unwrap()
+                .parse::<Bytes>()
+                .unwrap()[..600]
+                .to_vec()
+                .into(),
+        ),
+    ]
+}
+
+fn dicts() -> Vec<(&'static str, Dict)> {
+    let primary = Dict::new(WALLET, CONTRACT, &[word(0x11)]);
+    let fallback = Dict::new(WALLET, CONTRACT, &vec![word(0x22); 4200]);
+    vec![
+        ("reserved slots only", Dict::new(WALLET, CONTRACT, &[])),
+        (
+            "padded words",
+            Dict::new(WALLET, CONTRACT, &[word(0x11), word(0x22)]),
+        ),
+        (
+            "variable length",
+            Dict::with_entries(
+                WALLET,
+                CONTRACT,
+                &[
+                    DictEntry::new(SELECTOR).unwrap(),
+                    DictEntry::new([0x22; 20]).unwrap(),
+                ],
+            ),
+        ),
+        ("chained", Dict::chain(&primary, &fallback)),
+    ]
+}
+
+fn options() -> Vec<CompressOptions> {
+    let mut options = Vec::new();
+    for checksum in [false, true] {
+        for reverse in [false, true] {
+            options.push(CompressOptions {
+                checksum,
+                reverse,
+                ..CompressOptions::default()
+            });
+        }
+    }
+    options.push(CompressOptions {
+        forbid_inflation: true,
+        ..CompressOptions::default()
+    });
+    options.push(CompressOptions {
+        checksum: true,
+        reverse: true,
+        forbid_inflation: true,
+        extra_detectors: vec![Arc::new(WordDetector)],
+        ..CompressOptions::default()
+    });
+    options
+}
+
+#[test]
+fn test_option_matrix_roundtrip() {
+    let mut runs = 0;
+    for (input_name, data) in inputs() {
+        for (dict_name, dict) in dicts() {
+            for opts in options() {
+                let case = format!("{input_name} / {dict_name} / {opts:?}");
+                let mut cb = Calldata::new(data.clone(), WALLET, CONTRACT).unwrap();
+                cb.set_dict(&dict);
+                let result = match cb.compress_with(&opts) {
+                    Err(CompressorError::NoCompression {
+                        uncompressed,
+                        compressed,
+                    }) => {
+                        assert!(opts.forbid_inflation, "{case}");
+                        assert!(compressed >= uncompressed, "{case}");
+                        continue;
+                    }
+                    result => result.unwrap_or_else(|e| panic!("{case}: {e}")),
+                };
+                assert_eq!(result.verify(&dict), Ok(()), "{case}");
+                assert_eq!(
+                    decompress_with(&result.compressed_data, &dict, &opts).as_ref(),
+                    Ok(&data),
+                    "{case}"
+                );
+                runs += 1;
+            }
+        }
+    }
+    // most combinations compress, inflating ones are skipped only under `forbid_inflation`
+    assert!(runs > 100, "only {runs} combinations roundtripped");
+}
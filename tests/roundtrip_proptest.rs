@@ -0,0 +1,80 @@
+//! `decompress(compress(x)) == x` for generated inputs: arbitrary bytes, long zero runs, bytes
+//! that are never zero, and payloads stitched from the tails of dictionary entries.
+
+use alloy::primitives::Bytes;
+use calldata_compressor::{compress, decompress, Bytes32};
+use proptest::prelude::*;
+
+// the suffix lengths a storage reference can read
+const TAIL_LENGTHS: [usize; 4] = [32, 31, 20, 4];
+
+// a piece of a generated payload
+#[derive(Debug, Clone)]
+enum Piece {
+    Zeros(usize),
+    Bytes(Vec<u8>),
+    // `TAIL_LENGTHS[len]` bytes of the end of entry `entry` of the dictionary, wallet and
+    // contract included
+    Tail { entry: usize, len: usize },
+}
+
+fn piece() -> impl Strategy<Value = Piece> {
+    prop_oneof![
+        (1..200_usize).prop_map(Piece::Zeros),
+        prop::collection::vec(any::<u8>(), 1..40).prop_map(Piece::Bytes),
+        (any::<usize>(), 0..TAIL_LENGTHS.len()).prop_map(|(entry, len)| Piece::Tail { entry, len }),
+    ]
+}
+
+fn roundtrip(data: Vec<u8>, wallet: Bytes32, contract: Bytes32, dict: &[Bytes32]) {
+    let data = Bytes::from(data);
+    let result = compress(data.clone(), wallet, contract, dict).unwrap();
+    assert_eq!(
+        decompress(result.compressed_data.as_raw(), wallet, contract, dict),
+        Ok(data)
+    );
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_arbitrary(
+        data in prop::collection::vec(any::<u8>(), 0..400),
+        wallet in any::<Bytes32>(),
+        contract in any::<Bytes32>(),
+        dict in prop::collection::vec(any::<Bytes32>(), 0..8),
+    ) {
+        roundtrip(data, wallet, contract, &dict);
+    }
+
+    #[test]
+    fn roundtrip_nonzero(
+        data in prop::collection::vec(1..=u8::MAX, 0..400),
+        wallet in any::<Bytes32>(),
+        contract in any::<Bytes32>(),
+    ) {
+        roundtrip(data, wallet, contract, &[]);
+    }
+
+    #[test]
+    fn roundtrip_pieces(
+        pieces in prop::collection::vec(piece(), 0..24),
+        wallet in any::<Bytes32>(),
+        contract in any::<Bytes32>(),
+        dict in prop::collection::vec(any::<Bytes32>(), 0..8),
+    ) {
+        let entries: Vec<Bytes32> =
+            [wallet, contract].into_iter().chain(dict.iter().copied()).collect();
+        let mut data = Vec::new();
+        for piece in pieces {
+            match piece {
+                Piece::Zeros(len) => data.resize(data.len() + len, 0x00),
+                Piece::Bytes(bytes) => data.extend(bytes),
+                Piece::Tail { entry, len } => {
+                    let entry = &entries[entry % entries.len()];
+                    data.extend(&entry[32 - TAIL_LENGTHS[len]..]);
+                }
+            }
+        }
+        roundtrip(data, wallet, contract, &dict);
+    }
+}